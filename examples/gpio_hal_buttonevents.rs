@@ -0,0 +1,52 @@
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::buttons::{Buttons, Timings};
+use microbit::hal::delay::Delay;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        let mut delay = Delay::new(p.TIMER0);
+
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        let mut buttons = Buttons::new(
+            gpio.pin17.into_floating_input(),
+            gpio.pin26.into_floating_input(),
+            Timings::default(),
+        );
+
+        let _ = write!(
+            tx,
+            "\n\rWelcome to the button events demo. Click, double-click or hold A, B or both.\n\r",
+        );
+
+        /* Poll the buttons every 10ms; the delay is close enough to keep time */
+        let mut now_ms: u32 = 0;
+        loop {
+            buttons.update(now_ms);
+            while let Some(event) = buttons.next_event() {
+                let _ = write!(tx, "{:?}\n\r", event);
+            }
+            delay.delay_ms(10_u32);
+            now_ms = now_ms.wrapping_add(10);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
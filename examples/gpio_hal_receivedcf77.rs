@@ -43,12 +43,8 @@ fn main() -> ! {
             /* Configure DCF77 receiver GPIO as input */
             let pin = gpio.pin16.into_floating_input();
 
-            /* Configure RX and TX pins accordingly */
-            let tx = gpio.pin24.into_push_pull_output().into();
-            let rx = gpio.pin25.into_floating_input().into();
-
-            /* Set up serial port using the prepared pins */
-            let (mut tx, _) = serial::Serial::uart0(p.UART0, tx, rx, BAUD115200).split();
+            /* Initialise serial port on the micro:bit */
+            let (mut tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
 
             let _ = tx.write_str("\n\rWelcome to the DCF77 decoder demo.\n\r");
             let _ = tx.write_str("If you are within reach of a DCF77 radio clock signal and have a DCF77 receiver connected\n\r");
@@ -0,0 +1,102 @@
+//! Prints accelerometer readings over the serial port, using the
+//! accelerometer's data-ready interrupt.
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::gpiote::{Gpiote, Polarity};
+use microbit::hal::i2c;
+use microbit::hal::nrf51::interrupt;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+use microbit::sensors::accelerometer::{Accelerometer, DataRate, Range};
+use microbit::NVIC;
+
+use cortex_m::interrupt::Mutex;
+use cortex_m_rt::entry;
+
+use core::cell::RefCell;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+static PIN_EVENTS: Mutex<RefCell<Option<Gpiote>>> = Mutex::new(RefCell::new(None));
+static DATA_READY: AtomicBool = AtomicBool::new(false);
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        /* Configure the internal I2C bus, and whichever accelerometer the board has for 10
+         * readings a second */
+        let scl = gpio.pin0.into_open_drain_input().into();
+        let sda = gpio.pin30.into_open_drain_input().into();
+        let i2c = i2c::I2c::i2c1(p.TWI1, sda, scl);
+        let mut accelerometer = match Accelerometer::new(i2c) {
+            Ok(accelerometer) => accelerometer,
+            Err(e) => {
+                let _ = write!(tx, "No accelerometer found: {:?}\n\r", e);
+                loop {
+                    continue;
+                }
+            }
+        };
+        let _ = write!(tx, "Found {:?}\n\r", accelerometer.model());
+        let _ = accelerometer.configure(Range::G2, DataRate::Hz10);
+        let _ = accelerometer.set_data_ready_interrupt(true);
+
+        /* Generate an event when the accelerometer's interrupt pin (GPIO 28) goes high */
+        let (mut gpiote, channels) = Gpiote::new(p.GPIOTE);
+        let _data_ready = gpiote.listen(
+            channels.ch0,
+            gpio.pin28.into_floating_input(),
+            Polarity::LoToHi,
+            data_ready,
+        );
+        cortex_m::interrupt::free(move |cs| {
+            *PIN_EVENTS.borrow(cs).borrow_mut() = Some(gpiote);
+        });
+        unsafe {
+            NVIC::unmask(microbit::Interrupt::GPIOTE);
+        }
+
+        /* Read the first value so the interrupt pin goes low again */
+        let _ = accelerometer.read();
+
+        loop {
+            /* (thumbv6m has no atomic swap, but a reading missed here shows up next time) */
+            if DATA_READY.load(Ordering::Relaxed) {
+                DATA_READY.store(false, Ordering::Relaxed);
+                if let Ok(reading) = accelerometer.read() {
+                    let _ = write!(
+                        tx,
+                        "x: {}, y: {}, z: {}\n\r",
+                        reading.x, reading.y, reading.z
+                    );
+                }
+            }
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
+
+fn data_ready() {
+    DATA_READY.store(true, Ordering::Relaxed);
+}
+
+#[interrupt]
+fn GPIOTE() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(gpiote) = PIN_EVENTS.borrow(cs).borrow_mut().as_mut() {
+            gpiote.handle_interrupt();
+        }
+    });
+}
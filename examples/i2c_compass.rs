@@ -0,0 +1,72 @@
+//! Calibrates the magnetometer while the board is turned around, then
+//! prints the compass heading over the serial port.
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::hal::delay::Delay;
+use microbit::hal::i2c;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+use microbit::sensors::magnetometer::{HardIronCalibrator, Magnetometer};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        let mut delay = Delay::new(p.TIMER0);
+
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        /* Configure the internal I2C bus, and find the magnetometer */
+        let scl = gpio.pin0.into_open_drain_input().into();
+        let sda = gpio.pin30.into_open_drain_input().into();
+        let i2c = i2c::I2c::i2c1(p.TWI1, sda, scl);
+        let mut magnetometer = match Magnetometer::new(i2c) {
+            Ok(magnetometer) => magnetometer,
+            Err(e) => {
+                let _ = write!(tx, "No magnetometer found: {:?}\n\r", e);
+                loop {
+                    continue;
+                }
+            }
+        };
+
+        /* Collect readings until every axis has been turned right round */
+        let _ = write!(tx, "Turn the board around in all directions...\n\r");
+        let mut calibrator = HardIronCalibrator::new();
+        loop {
+            if let Ok(raw) = magnetometer.read_raw() {
+                calibrator.add(raw);
+            }
+            let spread = calibrator.spread();
+            if spread.x > 600 && spread.y > 600 && spread.z > 600 {
+                break;
+            }
+            delay.delay_ms(20_u32);
+        }
+        let calibration = calibrator.calibration();
+        let _ = write!(tx, "Calibrated: {:?}\n\r", calibration);
+        magnetometer.set_calibration(calibration);
+
+        /* Print the heading twice a second; hold the board level */
+        loop {
+            if let Ok(heading) = magnetometer.heading() {
+                let _ = write!(tx, "heading: {}\n\r", heading as u32);
+            }
+            delay.delay_ms(500_u32);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
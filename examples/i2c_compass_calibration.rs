@@ -0,0 +1,187 @@
+//! Calibrates the compass by asking for the board to be tilted to fill the
+//! screen, then shows which way is north (using the accelerometer to correct
+//! for tilt).
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::display::Render;
+use microbit::hal::delay::Delay;
+use microbit::hal::i2c;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+use microbit::led;
+use microbit::sensors::accelerometer::Accelerometer;
+use microbit::sensors::compass::{tilt_compensated_heading, CompassCalibrator};
+use microbit::sensors::magnetometer::Magnetometer;
+use microbit::sensors::{BoardRevision, Sensor};
+use microbit::shared_i2c::SharedBus;
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+
+/* Arrows pointing north, for headings around the compass in 45° steps */
+const ARROWS: [[[u8; 5]; 5]; 8] = [
+    [
+        [0, 0, 1, 0, 0],
+        [0, 1, 1, 1, 0],
+        [1, 0, 1, 0, 1],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+    ],
+    [
+        [1, 1, 1, 0, 0],
+        [1, 1, 0, 0, 0],
+        [1, 0, 1, 0, 0],
+        [0, 0, 0, 1, 0],
+        [0, 0, 0, 0, 1],
+    ],
+    [
+        [0, 0, 1, 0, 0],
+        [0, 1, 0, 0, 0],
+        [1, 1, 1, 1, 1],
+        [0, 1, 0, 0, 0],
+        [0, 0, 1, 0, 0],
+    ],
+    [
+        [0, 0, 0, 0, 1],
+        [0, 0, 0, 1, 0],
+        [1, 0, 1, 0, 0],
+        [1, 1, 0, 0, 0],
+        [1, 1, 1, 0, 0],
+    ],
+    [
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [1, 0, 1, 0, 1],
+        [0, 1, 1, 1, 0],
+        [0, 0, 1, 0, 0],
+    ],
+    [
+        [1, 0, 0, 0, 0],
+        [0, 1, 0, 0, 0],
+        [0, 0, 1, 0, 1],
+        [0, 0, 0, 1, 1],
+        [0, 0, 1, 1, 1],
+    ],
+    [
+        [0, 0, 1, 0, 0],
+        [0, 0, 0, 1, 0],
+        [1, 1, 1, 1, 1],
+        [0, 0, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+    ],
+    [
+        [0, 0, 1, 1, 1],
+        [0, 0, 0, 1, 1],
+        [0, 0, 1, 0, 1],
+        [0, 1, 0, 0, 0],
+        [1, 0, 0, 0, 0],
+    ],
+];
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        let mut delay = Delay::new(p.TIMER0);
+
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        /* Display */
+        let row1 = gpio.pin13.into_push_pull_output();
+        let row2 = gpio.pin14.into_push_pull_output();
+        let row3 = gpio.pin15.into_push_pull_output();
+        let col1 = gpio.pin4.into_push_pull_output();
+        let col2 = gpio.pin5.into_push_pull_output();
+        let col3 = gpio.pin6.into_push_pull_output();
+        let col4 = gpio.pin7.into_push_pull_output();
+        let col5 = gpio.pin8.into_push_pull_output();
+        let col6 = gpio.pin9.into_push_pull_output();
+        let col7 = gpio.pin10.into_push_pull_output();
+        let col8 = gpio.pin11.into_push_pull_output();
+        let col9 = gpio.pin12.into_push_pull_output();
+        let mut leds = led::Display::new(
+            col1, col2, col3, col4, col5, col6, col7, col8, col9, row1, row2, row3,
+        );
+
+        /* Configure the internal I2C bus, and give each sensor a handle to it */
+        let scl = gpio.pin0.into_open_drain_input().into();
+        let sda = gpio.pin30.into_open_drain_input().into();
+        let bus = SharedBus::critical_section(i2c::I2c::i2c1(p.TWI1, sda, scl));
+        let sensors = (
+            Accelerometer::new(bus.acquire()),
+            Magnetometer::new(bus.acquire()),
+        );
+        let (mut accelerometer, mut magnetometer) = match sensors {
+            (Ok(accelerometer), Ok(magnetometer)) => (accelerometer, magnetometer),
+            _ => {
+                let _ = write!(tx, "Sensors not found\n\r");
+                loop {
+                    continue;
+                }
+            }
+        };
+        let revision = BoardRevision::from(accelerometer.model());
+
+        /* Fill the screen: the visited LEDs stay lit, and the dot blinks. The
+         * dot follows the tilt in the board's axes; the calibration is for
+         * the magnetometer's own axes */
+        let mut calibrator = CompassCalibrator::new();
+        let mut blink = false;
+        while !calibrator.is_complete() {
+            if let (Ok(tilt), Ok(field)) = (accelerometer.read(), magnetometer.read_raw()) {
+                calibrator.update(tilt.to_board_axes(revision, Sensor::Accelerometer), field);
+            }
+            let image = calibrator.image();
+            let mut pixels = [[0; 5]; 5];
+            for (y, row) in pixels.iter_mut().enumerate() {
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    let brightness = image.brightness_at(x, y);
+                    *pixel = (brightness > 0 && (blink || brightness < 9)) as u8;
+                }
+            }
+            leds.display(&mut delay, pixels, 100);
+            blink = !blink;
+        }
+
+        match calibrator.calibration() {
+            Some(calibration) => {
+                let _ = write!(tx, "Calibrated: {:?}\n\r", calibration);
+                magnetometer.set_calibration(calibration);
+            }
+            None => {
+                let _ = write!(tx, "Calibration failed; reset to try again\n\r");
+                loop {
+                    continue;
+                }
+            }
+        }
+
+        /* Point north, however the board is tilted. The sensors are mounted
+         * differently, so both readings are turned into the board's axes
+         * first */
+        loop {
+            let heading = match (accelerometer.read(), magnetometer.read()) {
+                (Ok(tilt), Ok(field)) => tilt_compensated_heading(
+                    tilt.to_board_axes(revision, Sensor::Accelerometer),
+                    field.to_board_axes(revision, Sensor::Magnetometer),
+                ),
+                _ => None,
+            };
+            if let Some(heading) = heading {
+                let arrow = ((heading as u32 + 22) / 45 % 8) as usize;
+                leds.display(&mut delay, ARROWS[arrow], 100);
+            }
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
@@ -0,0 +1,63 @@
+//! Prints gestures (shake, tilt, face up and so on) over the serial port.
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::hal::delay::Delay;
+use microbit::hal::i2c;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+use microbit::sensors::accelerometer::{Accelerometer, DataRate, Range};
+use microbit::sensors::gesture::{GestureRecogniser, Thresholds};
+use microbit::sensors::{BoardRevision, Sensor};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        let mut delay = Delay::new(p.TIMER0);
+
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        /* Configure the internal I2C bus, and the accelerometer for 50 readings a second;
+         * the 8g range is needed to see 3g and 6g impacts */
+        let scl = gpio.pin0.into_open_drain_input().into();
+        let sda = gpio.pin30.into_open_drain_input().into();
+        let i2c = i2c::I2c::i2c1(p.TWI1, sda, scl);
+        let mut accelerometer = match Accelerometer::new(i2c) {
+            Ok(accelerometer) => accelerometer,
+            Err(e) => {
+                let _ = write!(tx, "No accelerometer found: {:?}\n\r", e);
+                loop {
+                    continue;
+                }
+            }
+        };
+        let _ = accelerometer.configure(Range::G8, DataRate::Hz50);
+        let revision = BoardRevision::from(accelerometer.model());
+
+        /* The recogniser expects readings in the board's axes */
+        let mut gestures = GestureRecogniser::new(Thresholds::default());
+        loop {
+            if let Ok(reading) = accelerometer.read() {
+                let reading = reading.to_board_axes(revision, Sensor::Accelerometer);
+                gestures.update_with(reading, |gesture| {
+                    let _ = write!(tx, "{:?}\n\r", gesture);
+                });
+            }
+            delay.delay_ms(20_u32);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
@@ -46,12 +46,8 @@ fn main() -> ! {
             /* Split GPIO pins */
             let gpio = p.GPIO.split();
 
-            /* Configure RX and TX pins accordingly */
-            let tx = gpio.pin24.into_push_pull_output().into();
-            let rx = gpio.pin25.into_floating_input().into();
-
-            /* Set up serial port using the prepared pins */
-            let (mut tx, _) = serial::Serial::uart0(p.UART0, tx, rx, BAUD115200).split();
+            /* Initialise serial port on the micro:bit */
+            let (mut tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
 
             let _ = write!(&mut tx, "\n\rWelcome to the magnetometer reader!\n\r");
             *TX.borrow(cs).borrow_mut() = Some(tx);
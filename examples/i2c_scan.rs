@@ -0,0 +1,58 @@
+//! Recovers the internal I2C bus if a sensor is holding it, then prints the
+//! address of each device which responds.
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::hal::hi_res_timer::TimerFrequency;
+use microbit::hal::i2c;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+use microbit::hal::timer::CountDownTimer;
+use microbit::i2c_bus::{self, I2cBus};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        /* Configure the internal I2C bus, with TIMER2 to time out transfers
+         * which a device holds up */
+        let scl = gpio.pin0.into_open_drain_input().into();
+        let sda = gpio.pin30.into_open_drain_input().into();
+        let timer = CountDownTimer::new(p.TIMER2, TimerFrequency::Freq62500Hz);
+        let mut bus = I2cBus::new(i2c::I2c::i2c1(p.TWI1, sda, scl), timer);
+
+        if !bus.is_idle() {
+            let _ = write!(tx, "Bus is stuck, recovering: ");
+            match bus.recover() {
+                Ok(()) => {
+                    let _ = write!(tx, "done\n\r");
+                }
+                Err(e) => {
+                    let _ = write!(tx, "{:?}\n\r", e);
+                }
+            }
+        }
+
+        /* Try every address */
+        let found = i2c_bus::scan(&mut bus);
+        let _ = write!(tx, "Found {} devices:\n\r", found.len());
+        for address in found.iter() {
+            let _ = write!(tx, "  {:#04x}\n\r", address);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
@@ -0,0 +1,80 @@
+//! Draws on the display using `embedded-graphics` primitives.
+//!
+//! Requires the `embedded-graphics` feature:
+//!
+//! ```text
+//! cargo run --example led_graphics --features embedded-graphics
+//! ```
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::Peripherals;
+use cortex_m_rt::entry;
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Circle, Line, PrimitiveStyle};
+
+use microbit::display::graphics::Brightness;
+use microbit::display::image::GreyscaleImage;
+use microbit::display::{self, Display, DisplayPins, MicrobitDisplayTimer, MicrobitFrame};
+use microbit::hal::nrf51::{interrupt, TIMER1};
+use microbit::hal::prelude::*;
+
+static DISPLAY_PINS: Mutex<RefCell<Option<DisplayPins>>> = Mutex::new(RefCell::new(None));
+static DISPLAY_TIMER: Mutex<RefCell<Option<MicrobitDisplayTimer<TIMER1>>>> =
+    Mutex::new(RefCell::new(None));
+static DISPLAY: Mutex<RefCell<Option<Display<MicrobitFrame>>>> = Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        /* Draw a dim circle crossed by a bright line */
+        let mut image = GreyscaleImage::blank();
+        let _ = Circle::new(Point::new(0, 0), 5)
+            .into_styled(PrimitiveStyle::with_stroke(Brightness::new(3), 1))
+            .draw(&mut image);
+        let _ = Line::new(Point::new(0, 4), Point::new(4, 0))
+            .into_styled(PrimitiveStyle::with_stroke(Brightness::MAX, 1))
+            .draw(&mut image);
+        let frame = MicrobitFrame::from(&image);
+
+        cortex_m::interrupt::free(move |cs| {
+            let gpio = p.GPIO.split();
+            let mut pins = microbit::display_pins!(gpio);
+            let mut timer = MicrobitDisplayTimer::new(p.TIMER1);
+            display::initialise_display(&mut timer, &mut pins);
+
+            let mut display = Display::new();
+            display.set_frame(&frame);
+
+            *DISPLAY_PINS.borrow(cs).borrow_mut() = Some(pins);
+            *DISPLAY_TIMER.borrow(cs).borrow_mut() = Some(timer);
+            *DISPLAY.borrow(cs).borrow_mut() = Some(display);
+        });
+        if let Some(mut cp) = Peripherals::take() {
+            cp.NVIC.enable(microbit::Interrupt::TIMER1);
+            microbit::NVIC::unpend(microbit::Interrupt::TIMER1);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
+
+#[interrupt]
+fn TIMER1() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(timer) = DISPLAY_TIMER.borrow(cs).borrow_mut().as_mut() {
+            if let Some(pins) = DISPLAY_PINS.borrow(cs).borrow_mut().as_mut() {
+                if let Some(d) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+                    display::handle_display_event(d, timer, pins);
+                }
+            }
+        }
+    });
+}
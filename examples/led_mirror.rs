@@ -0,0 +1,144 @@
+//! Animates a heart on the display, and mirrors each new image to the serial
+//! port as text.
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
+use cortex_m_rt::entry;
+
+use microbit::buffered_uart::BufferedUart;
+use microbit::display::image::GreyscaleImage;
+use microbit::display::mirror::{FrameMirror, MirroredDisplay};
+use microbit::display::{self, Display, DisplayPins, MicrobitDisplayTimer};
+use microbit::hal::lo_res_timer::{LoResTimer, FREQ_16HZ};
+use microbit::hal::nrf51::{interrupt, RTC0, TIMER1};
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+use microbit::NVIC;
+
+fn heart_image(inner_brightness: u8) -> GreyscaleImage {
+    let b = inner_brightness;
+    GreyscaleImage::new(&[
+        [0, 7, 0, 7, 0],
+        [7, b, 7, b, 7],
+        [7, b, b, b, 7],
+        [0, 7, b, 7, 0],
+        [0, 0, 7, 0, 0],
+    ])
+}
+
+// We use TIMER1 to drive the display, and RTC0 to update the animation.
+// Each new image is written to the serial port at most twice a second. The
+// mirror shares the display's resource, so it writes into a BufferedUart
+// rather than waiting for the serial port.
+
+static DISPLAY_PINS: Mutex<RefCell<Option<DisplayPins>>> = Mutex::new(RefCell::new(None));
+static ANIM_TIMER: Mutex<RefCell<Option<LoResTimer<RTC0>>>> = Mutex::new(RefCell::new(None));
+static DISPLAY_TIMER: Mutex<RefCell<Option<MicrobitDisplayTimer<TIMER1>>>> =
+    Mutex::new(RefCell::new(None));
+static DISPLAY: Mutex<RefCell<Option<MirroredDisplay<BufferedUart>>>> =
+    Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    static mut RX_BUFFER: [u8; 16] = [0; 16];
+    static mut TX_BUFFER: [u8; 256] = [0; 256];
+
+    if let Some(p) = microbit::Peripherals::take() {
+        // Starting the low-frequency clock (needed for RTC to work)
+        p.CLOCK.tasks_lfclkstart.write(|w| unsafe { w.bits(1) });
+        while p.CLOCK.events_lfclkstarted.read().bits() == 0 {}
+        p.CLOCK.events_lfclkstarted.reset();
+
+        let gpio = p.GPIO.split();
+        let mut pins = microbit::display_pins!(gpio);
+        let mut timer = MicrobitDisplayTimer::new(p.TIMER1);
+        display::initialise_display(&mut timer, &mut pins);
+
+        /* Initialise serial port on the micro:bit, and hand it to the buffered driver */
+        let (tx, rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+        let uart = BufferedUart::new(tx, rx, RX_BUFFER, TX_BUFFER);
+        let mirror = FrameMirror::new(uart, 8);
+
+        let mut rtc0 = LoResTimer::new(p.RTC0);
+        // 62.5ms period
+        rtc0.set_frequency(FREQ_16HZ);
+        rtc0.enable_tick_event();
+        rtc0.enable_tick_interrupt();
+        rtc0.start();
+
+        cortex_m::interrupt::free(move |cs| {
+            *DISPLAY_PINS.borrow(cs).borrow_mut() = Some(pins);
+            *ANIM_TIMER.borrow(cs).borrow_mut() = Some(rtc0);
+            *DISPLAY_TIMER.borrow(cs).borrow_mut() = Some(timer);
+            *DISPLAY.borrow(cs).borrow_mut() = Some(MirroredDisplay::new(Display::new(), mirror));
+        });
+        unsafe {
+            NVIC::unmask(microbit::Interrupt::UART0);
+            NVIC::unmask(microbit::Interrupt::RTC0);
+            NVIC::unmask(microbit::Interrupt::TIMER1);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
+
+#[interrupt]
+fn TIMER1() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(timer) = DISPLAY_TIMER.borrow(cs).borrow_mut().as_mut() {
+            if let Some(pins) = DISPLAY_PINS.borrow(cs).borrow_mut().as_mut() {
+                if let Some(d) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+                    display::handle_display_event(d.display_mut(), timer, pins);
+                }
+            }
+        }
+    });
+}
+
+#[interrupt]
+fn UART0() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(d) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+            d.mirror_mut().sink_mut().handle_interrupt();
+        }
+    });
+}
+
+#[interrupt]
+fn RTC0() {
+    static mut STEP: u8 = 0;
+    static mut TICKS: u32 = 0;
+
+    cortex_m::interrupt::free(|cs| {
+        if let Some(rtc) = ANIM_TIMER.borrow(cs).borrow_mut().as_mut() {
+            rtc.clear_tick_event();
+        }
+    });
+    *TICKS = TICKS.wrapping_add(1);
+
+    let inner_brightness = match *STEP {
+        0..=8 => 9 - *STEP,
+        9..=12 => 0,
+        _ => unreachable!(),
+    };
+    let image = heart_image(inner_brightness);
+
+    /* Showing the image and mirroring it are one call, so every image on
+     * the display is mirrored */
+    cortex_m::interrupt::free(|cs| {
+        if let Some(d) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+            let _ = d.set_frame(&image, *TICKS);
+        }
+    });
+
+    *STEP += 1;
+    if *STEP == 13 {
+        *STEP = 0
+    };
+}
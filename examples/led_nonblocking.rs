@@ -9,9 +9,10 @@ use cortex_m::peripheral::Peripherals;
 use cortex_m_rt::entry;
 
 use microbit::display::image::GreyscaleImage;
-use microbit::display::{self, Display, Frame, MicrobitDisplayTimer, MicrobitFrame};
+use microbit::display::{self, Display, DisplayPins, Frame, MicrobitDisplayTimer, MicrobitFrame};
 use microbit::hal::lo_res_timer::{LoResTimer, FREQ_16HZ};
-use microbit::hal::nrf51::{interrupt, GPIO, RTC0, TIMER1};
+use microbit::hal::nrf51::{interrupt, RTC0, TIMER1};
+use microbit::hal::prelude::*;
 
 fn heart_image(inner_brightness: u8) -> GreyscaleImage {
     let b = inner_brightness;
@@ -27,9 +28,10 @@ fn heart_image(inner_brightness: u8) -> GreyscaleImage {
 // We use TIMER1 to drive the display, and RTC0 to update the animation.
 // We set the TIMER1 interrupt to a higher priority than RTC0.
 
-static GPIO: Mutex<RefCell<Option<GPIO>>> = Mutex::new(RefCell::new(None));
+static DISPLAY_PINS: Mutex<RefCell<Option<DisplayPins>>> = Mutex::new(RefCell::new(None));
 static ANIM_TIMER: Mutex<RefCell<Option<LoResTimer<RTC0>>>> = Mutex::new(RefCell::new(None));
-static DISPLAY_TIMER: Mutex<RefCell<Option<MicrobitDisplayTimer<TIMER1>>>> = Mutex::new(RefCell::new(None));
+static DISPLAY_TIMER: Mutex<RefCell<Option<MicrobitDisplayTimer<TIMER1>>>> =
+    Mutex::new(RefCell::new(None));
 static DISPLAY: Mutex<RefCell<Option<Display<MicrobitFrame>>>> = Mutex::new(RefCell::new(None));
 
 #[entry]
@@ -48,18 +50,21 @@ fn main() -> ! {
             rtc0.enable_tick_interrupt();
             rtc0.start();
 
+            let gpio = p.GPIO.split();
+            let mut pins = microbit::display_pins!(gpio);
             let mut timer = MicrobitDisplayTimer::new(p.TIMER1);
-            let mut gpio = p.GPIO;
-            display::initialise_display(&mut timer, &mut gpio);
-            *GPIO.borrow(cs).borrow_mut() = Some(gpio);
+            display::initialise_display(&mut timer, &mut pins);
+            *DISPLAY_PINS.borrow(cs).borrow_mut() = Some(pins);
             *ANIM_TIMER.borrow(cs).borrow_mut() = Some(rtc0);
             *DISPLAY_TIMER.borrow(cs).borrow_mut() = Some(timer);
             *DISPLAY.borrow(cs).borrow_mut() = Some(Display::new());
         });
         if let Some(mut cp) = Peripherals::take() {
+            /* The display timer must not wait for the animation (a lower
+             * number is more urgent) */
             unsafe {
-                cp.NVIC.set_priority(microbit::Interrupt::RTC0, 64);
-                cp.NVIC.set_priority(microbit::Interrupt::TIMER1, 128);
+                cp.NVIC.set_priority(microbit::Interrupt::TIMER1, 64);
+                cp.NVIC.set_priority(microbit::Interrupt::RTC0, 128);
             }
             cp.NVIC.enable(microbit::Interrupt::RTC0);
             cp.NVIC.enable(microbit::Interrupt::TIMER1);
@@ -77,9 +82,9 @@ fn main() -> ! {
 fn TIMER1() {
     cortex_m::interrupt::free(|cs| {
         if let Some(timer) = DISPLAY_TIMER.borrow(cs).borrow_mut().as_mut() {
-            if let Some(gpio) = GPIO.borrow(cs).borrow_mut().as_mut() {
+            if let Some(pins) = DISPLAY_PINS.borrow(cs).borrow_mut().as_mut() {
                 if let Some(d) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
-                    display::handle_display_event(d, timer, gpio);
+                    display::handle_display_event(d, timer, pins);
                 }
             }
         }
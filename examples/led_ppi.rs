@@ -0,0 +1,195 @@
+//! Compares the interrupt load of the two display drivers.
+//!
+//! Shows an animated greyscale heart, and once a second prints the number of
+//! display timer interrupts taken in the previous second to the serial port.
+//!
+//! Set `USE_PPI` to `false` to drive the display with the standard
+//! interrupt-driven `Display` instead of the `PpiDisplay`.
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use core::cell::{Cell, RefCell};
+use core::fmt::Write;
+use core::ops::DerefMut;
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::Peripherals;
+use cortex_m_rt::entry;
+
+use microbit::display::image::GreyscaleImage;
+use microbit::display::ppi::{PpiDisplay, PpiFrame};
+use microbit::display::{self, Display, DisplayPins, Frame, MicrobitDisplayTimer, MicrobitFrame};
+use microbit::hal::lo_res_timer::{LoResTimer, FREQ_16HZ};
+use microbit::hal::nrf51::{interrupt, RTC0, TIMER1, UART0};
+use microbit::hal::prelude::*;
+use microbit::hal::serial;
+use microbit::hal::serial::BAUD115200;
+
+const USE_PPI: bool = true;
+
+/* The outline is at full brightness so that no matrix row has more dim
+ * LEDs than the PpiDisplay can switch off in hardware. The count of rows
+ * falling back to software is reported with the interrupt count. */
+fn heart_image(inner_brightness: u8) -> GreyscaleImage {
+    let b = inner_brightness;
+    let c = inner_brightness / 2;
+    GreyscaleImage::new(&[
+        [0, 9, 0, 9, 0],
+        [9, b, 9, b, 9],
+        [9, b, c, b, 9],
+        [0, 9, b, 9, 0],
+        [0, 0, 9, 0, 0],
+    ])
+}
+
+// We use TIMER1 to drive the display, and RTC0 to update the animation.
+// We set the TIMER1 interrupt to a higher priority than RTC0.
+
+static DISPLAY_PINS: Mutex<RefCell<Option<DisplayPins>>> = Mutex::new(RefCell::new(None));
+static TX: Mutex<RefCell<Option<serial::Tx<UART0>>>> = Mutex::new(RefCell::new(None));
+static ANIM_TIMER: Mutex<RefCell<Option<LoResTimer<RTC0>>>> = Mutex::new(RefCell::new(None));
+static DISPLAY_TIMER: Mutex<RefCell<Option<MicrobitDisplayTimer<TIMER1>>>> =
+    Mutex::new(RefCell::new(None));
+static DISPLAY: Mutex<RefCell<Option<Display<MicrobitFrame>>>> = Mutex::new(RefCell::new(None));
+static PPI_DISPLAY: Mutex<RefCell<Option<PpiDisplay<TIMER1>>>> = Mutex::new(RefCell::new(None));
+static INTERRUPTS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        // Starting the low-frequency clock (needed for RTC to work)
+        p.CLOCK.tasks_lfclkstart.write(|w| unsafe { w.bits(1) });
+        while p.CLOCK.events_lfclkstarted.read().bits() == 0 {}
+        p.CLOCK.events_lfclkstarted.reset();
+
+        cortex_m::interrupt::free(move |cs| {
+            let mut rtc0 = LoResTimer::new(p.RTC0);
+            // 62.5ms period
+            rtc0.set_frequency(FREQ_16HZ);
+            rtc0.enable_tick_event();
+            rtc0.enable_tick_interrupt();
+            rtc0.start();
+
+            /* Split GPIO pins */
+            let gpio = p.GPIO.split();
+            let mut pins = microbit::display_pins!(gpio);
+
+            /* Initialise serial port on the micro:bit */
+            let (mut tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+            let _ = write!(
+                tx,
+                "\n\rCounting display interrupts ({} driver)\n\r",
+                if USE_PPI { "PPI" } else { "standard" }
+            );
+
+            if USE_PPI {
+                let display = PpiDisplay::new(p.TIMER1, p.GPIOTE, p.PPI, pins);
+                *PPI_DISPLAY.borrow(cs).borrow_mut() = Some(display);
+            } else {
+                let mut timer = MicrobitDisplayTimer::new(p.TIMER1);
+                display::initialise_display(&mut timer, &mut pins);
+                *DISPLAY_PINS.borrow(cs).borrow_mut() = Some(pins);
+                *DISPLAY_TIMER.borrow(cs).borrow_mut() = Some(timer);
+                *DISPLAY.borrow(cs).borrow_mut() = Some(Display::new());
+            }
+            *TX.borrow(cs).borrow_mut() = Some(tx);
+            *ANIM_TIMER.borrow(cs).borrow_mut() = Some(rtc0);
+        });
+        if let Some(mut cp) = Peripherals::take() {
+            unsafe {
+                cp.NVIC.set_priority(microbit::Interrupt::TIMER1, 64);
+                cp.NVIC.set_priority(microbit::Interrupt::RTC0, 128);
+            }
+            cp.NVIC.enable(microbit::Interrupt::RTC0);
+            cp.NVIC.enable(microbit::Interrupt::TIMER1);
+            microbit::NVIC::unpend(microbit::Interrupt::RTC0);
+            microbit::NVIC::unpend(microbit::Interrupt::TIMER1);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
+
+#[interrupt]
+fn TIMER1() {
+    cortex_m::interrupt::free(|cs| {
+        let interrupts = INTERRUPTS.borrow(cs);
+        interrupts.set(interrupts.get() + 1);
+
+        if let Some(d) = PPI_DISPLAY.borrow(cs).borrow_mut().as_mut() {
+            d.handle_event();
+        }
+        if let Some(timer) = DISPLAY_TIMER.borrow(cs).borrow_mut().as_mut() {
+            if let Some(pins) = DISPLAY_PINS.borrow(cs).borrow_mut().as_mut() {
+                if let Some(d) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+                    display::handle_display_event(d, timer, pins);
+                }
+            }
+        }
+    });
+}
+
+#[interrupt]
+fn RTC0() {
+    static mut STEP: u8 = 0;
+    static mut TICKS: u8 = 0;
+    static mut FRAME: MicrobitFrame = MicrobitFrame::const_default();
+    static mut PPI_FRAME: PpiFrame = PpiFrame::const_default();
+    static mut SOFTWARE_ROWS: usize = 0;
+
+    cortex_m::interrupt::free(|cs| {
+        if let Some(rtc) = ANIM_TIMER.borrow(cs).borrow_mut().as_mut() {
+            rtc.clear_tick_event();
+        }
+    });
+
+    let inner_brightness = match *STEP {
+        0..=8 => 9 - *STEP,
+        9..=12 => 0,
+        _ => unreachable!(),
+    };
+    let image = heart_image(inner_brightness);
+
+    FRAME.set(&image);
+    PPI_FRAME.set(&image);
+    *SOFTWARE_ROWS = (*SOFTWARE_ROWS).max(PPI_FRAME.software_rows());
+
+    cortex_m::interrupt::free(|cs| {
+        if let Some(d) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+            d.set_frame(&FRAME);
+        }
+        if let Some(d) = PPI_DISPLAY.borrow(cs).borrow_mut().as_mut() {
+            d.set_frame(&PPI_FRAME);
+        }
+    });
+
+    *STEP += 1;
+    if *STEP == 13 {
+        *STEP = 0
+    };
+
+    /* Report once a second */
+    *TICKS += 1;
+    if *TICKS == 16 {
+        *TICKS = 0;
+        let software_rows = core::mem::replace(SOFTWARE_ROWS, 0);
+        cortex_m::interrupt::free(|cs| {
+            let count = INTERRUPTS.borrow(cs).replace(0);
+            if let &mut Some(ref mut tx) = TX.borrow(cs).borrow_mut().deref_mut() {
+                if USE_PPI {
+                    let _ = write!(
+                        tx,
+                        "{} interrupts/s, {} rows in software\n\r",
+                        count, software_rows
+                    );
+                } else {
+                    let _ = write!(tx, "{} interrupts/s\n\r", count);
+                }
+            }
+        });
+    }
+}
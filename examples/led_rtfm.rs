@@ -10,9 +10,10 @@
 use panic_halt as _;
 
 use microbit::display::image::GreyscaleImage;
-use microbit::display::{self, Display, Frame, MicrobitDisplayTimer, MicrobitFrame};
+use microbit::display::{self, Display, DisplayPins, Frame, MicrobitDisplayTimer, MicrobitFrame};
 use microbit::hal::lo_res_timer::{LoResTimer, FREQ_16HZ};
 use microbit::hal::nrf51;
+use microbit::hal::prelude::*;
 use rtfm::app;
 
 fn heart_image(inner_brightness: u8) -> GreyscaleImage {
@@ -28,14 +29,14 @@ fn heart_image(inner_brightness: u8) -> GreyscaleImage {
 
 #[app(device = microbit::hal::nrf51)]
 const APP: () = {
-    static mut GPIO: nrf51::GPIO = ();
+    static mut DISPLAY_PINS: DisplayPins = ();
     static mut DISPLAY_TIMER: MicrobitDisplayTimer<nrf51::TIMER1> = ();
     static mut ANIM_TIMER: LoResTimer<nrf51::RTC0> = ();
     static mut DISPLAY: Display<MicrobitFrame> = ();
 
     #[init]
     fn init() -> init::LateResources {
-        let mut p: nrf51::Peripherals = device;
+        let p: nrf51::Peripherals = device;
 
         // Starting the low-frequency clock (needed for RTC to work)
         p.CLOCK.tasks_lfclkstart.write(|w| unsafe { w.bits(1) });
@@ -49,11 +50,13 @@ const APP: () = {
         rtc0.enable_tick_interrupt();
         rtc0.start();
 
+        let gpio = p.GPIO.split();
+        let mut pins = microbit::display_pins!(gpio);
         let mut timer = MicrobitDisplayTimer::new(p.TIMER1);
-        display::initialise_display(&mut timer, &mut p.GPIO);
+        display::initialise_display(&mut timer, &mut pins);
 
         init::LateResources {
-            GPIO: p.GPIO,
+            DISPLAY_PINS: pins,
             DISPLAY_TIMER: timer,
             ANIM_TIMER: rtc0,
             DISPLAY: Display::new(),
@@ -61,12 +64,12 @@ const APP: () = {
     }
 
     #[interrupt(priority = 2,
-                resources = [DISPLAY_TIMER, GPIO, DISPLAY])]
+                resources = [DISPLAY_TIMER, DISPLAY_PINS, DISPLAY])]
     fn TIMER1() {
         display::handle_display_event(
             &mut resources.DISPLAY,
             resources.DISPLAY_TIMER,
-            resources.GPIO,
+            resources.DISPLAY_PINS,
         );
     }
 
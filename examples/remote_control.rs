@@ -0,0 +1,210 @@
+//! Lets a host control the board over the serial port, using the requests
+//! described in `microbit_protocol::message`.
+//!
+//! Try it with `microbit-remote` from the `host` directory, for example:
+//!
+//! ```text
+//! microbit-remote /dev/ttyACM0 image 09090/99999/99999/09990/00900
+//! microbit-remote /dev/ttyACM0 temp
+//! ```
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::Peripherals;
+use cortex_m_rt::entry;
+
+use microbit::display::image::GreyscaleImage;
+use microbit::display::{self, Display, DisplayPins, Frame, MicrobitDisplayTimer, MicrobitFrame};
+use microbit::hal::gpio::gpio::{PIN, PIN17, PIN26};
+use microbit::hal::gpio::{Floating, Input};
+use microbit::hal::i2c;
+use microbit::hal::nrf51::{interrupt, GPIO, TIMER1, TWI1};
+use microbit::hal::prelude::*;
+use microbit::hal::rng;
+use microbit::hal::serial::BAUD115200;
+use microbit::remote::{self, Refusal, RemoteBoard, RemoteControl};
+use microbit::sensors::accelerometer::Accelerometer;
+use microbit::sensors::magnetometer::Magnetometer;
+use microbit::shared_i2c::{CriticalSectionMutex, I2cProxy, SharedBus};
+use microbit::temperature::Temperature;
+
+static DISPLAY_PINS: Mutex<RefCell<Option<DisplayPins>>> = Mutex::new(RefCell::new(None));
+static DISPLAY_TIMER: Mutex<RefCell<Option<MicrobitDisplayTimer<TIMER1>>>> =
+    Mutex::new(RefCell::new(None));
+static DISPLAY: Mutex<RefCell<Option<Display<MicrobitFrame>>>> = Mutex::new(RefCell::new(None));
+
+/* Edge connector pins which aren't used by the display, buttons or I2C bus */
+const FREE_EDGE_PINS: [u8; 9] = [0, 1, 2, 8, 12, 13, 14, 15, 16];
+
+/* Both motion sensors share the internal I2C bus */
+type SensorBus = I2cProxy<'static, CriticalSectionMutex<i2c::I2c<TWI1>>>;
+
+struct Board {
+    frame: MicrobitFrame,
+    button_a: PIN17<Input<Floating>>,
+    button_b: PIN26<Input<Floating>>,
+    /* The free edge pins, taken from the HAL so that nothing else uses
+     * them. SetPin and ReadPin switch them between output and input
+     * through the GPIO registers, leaving their drive and pull settings
+     * alone, so the HAL's idea of their mode no longer applies. */
+    _edge_pins: [PIN<Input<Floating>>; 9],
+    /* None if the board doesn't have that sensor (v1.5 boards have no
+     * MAG3110) */
+    magnetometer: Option<Magnetometer<SensorBus>>,
+    accelerometer: Option<Accelerometer<SensorBus>>,
+    rng: rng::Rng,
+    temperature: Temperature,
+}
+
+impl Board {
+    /* Returns the GPIO registers and pin number for a free edge pin */
+    fn edge_pin(
+        &self,
+        pin: u8,
+    ) -> Result<(&'static microbit::hal::nrf51::gpio::RegisterBlock, usize), Refusal> {
+        if !FREE_EDGE_PINS.contains(&pin) {
+            return Err(Refusal::BadArgument);
+        }
+        let gpio_pin = remote::edge_pin_to_gpio(pin).ok_or(Refusal::BadArgument)?;
+        Ok((unsafe { &*GPIO::ptr() }, gpio_pin as usize))
+    }
+}
+
+impl RemoteBoard for Board {
+    fn set_image(&mut self, brightness: &[[u8; 5]; 5]) -> Result<(), Refusal> {
+        self.frame.set(&GreyscaleImage::new(brightness));
+        let frame = &self.frame;
+        cortex_m::interrupt::free(|cs| {
+            if let Some(d) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+                d.set_frame(frame);
+            }
+        });
+        Ok(())
+    }
+
+    fn buttons(&mut self) -> Result<(bool, bool), Refusal> {
+        /* The buttons pull their pins low when pressed */
+        let a = self.button_a.is_low().map_err(|_| Refusal::Failed)?;
+        let b = self.button_b.is_low().map_err(|_| Refusal::Failed)?;
+        Ok((a, b))
+    }
+
+    fn magnetometer(&mut self) -> Result<(i16, i16, i16), Refusal> {
+        let magnetometer = self.magnetometer.as_mut().ok_or(Refusal::Unsupported)?;
+        let field = magnetometer.read_raw().map_err(|_| Refusal::Failed)?;
+        Ok((field.x, field.y, field.z))
+    }
+
+    fn accelerometer(&mut self) -> Result<(i16, i16, i16), Refusal> {
+        let accelerometer = self.accelerometer.as_mut().ok_or(Refusal::Unsupported)?;
+        let tilt = accelerometer.read().map_err(|_| Refusal::Failed)?;
+        Ok((tilt.x.0, tilt.y.0, tilt.z.0))
+    }
+
+    fn temperature(&mut self) -> Result<i16, Refusal> {
+        self.temperature.start();
+        nb::block!(self.temperature.read_raw()).map_err(|_| Refusal::Failed)
+    }
+
+    fn set_pin(&mut self, pin: u8, high: bool) -> Result<(), Refusal> {
+        let (gpio, n) = self.edge_pin(pin)?;
+        if high {
+            gpio.outset.write(|w| unsafe { w.bits(1 << n) });
+        } else {
+            gpio.outclr.write(|w| unsafe { w.bits(1 << n) });
+        }
+        gpio.pin_cnf[n].modify(|_, w| w.dir().output());
+        Ok(())
+    }
+
+    fn read_pin(&mut self, pin: u8) -> Result<bool, Refusal> {
+        let (gpio, n) = self.edge_pin(pin)?;
+        gpio.pin_cnf[n].modify(|_, w| w.dir().input().input().connect());
+        Ok(gpio.in_.read().bits() & (1 << n) != 0)
+    }
+
+    fn random(&mut self) -> Result<u32, Refusal> {
+        let mut bytes = [0; 4];
+        self.rng.read(&mut bytes).map_err(|_| Refusal::Failed)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        let gpio = p.GPIO.split();
+
+        let mut pins = microbit::display_pins!(gpio);
+        let mut timer = MicrobitDisplayTimer::new(p.TIMER1);
+        display::initialise_display(&mut timer, &mut pins);
+        cortex_m::interrupt::free(move |cs| {
+            *DISPLAY_PINS.borrow(cs).borrow_mut() = Some(pins);
+            *DISPLAY_TIMER.borrow(cs).borrow_mut() = Some(timer);
+            *DISPLAY.borrow(cs).borrow_mut() = Some(Display::new());
+        });
+        if let Some(mut cp) = Peripherals::take() {
+            cp.NVIC.enable(microbit::Interrupt::TIMER1);
+            microbit::NVIC::unpend(microbit::Interrupt::TIMER1);
+        }
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, mut rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        /* Configure the internal I2C bus, and whichever sensors answer on it */
+        let scl = gpio.pin0.into_open_drain_input().into();
+        let sda = gpio.pin30.into_open_drain_input().into();
+        let i2c = i2c::I2c::i2c1(p.TWI1, sda, scl);
+        let bus: &'static _ = cortex_m::singleton!(
+            : SharedBus<CriticalSectionMutex<i2c::I2c<TWI1>>> = SharedBus::critical_section(i2c)
+        )
+        .unwrap();
+
+        let mut board = Board {
+            frame: MicrobitFrame::default(),
+            button_a: gpio.pin17.into_floating_input(),
+            button_b: gpio.pin26.into_floating_input(),
+            _edge_pins: [
+                gpio.pin3.into(),
+                gpio.pin2.into(),
+                gpio.pin1.into(),
+                gpio.pin18.into(),
+                gpio.pin20.into(),
+                gpio.pin23.into(),
+                gpio.pin22.into(),
+                gpio.pin21.into(),
+                gpio.pin16.into(),
+            ],
+            magnetometer: Magnetometer::new(bus.acquire()).ok(),
+            accelerometer: Accelerometer::new(bus.acquire()).ok(),
+            rng: rng::Rng::new(p.RNG),
+            temperature: Temperature::new(p.TEMP),
+        };
+
+        let mut remote = RemoteControl::new();
+        loop {
+            let _ = remote.poll(&mut rx, &mut tx, &mut board);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
+
+#[interrupt]
+fn TIMER1() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(timer) = DISPLAY_TIMER.borrow(cs).borrow_mut().as_mut() {
+            if let Some(pins) = DISPLAY_PINS.borrow(cs).borrow_mut().as_mut() {
+                if let Some(d) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+                    display::handle_display_event(d, timer, pins);
+                }
+            }
+        }
+    });
+}
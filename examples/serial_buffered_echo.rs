@@ -0,0 +1,70 @@
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::buffered_uart::BufferedUart;
+use microbit::hal::nrf51::interrupt;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+use microbit::NVIC;
+
+use cortex_m::interrupt::Mutex;
+use cortex_m_rt::entry;
+
+use core::cell::RefCell;
+use core::fmt::Write;
+
+static UART: Mutex<RefCell<Option<BufferedUart>>> = Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    static mut RX_BUFFER: [u8; 64] = [0; 64];
+    static mut TX_BUFFER: [u8; 256] = [0; 256];
+
+    if let Some(p) = microbit::Peripherals::take() {
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit, and hand it to the buffered driver */
+        let (tx, rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+        let mut uart = BufferedUart::new(tx, rx, RX_BUFFER, TX_BUFFER);
+
+        /* Print a nice hello message */
+        let _ = write!(uart, "Please type characters to echo:\r\n");
+
+        cortex_m::interrupt::free(move |cs| {
+            *UART.borrow(cs).borrow_mut() = Some(uart);
+        });
+        unsafe {
+            NVIC::unmask(microbit::Interrupt::UART0);
+        }
+    }
+
+    /* Echo whatever arrives; the main loop could be doing something slow in between */
+    let mut buf = [0; 16];
+    let mut last_errors = Default::default();
+    loop {
+        cortex_m::interrupt::free(|cs| {
+            if let Some(uart) = UART.borrow(cs).borrow_mut().as_mut() {
+                let count = uart.read(&mut buf);
+                uart.write(&buf[..count]);
+
+                let errors = uart.error_counts();
+                if errors != last_errors {
+                    let _ = write!(uart, "\r\n[errors: {:?}]\r\n", errors);
+                    last_errors = errors;
+                }
+            }
+        });
+    }
+}
+
+#[interrupt]
+fn UART0() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(uart) = UART.borrow(cs).borrow_mut().as_mut() {
+            uart.handle_interrupt();
+        }
+    });
+}
@@ -0,0 +1,67 @@
+//! Sends magnetometer readings to the host as framed binary messages, and
+//! answers pings.
+//!
+//! Use `microbit-monitor` from the `host` directory to see the readings.
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::framing::{self, Message, MessageReceiver};
+use microbit::hal::delay::Delay;
+use microbit::hal::i2c;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        let mut delay = Delay::new(p.TIMER0);
+
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, mut rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        /* Configure the internal I2C bus, and the magnetometer for automatic updates */
+        let scl = gpio.pin0.into_open_drain_input().into();
+        let sda = gpio.pin30.into_open_drain_input().into();
+        let mut i2c = i2c::I2c::i2c1(p.TWI1, sda, scl);
+        let _ = i2c.write(0xE, &[0x10, 0x1]);
+        let _ = i2c.write(0xE, &[0x11, 0x7f]);
+
+        let mut receiver = MessageReceiver::new();
+        let mut ticks: u32 = 0;
+        loop {
+            /* Answer pings; anything else (including bad frames) is ignored */
+            while let Ok(result) = receiver.receive(&mut rx) {
+                if let Ok(Message::Ping { seq }) = result {
+                    let _ = framing::send(&mut tx, &Message::Pong { seq });
+                }
+            }
+
+            /* Send a reading every 100ms */
+            if ticks % 10 == 0 {
+                let mut data: [u8; 6] = [0; 6];
+                if i2c.write_read(0xE, &[0x1], &mut data).is_ok() {
+                    let message = Message::Magnetometer {
+                        x: i16::from_be_bytes([data[0], data[1]]),
+                        y: i16::from_be_bytes([data[2], data[3]]),
+                        z: i16::from_be_bytes([data[4], data[5]]),
+                    };
+                    let _ = framing::send(&mut tx, &message);
+                }
+            }
+
+            delay.delay_ms(10_u32);
+            ticks = ticks.wrapping_add(1);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
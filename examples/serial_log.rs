@@ -0,0 +1,72 @@
+//! Logs button events using the `log` macros.
+//!
+//! Requires the `log` feature:
+//!
+//! ```text
+//! cargo run --example serial_log --features log
+//! ```
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use log::LevelFilter;
+
+use microbit::buttons::{Button, Buttons, Event, Timings};
+use microbit::hal::delay::Delay;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        /* Start the low-frequency clock, which drives the log timestamps */
+        p.CLOCK.tasks_lfclkstart.write(|w| unsafe { w.bits(1) });
+        while p.CLOCK.events_lfclkstarted.read().bits() == 0 {}
+        p.CLOCK.events_lfclkstarted.write(|w| unsafe { w.bits(0) });
+
+        let gpio = p.GPIO.split();
+        let (tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+        microbit::logger::init(tx, p.RTC1, LevelFilter::Info);
+
+        let mut delay = Delay::new(p.TIMER0);
+        let mut buttons = Buttons::new(
+            gpio.pin17.into_floating_input(),
+            gpio.pin26.into_floating_input(),
+            Timings::default(),
+        );
+
+        log::info!("press A for more detail, B for less, or hold both to stop");
+
+        let mut now_ms: u32 = 0;
+        loop {
+            buttons.update(now_ms);
+            while let Some(event) = buttons.next_event() {
+                log::debug!("{:?}", event);
+                match event {
+                    Event::Click(Button::A) => {
+                        microbit::logger::set_level(LevelFilter::Debug);
+                        log::info!("showing debug messages");
+                    }
+                    Event::Click(Button::B) => {
+                        log::info!("hiding debug messages");
+                        microbit::logger::set_level(LevelFilter::Info);
+                    }
+                    Event::LongPress(Button::AB) => {
+                        log::warn!("stopping");
+                        microbit::logger::set_level(LevelFilter::Off);
+                    }
+                    _ => {}
+                }
+            }
+            delay.delay_ms(10_u32);
+            now_ms = now_ms.wrapping_add(10);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
@@ -0,0 +1,57 @@
+//! Prints magnetometer readings (in microtesla) as tuples ten times a
+//! second, for the Mu editor's plotter (or the MakeCode console) to graph.
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::hal::delay::Delay;
+use microbit::hal::i2c;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+use microbit::plotter::Plotter;
+use microbit::sensors::magnetometer::Magnetometer;
+
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        let mut delay = Delay::new(p.TIMER0);
+
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit, and plot every 100ms */
+        let (tx, _rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+        let mut plotter = Plotter::tuples(tx, 100);
+
+        /* Configure the internal I2C bus, and the magnetometer */
+        let scl = gpio.pin0.into_open_drain_input().into();
+        let sda = gpio.pin30.into_open_drain_input().into();
+        let i2c = i2c::I2c::i2c1(p.TWI1, sda, scl);
+        let mut magnetometer = match Magnetometer::new(i2c) {
+            Ok(magnetometer) => magnetometer,
+            Err(_) => loop {
+                continue;
+            },
+        };
+
+        let mut now_ms: u32 = 0;
+        loop {
+            if plotter.is_due(now_ms) {
+                if let Ok(field) = magnetometer.read() {
+                    /* Plot the bare numbers: the plotter doesn't understand units */
+                    let _ = plotter.plot(now_ms, &[field.x.0, field.y.0, field.z.0]);
+                }
+            }
+
+            delay.delay_ms(10_u32);
+            now_ms = now_ms.wrapping_add(10);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
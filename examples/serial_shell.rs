@@ -0,0 +1,129 @@
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::hal::delay::Delay;
+use microbit::hal::gpio::gpio::{PIN17, PIN26};
+use microbit::hal::gpio::{Floating, Input};
+use microbit::hal::i2c;
+use microbit::hal::nrf51::TWI1;
+use microbit::hal::prelude::*;
+use microbit::hal::rng;
+use microbit::hal::serial::BAUD115200;
+use microbit::led;
+use microbit::sensors::magnetometer::Magnetometer;
+use microbit::sensors::Vector;
+use microbit::shell::{Command, RandomNumbers, ReadButtons, ReadMagnetometer, Shell, ShowImage};
+use microbit::units::MicroTesla;
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+
+/* Everything the commands need to get at */
+struct Board {
+    leds: led::Display,
+    delay: Delay,
+    button_a: PIN17<Input<Floating>>,
+    button_b: PIN26<Input<Floating>>,
+    rng: rng::Rng,
+    magnetometer: Option<Magnetometer<i2c::I2c<TWI1>>>,
+}
+
+const COMMANDS: &[Command<Board>] = &[
+    Command::show(),
+    Command::buttons(),
+    Command::rng(),
+    Command::magnetometer(),
+];
+
+/* Give the built-in commands their hardware */
+impl ShowImage for Board {
+    fn show_image(&mut self, image: [[u8; 5]; 5], duration_ms: u32) {
+        self.leds.display(&mut self.delay, image, duration_ms);
+    }
+}
+
+impl ReadButtons for Board {
+    fn buttons_pressed(&mut self) -> (bool, bool) {
+        /* The buttons pull their pins low when pressed */
+        let a = self.button_a.is_low().unwrap_or(false);
+        let b = self.button_b.is_low().unwrap_or(false);
+        (a, b)
+    }
+}
+
+impl RandomNumbers for Board {
+    fn random_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.rng.read(&mut bytes).ok();
+        u32::from_le_bytes(bytes)
+    }
+}
+
+impl ReadMagnetometer for Board {
+    fn magnetic_field(&mut self) -> Option<Vector<MicroTesla>> {
+        self.magnetometer.as_mut()?.read().ok()
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, mut rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        /* Display */
+        let leds = led::Display::new(
+            gpio.pin4.into_push_pull_output(),
+            gpio.pin5.into_push_pull_output(),
+            gpio.pin6.into_push_pull_output(),
+            gpio.pin7.into_push_pull_output(),
+            gpio.pin8.into_push_pull_output(),
+            gpio.pin9.into_push_pull_output(),
+            gpio.pin10.into_push_pull_output(),
+            gpio.pin11.into_push_pull_output(),
+            gpio.pin12.into_push_pull_output(),
+            gpio.pin13.into_push_pull_output(),
+            gpio.pin14.into_push_pull_output(),
+            gpio.pin15.into_push_pull_output(),
+        );
+
+        /* Configure the internal I2C bus, and the magnetometer if there is one */
+        let scl = gpio.pin0.into_open_drain_input().into();
+        let sda = gpio.pin30.into_open_drain_input().into();
+        let i2c = i2c::I2c::i2c1(p.TWI1, sda, scl);
+
+        let mut board = Board {
+            leds,
+            delay: Delay::new(p.TIMER0),
+            button_a: gpio.pin17.into_floating_input(),
+            button_b: gpio.pin26.into_floating_input(),
+            rng: rng::Rng::new(p.RNG),
+            magnetometer: Magnetometer::new(i2c).ok(),
+        };
+
+        let mut shell = Shell::new(COMMANDS);
+        let _ = write!(
+            tx,
+            "\r\nmicro:bit shell; type 'help' for a list of commands\r\n"
+        );
+        let _ = shell.prompt(&mut tx);
+
+        loop {
+            let byte = match nb::block!(rx.read()) {
+                Ok(byte) => byte,
+                Err(never) => match never {},
+            };
+            let _ = shell.feed(byte, &mut board, &mut tx);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
@@ -0,0 +1,104 @@
+//! Bridges a software UART on edge connector pins 0 (TX) and 1 (RX) to the
+//! USB serial port, for talking to a GPS module or similar at 9600 baud.
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::gpiote::Gpiote;
+use microbit::hal::gpio::gpio::{PIN2, PIN3};
+use microbit::hal::gpio::{Floating, Input, Output, PushPull};
+use microbit::hal::nrf51::{interrupt, TIMER2};
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+use microbit::soft_uart::SoftUart;
+use microbit::NVIC;
+
+use cortex_m::interrupt::Mutex;
+use cortex_m_rt::entry;
+
+use core::cell::RefCell;
+
+type EdgeUart = SoftUart<TIMER2, PIN3<Output<PushPull>>, PIN2<Input<Floating>>>;
+
+static SOFT_UART: Mutex<RefCell<Option<EdgeUart>>> = Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, mut rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        /* Set up the software UART on edge pins 0 and 1 */
+        let (_gpiote, channels) = Gpiote::new(p.GPIOTE);
+        let ppi_channels = microbit::ppi::split(p.PPI);
+        let soft_tx = gpio.pin3.into_push_pull_output();
+        let soft_rx = gpio.pin2.into_floating_input();
+        let soft_uart = SoftUart::new(
+            p.TIMER2,
+            channels.ch0,
+            ppi_channels.ch0,
+            soft_tx,
+            soft_rx,
+            9600,
+        );
+
+        cortex_m::interrupt::free(move |cs| {
+            *SOFT_UART.borrow(cs).borrow_mut() = Some(soft_uart);
+        });
+        unsafe {
+            NVIC::unmask(microbit::Interrupt::TIMER2);
+            NVIC::unmask(microbit::Interrupt::GPIOTE);
+        }
+
+        /* Copy bytes across in both directions, leaving the interrupts free
+         * to run while waiting */
+        loop {
+            let received = cortex_m::interrupt::free(|cs| {
+                SOFT_UART
+                    .borrow(cs)
+                    .borrow_mut()
+                    .as_mut()
+                    .and_then(|soft_uart| soft_uart.read().ok())
+            });
+            if let Some(byte) = received {
+                let _ = nb::block!(tx.write(byte));
+            }
+
+            if let Ok(byte) = rx.read() {
+                while cortex_m::interrupt::free(|cs| {
+                    SOFT_UART
+                        .borrow(cs)
+                        .borrow_mut()
+                        .as_mut()
+                        .map_or(false, |soft_uart| soft_uart.write(byte).is_err())
+                }) {}
+            }
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
+
+#[interrupt]
+fn TIMER2() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(soft_uart) = SOFT_UART.borrow(cs).borrow_mut().as_mut() {
+            soft_uart.handle_timer_interrupt();
+        }
+    });
+}
+
+#[interrupt]
+fn GPIOTE() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(soft_uart) = SOFT_UART.borrow(cs).borrow_mut().as_mut() {
+            soft_uart.handle_gpiote_interrupt();
+        }
+    });
+}
@@ -0,0 +1,43 @@
+//! Prints the temperature of the nRF51 over the serial port once a second.
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use microbit::hal::delay::Delay;
+use microbit::hal::prelude::*;
+use microbit::hal::serial::BAUD115200;
+use microbit::temperature::Temperature;
+use microbit::units::Celsius;
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+
+/* The difference between this board's readings and a thermometer */
+const OFFSET: Celsius = Celsius(0.0);
+
+#[entry]
+fn main() -> ! {
+    if let Some(p) = microbit::Peripherals::take() {
+        let mut delay = Delay::new(p.TIMER0);
+
+        /* Split GPIO pins */
+        let gpio = p.GPIO.split();
+
+        /* Initialise serial port on the micro:bit */
+        let (mut tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+
+        let mut temperature = Temperature::new(p.TEMP);
+        temperature.set_offset(OFFSET);
+
+        loop {
+            let _ = write!(tx, "{:.2}\n\r", temperature.measure());
+            delay.delay_ms(1000_u32);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
@@ -0,0 +1,46 @@
+//! Prints the messages sent by a micro:bit.
+//!
+//! Usage: `microbit-monitor <serial port> [baud rate]`
+
+use std::env;
+use std::process;
+use std::time::Duration;
+
+use microbit_host::{open, Error};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: microbit-monitor <serial port> [baud rate]");
+            process::exit(2);
+        }
+    };
+    let baud_rate = args.next().and_then(|s| s.parse().ok()).unwrap_or(115_200);
+
+    let mut connection = match open(&path, baud_rate, Duration::from_secs(5)) {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    match connection.ping(0) {
+        Ok(()) => println!("board is responding"),
+        Err(e) => eprintln!("no reply to ping: {}", e),
+    }
+
+    loop {
+        match connection.receive() {
+            Ok(message) => println!("{:?}", message),
+            Err(Error::Protocol(e)) => eprintln!("bad frame: {}", e),
+            Err(Error::Io(ref e)) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Controls a micro:bit running remote control firmware (such as the
+//! `remote_control` example) from the command line.
+//!
+//! Usage: `microbit-remote <serial port> <command> [arguments]`, where the
+//! command is one of:
+//! - `image <25 digits>`: show an image, giving each LED's brightness (0 to
+//!   9) a row at a time; spaces and `/` between rows are ignored
+//! - `buttons`, `mag`, `accel`, `temp`, `random`: print a reading
+//! - `pin <n>`: print the level of edge connector pin `n`
+//! - `pin <n> high|low`: drive edge connector pin `n`.
+
+use std::env;
+use std::process;
+use std::time::Duration;
+
+use microbit_host::{open, Error};
+
+const USAGE: &str = "usage: microbit-remote <serial port> image <25 digits>|buttons|mag|accel|temp|random|pin <n> [high|low]";
+
+fn parse_image(text: &str) -> Option<[[u8; 5]; 5]> {
+    let digits: Vec<u8> = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '/')
+        .map(|c| c.to_digit(10).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+    if digits.len() != 25 {
+        return None;
+    }
+    let mut image = [[0; 5]; 5];
+    for (value, &digit) in image.iter_mut().flatten().zip(digits.iter()) {
+        *value = digit;
+    }
+    Some(image)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let mut board = match open(&args[0], 115_200, Duration::from_secs(2)) {
+        Ok(board) => board,
+        Err(e) => {
+            eprintln!("{}: {}", args[0], e);
+            process::exit(1);
+        }
+    };
+
+    let command: Vec<&str> = args[1..].iter().map(String::as_str).collect();
+    let result: Result<(), Error> = match command[..] {
+        ["image", ref rest @ ..] => match parse_image(&rest.join(" ")) {
+            Some(image) => board.set_image(image),
+            None => {
+                eprintln!("an image needs 25 digits from 0 to 9");
+                process::exit(2);
+            }
+        },
+        ["buttons"] => board.buttons().map(|(a, b)| println!("A: {}, B: {}", a, b)),
+        ["mag"] => board
+            .magnetometer()
+            .map(|(x, y, z)| println!("x: {}, y: {}, z: {}", x, y, z)),
+        ["accel"] => board
+            .accelerometer()
+            .map(|(x, y, z)| println!("x: {} mg, y: {} mg, z: {} mg", x, y, z)),
+        ["temp"] => board.temperature().map(|t| println!("{:.2} °C", t)),
+        ["random"] => board.random().map(|value| println!("{}", value)),
+        ["pin", pin] => match pin.parse() {
+            Ok(pin) => board
+                .read_pin(pin)
+                .map(|high| println!("{}", if high { "high" } else { "low" })),
+            Err(_) => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        },
+        ["pin", pin, level @ "high"] | ["pin", pin, level @ "low"] => match pin.parse() {
+            Ok(pin) => board.set_pin(pin, level == "high"),
+            Err(_) => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
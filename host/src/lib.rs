@@ -0,0 +1,437 @@
+//! Host-side support for talking to a BBC micro:bit using the framed binary
+//! protocol from the [`microbit_protocol`] crate.
+//!
+//! [`Connection`] sends and receives [`Message`]s over anything implementing
+//! `std::io::Read` and `std::io::Write`: usually the board's serial port,
+//! opened with [`open()`], but equally a pseudo-terminal or an in-memory
+//! stream when testing without a board.
+//!
+//! This crate lives inside the firmware's source tree, which builds for the
+//! micro:bit by default, so give the host target explicitly when building
+//! it, for example:
+//!
+//! ```text
+//! cargo run --target x86_64-unknown-linux-gnu --bin microbit-monitor -- /dev/ttyACM0
+//! ```
+//!
+//! [`microbit_protocol`]: microbit_protocol
+//! [`Connection`]: Connection
+//! [`Message`]: Message
+//! [`open()`]: open
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+pub use microbit_protocol::{frame, FrameDecoder, Message};
+
+use microbit_protocol::message::{NAK_BAD_ARGUMENT, NAK_FAILED, NAK_UNSUPPORTED};
+
+/// Errors from a [`Connection`].
+#[derive(Debug)]
+pub enum Error {
+    /// Reading from or writing to the stream failed.
+    Io(io::Error),
+    /// A frame was received but couldn't be decoded.
+    Protocol(microbit_protocol::Error),
+    /// The board refused a request, replying with `Nak` and this reason.
+    Refused(u8),
+    /// The board replied to a request with the wrong type of message (for
+    /// example `Ack` to a request for a reading).
+    UnexpectedReply(Message),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Protocol(e) => write!(f, "bad frame: {}", e),
+            Error::Refused(NAK_UNSUPPORTED) => f.write_str("request not supported by the board"),
+            Error::Refused(NAK_BAD_ARGUMENT) => f.write_str("argument out of range"),
+            Error::Refused(NAK_FAILED) => f.write_str("board hardware failed"),
+            Error::Refused(reason) => write!(f, "request refused (reason {})", reason),
+            Error::UnexpectedReply(reply) => write!(f, "unexpected reply {:?}", reply),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Protocol(e) => Some(e),
+            Error::Refused(_) | Error::UnexpectedReply(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<microbit_protocol::Error> for Error {
+    fn from(e: microbit_protocol::Error) -> Error {
+        Error::Protocol(e)
+    }
+}
+
+/// Opens the board's serial port (for example `/dev/ttyACM0`) at the given
+/// baud rate.
+///
+/// Reads time out after `timeout`, returning an `Io` error of kind
+/// `TimedOut`.
+pub fn open(
+    path: &str,
+    baud_rate: u32,
+    timeout: Duration,
+) -> Result<Connection<Box<dyn serialport::SerialPort>>, Error> {
+    let port = serialport::new(path, baud_rate)
+        .timeout(timeout)
+        .open()
+        .map_err(io::Error::from)?;
+    Ok(Connection::new(port))
+}
+
+/// Sends and receives messages over a byte stream.
+pub struct Connection<S> {
+    stream: S,
+    decoder: FrameDecoder,
+}
+
+impl<S: Read + Write> Connection<S> {
+    /// Returns a connection using `stream`.
+    pub fn new(stream: S) -> Connection<S> {
+        Connection {
+            stream,
+            decoder: FrameDecoder::new(),
+        }
+    }
+
+    /// Gives the stream back.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Sends a message.
+    pub fn send(&mut self, message: &Message) -> Result<(), Error> {
+        let mut buffer = [0; frame::MAX_FRAME_LEN];
+        self.stream.write_all(frame::encode(message, &mut buffer))?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    /// Waits for the next frame, and returns its message.
+    ///
+    /// A frame which can't be decoded is returned as a `Protocol` error;
+    /// the connection can carry on being used afterwards.
+    pub fn receive(&mut self) -> Result<Message, Error> {
+        let mut byte = [0];
+        loop {
+            match self.stream.read(&mut byte) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(_) => {
+                    if let Some(result) = self.decoder.push(byte[0]) {
+                        return Ok(result?);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Sends a `Ping` and waits for the matching `Pong`, ignoring any other
+    /// messages which arrive first.
+    pub fn ping(&mut self, seq: u8) -> Result<(), Error> {
+        self.send(&Message::Ping { seq })?;
+        loop {
+            if let Message::Pong { seq: reply } = self.receive()? {
+                if reply == seq {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Sends a request, and waits for its reply.
+    ///
+    /// Messages which can't be the reply (such as readings the board sends
+    /// of its own accord) are skipped over, as long as they're of a different
+    /// type to the reply. An `Ack` for the request is returned as it is,
+    /// even if the request expects a different reply; the methods for each
+    /// request report that as `UnexpectedReply`.
+    pub fn request(&mut self, request: &Message) -> Result<Message, Error> {
+        let request_type = request.message_type();
+        self.send(request)?;
+        loop {
+            let reply = self.receive()?;
+            match (request, reply) {
+                (_, Message::Nak { request, reason }) if request == request_type => {
+                    return Err(Error::Refused(reason))
+                }
+                (_, Message::Ack { request }) if request == request_type => return Ok(reply),
+                (Message::ReadButtons, Message::Buttons { .. })
+                | (Message::ReadMagnetometer, Message::Magnetometer { .. })
+                | (Message::ReadAccelerometer, Message::Accelerometer { .. })
+                | (Message::ReadTemperature, Message::Temperature { .. })
+                | (Message::ReadRandom, Message::Random { .. }) => return Ok(reply),
+                (&Message::ReadPin { pin }, Message::PinLevel { pin: reply_pin, .. })
+                    if pin == reply_pin =>
+                {
+                    return Ok(reply)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Shows an image on the board's display, given the brightness of each
+    /// LED from 0 to 9.
+    pub fn set_image(&mut self, brightness: [[u8; 5]; 5]) -> Result<(), Error> {
+        self.request(&Message::SetImage { brightness }).map(|_| ())
+    }
+
+    /// Returns whether buttons A and B are pressed.
+    pub fn buttons(&mut self) -> Result<(bool, bool), Error> {
+        match self.request(&Message::ReadButtons)? {
+            Message::Buttons { a, b } => Ok((a, b)),
+            reply => Err(Error::UnexpectedReply(reply)),
+        }
+    }
+
+    /// Returns a magnetometer reading (x, y, z).
+    pub fn magnetometer(&mut self) -> Result<(i16, i16, i16), Error> {
+        match self.request(&Message::ReadMagnetometer)? {
+            Message::Magnetometer { x, y, z } => Ok((x, y, z)),
+            reply => Err(Error::UnexpectedReply(reply)),
+        }
+    }
+
+    /// Returns an accelerometer reading (x, y, z) in milli-g.
+    pub fn accelerometer(&mut self) -> Result<(i16, i16, i16), Error> {
+        match self.request(&Message::ReadAccelerometer)? {
+            Message::Accelerometer { x, y, z } => Ok((x, y, z)),
+            reply => Err(Error::UnexpectedReply(reply)),
+        }
+    }
+
+    /// Returns the board's temperature in °C.
+    pub fn temperature(&mut self) -> Result<f32, Error> {
+        match self.request(&Message::ReadTemperature)? {
+            Message::Temperature { quarter_degrees } => Ok(f32::from(quarter_degrees) / 4.0),
+            reply => Err(Error::UnexpectedReply(reply)),
+        }
+    }
+
+    /// Drives an edge connector pin high or low.
+    pub fn set_pin(&mut self, pin: u8, high: bool) -> Result<(), Error> {
+        self.request(&Message::SetPin { pin, high }).map(|_| ())
+    }
+
+    /// Reads the level of an edge connector pin.
+    pub fn read_pin(&mut self, pin: u8) -> Result<bool, Error> {
+        match self.request(&Message::ReadPin { pin })? {
+            Message::PinLevel { high, .. } => Ok(high),
+            reply => Err(Error::UnexpectedReply(reply)),
+        }
+    }
+
+    /// Returns a random number from the board's hardware RNG.
+    pub fn random(&mut self) -> Result<u32, Error> {
+        match self.request(&Message::ReadRandom)? {
+            Message::Random { value } => Ok(value),
+            reply => Err(Error::UnexpectedReply(reply)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// An in-memory stream: reads come from `incoming`, writes go to
+    /// `outgoing`.
+    #[derive(Default)]
+    struct Loopback {
+        incoming: VecDeque<u8>,
+        outgoing: Vec<u8>,
+    }
+
+    impl Loopback {
+        fn with_replies(replies: &[Message]) -> Loopback {
+            let mut loopback = Loopback::default();
+            for reply in replies {
+                loopback.queue(reply);
+            }
+            loopback
+        }
+
+        fn queue(&mut self, message: &Message) {
+            let mut buffer = [0; frame::MAX_FRAME_LEN];
+            self.incoming.extend(frame::encode(message, &mut buffer));
+        }
+
+        /// Decodes everything written so far.
+        fn sent(&self) -> Vec<Message> {
+            let mut decoder = FrameDecoder::new();
+            self.outgoing
+                .iter()
+                .filter_map(|&byte| decoder.push(byte))
+                .map(Result::unwrap)
+                .collect()
+        }
+    }
+
+    impl Read for Loopback {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.incoming.read(buf)
+        }
+    }
+
+    impl Write for Loopback {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.outgoing.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn send_writes_one_frame() {
+        let mut connection = Connection::new(Loopback::default());
+        connection.send(&Message::Ping { seq: 3 }).unwrap();
+        connection.send(&Message::ReadRandom).unwrap();
+        let loopback = connection.into_inner();
+        assert_eq!(
+            loopback.sent(),
+            [Message::Ping { seq: 3 }, Message::ReadRandom]
+        );
+    }
+
+    #[test]
+    fn receive_decodes_frames_in_order() {
+        let replies = [Message::Pong { seq: 1 }, Message::ReadButtons];
+        let mut connection = Connection::new(Loopback::with_replies(&replies));
+        assert_eq!(connection.receive().unwrap(), replies[0]);
+        assert_eq!(connection.receive().unwrap(), replies[1]);
+        match connection.receive() {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("expected end of stream, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn receive_reports_bad_frame_and_carries_on() {
+        let mut loopback = Loopback::default();
+        loopback.incoming.extend(&[0x03, 0x01, 0x02, 0x00]);
+        loopback.queue(&Message::Pong { seq: 9 });
+        let mut connection = Connection::new(loopback);
+        match connection.receive() {
+            Err(Error::Protocol(_)) => {}
+            other => panic!("expected a protocol error, got {:?}", other),
+        }
+        assert_eq!(connection.receive().unwrap(), Message::Pong { seq: 9 });
+    }
+
+    #[test]
+    fn ping_waits_for_matching_pong() {
+        let replies = [
+            Message::Temperature {
+                quarter_degrees: 80,
+            },
+            Message::Pong { seq: 4 },
+            Message::Pong { seq: 5 },
+        ];
+        let mut connection = Connection::new(Loopback::with_replies(&replies));
+        connection.ping(5).unwrap();
+        assert_eq!(connection.into_inner().sent(), [Message::Ping { seq: 5 }]);
+    }
+
+    #[test]
+    fn requests_return_their_replies() {
+        let replies = [
+            Message::Buttons { a: true, b: false },
+            Message::Accelerometer { x: 1, y: -2, z: 3 },
+            Message::Temperature {
+                quarter_degrees: 86,
+            },
+            Message::Ack { request: 0x25 },
+            Message::PinLevel { pin: 2, high: true },
+            Message::Random { value: 42 },
+        ];
+        let mut connection = Connection::new(Loopback::with_replies(&replies));
+        assert_eq!(connection.buttons().unwrap(), (true, false));
+        assert_eq!(connection.accelerometer().unwrap(), (1, -2, 3));
+        assert_eq!(connection.temperature().unwrap(), 21.5);
+        connection.set_pin(1, true).unwrap();
+        assert!(connection.read_pin(2).unwrap());
+        assert_eq!(connection.random().unwrap(), 42);
+        assert_eq!(
+            connection.into_inner().sent(),
+            [
+                Message::ReadButtons,
+                Message::ReadAccelerometer,
+                Message::ReadTemperature,
+                Message::SetPin { pin: 1, high: true },
+                Message::ReadPin { pin: 2 },
+                Message::ReadRandom,
+            ]
+        );
+    }
+
+    #[test]
+    fn request_skips_unrelated_messages() {
+        let replies = [
+            Message::Magnetometer { x: 0, y: 0, z: 0 },
+            Message::PinLevel {
+                pin: 1,
+                high: false,
+            },
+            Message::Ack { request: 0x20 },
+            Message::PinLevel { pin: 2, high: true },
+        ];
+        let mut connection = Connection::new(Loopback::with_replies(&replies));
+        assert!(connection.read_pin(2).unwrap());
+    }
+
+    #[test]
+    fn wrong_reply_is_an_error() {
+        let replies = [
+            Message::Ack { request: 0x21 },
+            Message::Ack { request: 0x27 },
+        ];
+        let mut connection = Connection::new(Loopback::with_replies(&replies));
+        match connection.buttons() {
+            Err(Error::UnexpectedReply(Message::Ack { request: 0x21 })) => {}
+            other => panic!("expected an unexpected reply, got {:?}", other),
+        }
+        match connection.random() {
+            Err(Error::UnexpectedReply(_)) => {}
+            other => panic!("expected an unexpected reply, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn nak_is_refused() {
+        let replies = [
+            Message::Nak {
+                request: 0x20,
+                reason: NAK_FAILED,
+            },
+            Message::Nak {
+                request: 0x22,
+                reason: NAK_UNSUPPORTED,
+            },
+        ];
+        let mut connection = Connection::new(Loopback::with_replies(&replies));
+        match connection.magnetometer() {
+            Err(Error::Refused(NAK_UNSUPPORTED)) => {}
+            other => panic!("expected a refusal, got {:?}", other),
+        }
+    }
+}
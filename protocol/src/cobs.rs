@@ -0,0 +1,176 @@
+//! Consistent Overhead Byte Stuffing.
+//!
+//! COBS rewrites a block of bytes so that it contains no zeros, at a cost of
+//! at most one extra byte per 254. A zero can then mark the end of each
+//! frame, so a receiver which starts listening part-way through a stream (or
+//! loses a byte) finds the start of the next frame straight away.
+
+use crate::Error;
+
+/// Returns the largest encoded size of `len` bytes of data.
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// Encodes `data` into `out`, and returns the number of bytes written.
+///
+/// The output doesn't include the terminating zero.
+pub fn encode(data: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let mut code_pos = 0;
+    let mut out_pos = 1;
+    let mut code: u8 = 1;
+    for &byte in data {
+        if byte != 0 {
+            *out.get_mut(out_pos).ok_or(Error::BufferTooSmall)? = byte;
+            out_pos += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            *out.get_mut(code_pos).ok_or(Error::BufferTooSmall)? = code;
+            code_pos = out_pos;
+            out_pos += 1;
+            code = 1;
+        }
+    }
+    *out.get_mut(code_pos).ok_or(Error::BufferTooSmall)? = code;
+    Ok(out_pos)
+}
+
+/// Decodes `data` (without its terminating zero) in place, and returns the
+/// length of the decoded data.
+pub fn decode_in_place(data: &mut [u8]) -> Result<usize, Error> {
+    let mut in_pos = 0;
+    let mut out_pos = 0;
+    while in_pos < data.len() {
+        let code = data[in_pos];
+        if code == 0 {
+            return Err(Error::Cobs);
+        }
+        let end = in_pos + code as usize;
+        if end > data.len() {
+            return Err(Error::Cobs);
+        }
+        for i in in_pos + 1..end {
+            if data[i] == 0 {
+                return Err(Error::Cobs);
+            }
+            data[out_pos] = data[i];
+            out_pos += 1;
+        }
+        in_pos = end;
+        if code != 0xFF && in_pos < data.len() {
+            data[out_pos] = 0;
+            out_pos += 1;
+        }
+    }
+    Ok(out_pos)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec;
+    use std::vec::Vec;
+
+    fn encoded(data: &[u8]) -> Vec<u8> {
+        let mut out = [0; 600];
+        let len = encode(data, &mut out).unwrap();
+        assert!(len <= max_encoded_len(data.len()));
+        assert!(!out[..len].contains(&0));
+        out[..len].to_vec()
+    }
+
+    fn decoded(data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut buffer = data.to_vec();
+        let len = decode_in_place(&mut buffer)?;
+        Ok(buffer[..len].to_vec())
+    }
+
+    fn assert_round_trip(data: &[u8]) {
+        assert_eq!(decoded(&encoded(data)).unwrap(), data);
+    }
+
+    #[test]
+    fn encodes_zeros() {
+        assert_eq!(encoded(&[]), [0x01]);
+        assert_eq!(encoded(&[0]), [0x01, 0x01]);
+        assert_eq!(encoded(&[0, 0]), [0x01, 0x01, 0x01]);
+        assert_eq!(
+            encoded(&[0x11, 0x22, 0, 0x33]),
+            [0x03, 0x11, 0x22, 0x02, 0x33]
+        );
+        assert_eq!(encoded(&[0x11, 0, 0, 0]), [0x02, 0x11, 0x01, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn decodes_zeros() {
+        assert_eq!(decoded(&[0x01]).unwrap(), []);
+        assert_eq!(decoded(&[0x01, 0x01]).unwrap(), [0]);
+        assert_eq!(
+            decoded(&[0x03, 0x11, 0x22, 0x02, 0x33]).unwrap(),
+            [0x11, 0x22, 0, 0x33]
+        );
+        assert_eq!(
+            decoded(&[0x02, 0x11, 0x01, 0x01, 0x01]).unwrap(),
+            [0x11, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn long_blocks() {
+        let data: Vec<u8> = (1..=255).collect();
+
+        // 254 non-zero bytes fill a block
+        let out = encoded(&data[..254]);
+        assert_eq!(out[0], 0xFF);
+        assert_eq!(out[1..255], data[..254]);
+        assert_round_trip(&data[..254]);
+        let mut canonical = vec![0xFF];
+        canonical.extend_from_slice(&data[..254]);
+        assert_eq!(decoded(&canonical).unwrap(), &data[..254]);
+
+        // The 255th starts another
+        let out = encoded(&data);
+        assert_eq!(out[0], 0xFF);
+        assert_eq!(out[255..], [0x02, 0xFF]);
+        assert_round_trip(&data);
+
+        // A zero straight after a full block
+        let mut with_zero = data[..254].to_vec();
+        with_zero.push(0);
+        assert_round_trip(&with_zero);
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut data = [0; 520];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = if i % 97 < 3 { 0 } else { i as u8 | 1 };
+        }
+        for len in 0..data.len() {
+            assert_round_trip(&data[..len]);
+        }
+        assert_round_trip(&[0; 300]);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        // A zero byte is never valid inside a frame
+        assert_eq!(decoded(&[0x00]), Err(Error::Cobs));
+        assert_eq!(decoded(&[0x02, 0x11, 0x00, 0x01]), Err(Error::Cobs));
+        assert_eq!(decoded(&[0x03, 0x00, 0x11]), Err(Error::Cobs));
+        // A block running past the end
+        assert_eq!(decoded(&[0x03, 0x11]), Err(Error::Cobs));
+        assert_eq!(decoded(&[0x01, 0xFF, 0x11]), Err(Error::Cobs));
+    }
+
+    #[test]
+    fn reports_small_buffer() {
+        let mut out = [0; 4];
+        assert_eq!(encode(&[1, 2, 3, 4], &mut out), Err(Error::BufferTooSmall));
+        assert_eq!(encode(&[1, 2, 3], &mut out), Ok(4));
+        assert_eq!(encode(&[], &mut []), Err(Error::BufferTooSmall));
+    }
+}
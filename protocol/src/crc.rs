@@ -0,0 +1,42 @@
+//! CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xFFFF).
+
+/// Returns the CRC of `data`.
+///
+/// ```
+/// assert_eq!(microbit_protocol::crc::crc16(b"123456789"), 0x29B1);
+/// ```
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::crc16;
+
+    #[test]
+    fn known_values() {
+        assert_eq!(crc16(b""), 0xFFFF);
+        assert_eq!(crc16(b"A"), 0xB915);
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(&[0]), 0xE1F0);
+    }
+
+    #[test]
+    fn appending_crc_leaves_zero() {
+        let mut data = *b"123456789\0\0";
+        let crc = crc16(&data[..9]).to_be_bytes();
+        data[9..].copy_from_slice(&crc);
+        assert_eq!(crc16(&data), 0);
+    }
+}
@@ -0,0 +1,187 @@
+//! Framing messages for a byte stream.
+//!
+//! A frame is a message followed by the CRC-16 of the message (low byte
+//! first), COBS-encoded, and then a zero byte.
+
+use crate::cobs;
+use crate::crc::crc16;
+use crate::message::{Message, MAX_MESSAGE_LEN};
+use crate::Error;
+
+/// The longest encoded frame, including the terminating zero.
+pub const MAX_FRAME_LEN: usize = cobs::max_encoded_len(MAX_MESSAGE_LEN + 2) + 1;
+
+/// Encodes a message as a frame in `out`, and returns the frame.
+pub fn encode<'a>(message: &Message, out: &'a mut [u8; MAX_FRAME_LEN]) -> &'a [u8] {
+    let mut raw = [0; MAX_MESSAGE_LEN + 2];
+    // Neither can fail: the buffers are big enough for any message.
+    let len = message.encode(&mut raw).unwrap_or(0);
+    let crc = crc16(&raw[..len]).to_le_bytes();
+    raw[len] = crc[0];
+    raw[len + 1] = crc[1];
+    let encoded_len = cobs::encode(&raw[..len + 2], &mut out[..]).unwrap_or(0);
+    out[encoded_len] = 0;
+    &out[..=encoded_len]
+}
+
+/// Collects bytes from a stream into frames, and decodes them.
+pub struct FrameDecoder {
+    buffer: [u8; MAX_FRAME_LEN],
+    len: usize,
+    /// Set while skipping the rest of a frame which was too long.
+    overflowed: bool,
+}
+
+impl FrameDecoder {
+    /// Returns a new `FrameDecoder`.
+    pub const fn new() -> FrameDecoder {
+        FrameDecoder {
+            buffer: [0; MAX_FRAME_LEN],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Takes the next byte from the stream.
+    ///
+    /// Returns `None` until the byte completes a frame, then the message or
+    /// the reason the frame couldn't be decoded.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Message, Error>> {
+        if byte != 0 {
+            if self.len < self.buffer.len() {
+                self.buffer[self.len] = byte;
+                self.len += 1;
+            } else {
+                self.overflowed = true;
+            }
+            return None;
+        }
+
+        let len = self.len;
+        let overflowed = self.overflowed;
+        self.len = 0;
+        self.overflowed = false;
+        if overflowed {
+            return Some(Err(Error::Overflow));
+        }
+        if len == 0 {
+            // Back-to-back zeros carry no frame; a sender may use them to
+            // resynchronise.
+            return None;
+        }
+        Some(decode(&mut self.buffer[..len]))
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> FrameDecoder {
+        FrameDecoder::new()
+    }
+}
+
+/// Decodes a frame (without its terminating zero) in place.
+fn decode(frame: &mut [u8]) -> Result<Message, Error> {
+    let len = cobs::decode_in_place(frame)?;
+    if len < 3 {
+        return Err(Error::Length);
+    }
+    let (data, crc) = frame[..len].split_at(len - 2);
+    if crc16(data).to_le_bytes() != [crc[0], crc[1]] {
+        return Err(Error::Crc);
+    }
+    Message::decode(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(decoder: &mut FrameDecoder, bytes: &[u8]) -> [Option<Result<Message, Error>>; 4] {
+        let mut results = [None; 4];
+        let mut count = 0;
+        for &byte in bytes {
+            if let Some(result) = decoder.push(byte) {
+                results[count] = Some(result);
+                count += 1;
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn frame_round_trips() {
+        let message = Message::Magnetometer {
+            x: 0,
+            y: 256,
+            z: -1,
+        };
+        let mut buffer = [0; MAX_FRAME_LEN];
+        let frame = encode(&message, &mut buffer);
+        assert_eq!(frame.last(), Some(&0));
+        assert!(!frame[..frame.len() - 1].contains(&0));
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(
+            decode_all(&mut decoder, frame),
+            [Some(Ok(message)), None, None, None]
+        );
+    }
+
+    #[test]
+    fn longest_message_fits() {
+        let message = Message::SetImage {
+            brightness: [[9; 5]; 5],
+        };
+        let mut buffer = [0; MAX_FRAME_LEN];
+        let frame = encode(&message, &mut buffer);
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decode_all(&mut decoder, frame)[0], Some(Ok(message)));
+    }
+
+    #[test]
+    fn corrupted_frame_is_reported_and_skipped() {
+        let mut buffer = [0; MAX_FRAME_LEN];
+        let mut stream = [0; 2 * MAX_FRAME_LEN];
+        let first = encode(&Message::Ping { seq: 1 }, &mut buffer);
+        let len = first.len();
+        stream[..len].copy_from_slice(first);
+        stream[1] ^= 0x40;
+        let second = encode(&Message::Ping { seq: 2 }, &mut buffer);
+        stream[len..len + second.len()].copy_from_slice(second);
+
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(
+            decode_all(&mut decoder, &stream[..len + second.len()]),
+            [
+                Some(Err(Error::Crc)),
+                Some(Ok(Message::Ping { seq: 2 })),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_and_short_frames() {
+        let mut decoder = FrameDecoder::new();
+        // Back-to-back zeros are ignored, a frame too short for a CRC isn't
+        assert_eq!(
+            decode_all(&mut decoder, &[0, 0, 0x02, 0x01, 0]),
+            [Some(Err(Error::Length)), None, None, None]
+        );
+    }
+
+    #[test]
+    fn overlong_frame_is_reported_once() {
+        let mut decoder = FrameDecoder::new();
+        let stream = [0x01; MAX_FRAME_LEN + 10];
+        assert_eq!(decode_all(&mut decoder, &stream), [None, None, None, None]);
+        assert_eq!(decoder.push(0), Some(Err(Error::Overflow)));
+
+        let mut buffer = [0; MAX_FRAME_LEN];
+        let frame = encode(&Message::ReadRandom, &mut buffer);
+        assert_eq!(
+            decode_all(&mut decoder, frame)[0],
+            Some(Ok(Message::ReadRandom))
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! A framed binary protocol for talking to a BBC micro:bit over its serial
+//! port.
+//!
+//! This crate is shared by the firmware (through the `microbit` crate's
+//! `framing` module) and host-side tools, so both ends always agree on the
+//! format. It's `no_std`; enable the `std` feature to get
+//! `std::error::Error` for [`Error`].
+//!
+//! Each [`Message`] is sent as one frame:
+//! 1. the message's type byte and payload (see [`message`])
+//! 2. followed by their CRC-16/CCITT-FALSE, low byte first
+//! 3. all COBS-encoded so that they contain no zero bytes
+//! 4. followed by a zero byte to end the frame.
+//!
+//! A receiver passes every byte to a [`FrameDecoder`], which reports each
+//! complete message. Corrupted frames are reported as errors and don't
+//! affect the frames which follow.
+//!
+//! ```
+//! use microbit_protocol::{frame, FrameDecoder, Message};
+//!
+//! let mut buffer = [0; frame::MAX_FRAME_LEN];
+//! let bytes = frame::encode(&Message::Ping { seq: 7 }, &mut buffer);
+//!
+//! let mut decoder = FrameDecoder::new();
+//! let received: Vec<_> = bytes.iter().filter_map(|&b| decoder.push(b)).collect();
+//! assert_eq!(received, [Ok(Message::Ping { seq: 7 })]);
+//! ```
+//!
+//! [`Error`]: Error
+//! [`Message`]: Message
+//! [`FrameDecoder`]: FrameDecoder
+#![no_std]
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt;
+
+pub mod cobs;
+pub mod crc;
+pub mod frame;
+pub mod message;
+
+pub use crate::frame::FrameDecoder;
+pub use crate::message::Message;
+
+/// Reasons a frame or message couldn't be handled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The output buffer is too small.
+    BufferTooSmall,
+    /// The frame isn't valid COBS.
+    Cobs,
+    /// The frame's CRC doesn't match its contents.
+    Crc,
+    /// The frame was longer than any valid frame.
+    Overflow,
+    /// The message is the wrong length for its type.
+    Length,
+    /// The message type isn't known.
+    UnknownType(u8),
+    /// A field has a value which isn't allowed.
+    BadValue,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::BufferTooSmall => f.write_str("buffer too small"),
+            Error::Cobs => f.write_str("invalid COBS encoding"),
+            Error::Crc => f.write_str("CRC mismatch"),
+            Error::Overflow => f.write_str("frame too long"),
+            Error::Length => f.write_str("wrong message length"),
+            Error::UnknownType(t) => write!(f, "unknown message type {:#04x}", t),
+            Error::BadValue => f.write_str("invalid field value"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
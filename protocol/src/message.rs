@@ -0,0 +1,426 @@
+//! The messages exchanged between the board and the host.
+//!
+//! Each message is a type byte followed by a fixed-size payload for that
+//! type. Multi-byte values are little-endian and signed values are two's
+//! complement.
+//!
+//! | Type   | Message          | Payload                        |
+//! |--------|------------------|--------------------------------|
+//! | `0x01` | `Ping`           | `seq: u8`                      |
+//! | `0x02` | `Pong`           | `seq: u8`                      |
+//! | `0x10` | `Magnetometer`   | `x: i16`, `y: i16`, `z: i16`   |
+//! | `0x11` | `Accelerometer`  | `x: i16`, `y: i16`, `z: i16`   |
+//! | `0x12` | `Temperature`    | `quarter_degrees: i16`         |
+//! | `0x13` | `Buttons`        | `a: u8`, `b: u8` (0 or 1)      |
+//!
+//! The host can also control the board remotely. It sends a request, and the
+//! board replies with the message shown, or with `Nak` if it can't carry out
+//! the request:
+//!
+//! | Type   | Message              | Payload                   | Reply          |
+//! |--------|----------------------|---------------------------|----------------|
+//! | `0x20` | `SetImage`           | 25 × `u8` (see below)     | `Ack`          |
+//! | `0x21` | `ReadButtons`        |                           | `Buttons`      |
+//! | `0x22` | `ReadMagnetometer`   |                           | `Magnetometer` |
+//! | `0x23` | `ReadAccelerometer`  |                           | `Accelerometer`|
+//! | `0x24` | `ReadTemperature`    |                           | `Temperature`  |
+//! | `0x25` | `SetPin`             | `pin: u8`, `high: u8`     | `Ack`          |
+//! | `0x26` | `ReadPin`            | `pin: u8`                 | `PinLevel`     |
+//! | `0x27` | `ReadRandom`         |                           | `Random`       |
+//!
+//! with these replies:
+//!
+//! | Type   | Message          | Payload                                  |
+//! |--------|------------------|------------------------------------------|
+//! | `0x03` | `Ack`            | `request: u8` (the request's type)       |
+//! | `0x04` | `Nak`            | `request: u8`, `reason: u8` (see below)  |
+//! | `0x14` | `PinLevel`       | `pin: u8`, `high: u8`                    |
+//! | `0x15` | `Random`         | `value: u32`                             |
+//!
+//! `SetImage` gives the brightness of each LED, from 0 to 9, a row at a time
+//! starting at the top left. Pins are numbered as on the edge connector
+//! (0 to 20).
+//!
+//! The `Nak` reasons are:
+//! - 0: the board doesn't support the request (for example it has no
+//!   driver for that sensor)
+//! - 1: an argument is out of range (for example a pin which can't be used)
+//! - 2: the hardware didn't respond (for example a sensor read failed).
+
+use crate::Error;
+
+/// The longest encoded message (type byte and payload).
+pub const MAX_MESSAGE_LEN: usize = 26;
+
+/// `Nak` reason: the request isn't supported.
+pub const NAK_UNSUPPORTED: u8 = 0;
+/// `Nak` reason: an argument is out of range.
+pub const NAK_BAD_ARGUMENT: u8 = 1;
+/// `Nak` reason: the hardware didn't respond.
+pub const NAK_FAILED: u8 = 2;
+
+/// A message between the board and the host.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// Asks the other side to reply with a `Pong` with the same `seq`.
+    Ping { seq: u8 },
+    /// The reply to a `Ping`.
+    Pong { seq: u8 },
+    /// A magnetometer reading, in the sensor's raw units.
+    Magnetometer { x: i16, y: i16, z: i16 },
+    /// An accelerometer reading, in milli-g.
+    Accelerometer { x: i16, y: i16, z: i16 },
+    /// A temperature, in units of 0.25°C.
+    Temperature { quarter_degrees: i16 },
+    /// The state of the buttons (`true` for pressed).
+    Buttons { a: bool, b: bool },
+    /// The level of an edge connector pin.
+    PinLevel { pin: u8, high: bool },
+    /// A random number from the hardware RNG.
+    Random { value: u32 },
+    /// Acknowledges a request of the given type which has no other reply.
+    Ack { request: u8 },
+    /// Reports that a request of the given type couldn't be carried out.
+    Nak { request: u8, reason: u8 },
+
+    /// Asks the board to show an image on its display.
+    SetImage { brightness: [[u8; 5]; 5] },
+    /// Asks the board for a `Buttons` reply.
+    ReadButtons,
+    /// Asks the board for a `Magnetometer` reply.
+    ReadMagnetometer,
+    /// Asks the board for an `Accelerometer` reply.
+    ReadAccelerometer,
+    /// Asks the board for a `Temperature` reply.
+    ReadTemperature,
+    /// Asks the board to drive an edge connector pin high or low.
+    SetPin { pin: u8, high: bool },
+    /// Asks the board for a `PinLevel` reply.
+    ReadPin { pin: u8 },
+    /// Asks the board for a `Random` reply.
+    ReadRandom,
+}
+
+impl Message {
+    /// Returns the message's type byte.
+    pub fn message_type(&self) -> u8 {
+        match self {
+            Message::Ping { .. } => 0x01,
+            Message::Pong { .. } => 0x02,
+            Message::Ack { .. } => 0x03,
+            Message::Nak { .. } => 0x04,
+            Message::Magnetometer { .. } => 0x10,
+            Message::Accelerometer { .. } => 0x11,
+            Message::Temperature { .. } => 0x12,
+            Message::Buttons { .. } => 0x13,
+            Message::PinLevel { .. } => 0x14,
+            Message::Random { .. } => 0x15,
+            Message::SetImage { .. } => 0x20,
+            Message::ReadButtons => 0x21,
+            Message::ReadMagnetometer => 0x22,
+            Message::ReadAccelerometer => 0x23,
+            Message::ReadTemperature => 0x24,
+            Message::SetPin { .. } => 0x25,
+            Message::ReadPin { .. } => 0x26,
+            Message::ReadRandom => 0x27,
+        }
+    }
+
+    /// Writes the message to `out`, and returns the number of bytes written.
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, Error> {
+        let mut writer = Writer { out, pos: 0 };
+        writer.u8(self.message_type())?;
+        match *self {
+            Message::Ping { seq } | Message::Pong { seq } => writer.u8(seq)?,
+            Message::Ack { request } => writer.u8(request)?,
+            Message::Nak { request, reason } => {
+                writer.u8(request)?;
+                writer.u8(reason)?;
+            }
+            Message::Magnetometer { x, y, z } | Message::Accelerometer { x, y, z } => {
+                writer.vector(x, y, z)?
+            }
+            Message::Temperature { quarter_degrees } => writer.i16(quarter_degrees)?,
+            Message::Buttons { a, b } => {
+                writer.u8(a as u8)?;
+                writer.u8(b as u8)?;
+            }
+            Message::PinLevel { pin, high } | Message::SetPin { pin, high } => {
+                writer.u8(pin)?;
+                writer.u8(high as u8)?;
+            }
+            Message::Random { value } => {
+                for &byte in value.to_le_bytes().iter() {
+                    writer.u8(byte)?;
+                }
+            }
+            Message::SetImage { brightness } => {
+                for &value in brightness.iter().flatten() {
+                    writer.u8(value)?;
+                }
+            }
+            Message::ReadPin { pin } => writer.u8(pin)?,
+            Message::ReadButtons
+            | Message::ReadMagnetometer
+            | Message::ReadAccelerometer
+            | Message::ReadTemperature
+            | Message::ReadRandom => {}
+        }
+        Ok(writer.pos)
+    }
+
+    /// Reads a message from `data`, which must hold exactly one message.
+    pub fn decode(data: &[u8]) -> Result<Message, Error> {
+        let (&message_type, payload) = data.split_first().ok_or(Error::Length)?;
+        let mut reader = Reader { data: payload };
+        let message = match message_type {
+            0x01 => Message::Ping { seq: reader.u8()? },
+            0x02 => Message::Pong { seq: reader.u8()? },
+            0x10 => {
+                let (x, y, z) = reader.vector()?;
+                Message::Magnetometer { x, y, z }
+            }
+            0x11 => {
+                let (x, y, z) = reader.vector()?;
+                Message::Accelerometer { x, y, z }
+            }
+            0x12 => Message::Temperature {
+                quarter_degrees: reader.i16()?,
+            },
+            0x13 => Message::Buttons {
+                a: reader.bool()?,
+                b: reader.bool()?,
+            },
+            0x03 => Message::Ack {
+                request: reader.u8()?,
+            },
+            0x04 => Message::Nak {
+                request: reader.u8()?,
+                reason: reader.u8()?,
+            },
+            0x14 => Message::PinLevel {
+                pin: reader.u8()?,
+                high: reader.bool()?,
+            },
+            0x15 => {
+                let mut bytes = [0; 4];
+                for byte in bytes.iter_mut() {
+                    *byte = reader.u8()?;
+                }
+                Message::Random {
+                    value: u32::from_le_bytes(bytes),
+                }
+            }
+            0x20 => {
+                let mut brightness = [[0; 5]; 5];
+                for value in brightness.iter_mut().flatten() {
+                    *value = reader.u8()?;
+                    if *value > 9 {
+                        return Err(Error::BadValue);
+                    }
+                }
+                Message::SetImage { brightness }
+            }
+            0x21 => Message::ReadButtons,
+            0x22 => Message::ReadMagnetometer,
+            0x23 => Message::ReadAccelerometer,
+            0x24 => Message::ReadTemperature,
+            0x25 => Message::SetPin {
+                pin: reader.u8()?,
+                high: reader.bool()?,
+            },
+            0x26 => Message::ReadPin { pin: reader.u8()? },
+            0x27 => Message::ReadRandom,
+            other => return Err(Error::UnknownType(other)),
+        };
+        if !reader.data.is_empty() {
+            return Err(Error::Length);
+        }
+        Ok(message)
+    }
+}
+
+struct Writer<'a> {
+    out: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn u8(&mut self, value: u8) -> Result<(), Error> {
+        *self.out.get_mut(self.pos).ok_or(Error::BufferTooSmall)? = value;
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn i16(&mut self, value: i16) -> Result<(), Error> {
+        let bytes = value.to_le_bytes();
+        self.u8(bytes[0])?;
+        self.u8(bytes[1])
+    }
+
+    fn vector(&mut self, x: i16, y: i16, z: i16) -> Result<(), Error> {
+        self.i16(x)?;
+        self.i16(y)?;
+        self.i16(z)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn u8(&mut self) -> Result<u8, Error> {
+        let (&value, rest) = self.data.split_first().ok_or(Error::Length)?;
+        self.data = rest;
+        Ok(value)
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::BadValue),
+        }
+    }
+
+    fn i16(&mut self) -> Result<i16, Error> {
+        let low = self.u8()?;
+        let high = self.u8()?;
+        Ok(i16::from_le_bytes([low, high]))
+    }
+
+    fn vector(&mut self) -> Result<(i16, i16, i16), Error> {
+        Ok((self.i16()?, self.i16()?, self.i16()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One of each message.
+    const MESSAGES: [Message; 18] = [
+        Message::Ping { seq: 0xA5 },
+        Message::Pong { seq: 0 },
+        Message::Magnetometer {
+            x: -1,
+            y: i16::MAX,
+            z: i16::MIN,
+        },
+        Message::Accelerometer {
+            x: 1000,
+            y: -1000,
+            z: 0x1234,
+        },
+        Message::Temperature {
+            quarter_degrees: -41,
+        },
+        Message::Buttons { a: true, b: false },
+        Message::PinLevel {
+            pin: 20,
+            high: true,
+        },
+        Message::Random { value: 0xDEAD_BEEF },
+        Message::Ack { request: 0x20 },
+        Message::Nak {
+            request: 0x26,
+            reason: NAK_BAD_ARGUMENT,
+        },
+        Message::SetImage {
+            brightness: [
+                [0, 1, 2, 3, 4],
+                [5, 6, 7, 8, 9],
+                [9, 0, 9, 0, 9],
+                [1, 1, 1, 1, 1],
+                [0, 0, 0, 0, 0],
+            ],
+        },
+        Message::ReadButtons,
+        Message::ReadMagnetometer,
+        Message::ReadAccelerometer,
+        Message::ReadTemperature,
+        Message::SetPin {
+            pin: 3,
+            high: false,
+        },
+        Message::ReadPin { pin: 19 },
+        Message::ReadRandom,
+    ];
+
+    fn encoded(message: &Message) -> ([u8; MAX_MESSAGE_LEN + 1], usize) {
+        let mut buffer = [0; MAX_MESSAGE_LEN + 1];
+        let len = message.encode(&mut buffer).unwrap();
+        (buffer, len)
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        for message in MESSAGES.iter() {
+            let (buffer, len) = encoded(message);
+            assert!(len <= MAX_MESSAGE_LEN);
+            assert_eq!(buffer[0], message.message_type());
+            assert_eq!(Message::decode(&buffer[..len]), Ok(*message));
+        }
+    }
+
+    #[test]
+    fn encodes_little_endian() {
+        let (buffer, len) = encoded(&Message::Accelerometer {
+            x: 0x1234,
+            y: -2,
+            z: 1,
+        });
+        assert_eq!(buffer[..len], [0x11, 0x34, 0x12, 0xFE, 0xFF, 0x01, 0x00]);
+        let (buffer, len) = encoded(&Message::Random { value: 0x0102_0304 });
+        assert_eq!(buffer[..len], [0x15, 0x04, 0x03, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn rejects_truncated_messages() {
+        for message in MESSAGES.iter() {
+            let (buffer, len) = encoded(message);
+            for short in 0..len {
+                assert_eq!(
+                    Message::decode(&buffer[..short]),
+                    Err(Error::Length),
+                    "{:?} truncated to {} bytes",
+                    message,
+                    short
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_bytes() {
+        for message in MESSAGES.iter() {
+            let (buffer, len) = encoded(message);
+            assert_eq!(
+                Message::decode(&buffer[..=len]),
+                Err(Error::Length),
+                "{:?}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        assert_eq!(Message::decode(&[0x7F]), Err(Error::UnknownType(0x7F)));
+        assert_eq!(Message::decode(&[0x13, 0, 2]), Err(Error::BadValue));
+        let mut image = [1; 26];
+        image[0] = 0x20;
+        image[25] = 10;
+        assert_eq!(Message::decode(&image), Err(Error::BadValue));
+    }
+
+    #[test]
+    fn reports_small_buffer() {
+        let mut buffer = [0; 2];
+        assert_eq!(
+            Message::Temperature { quarter_degrees: 1 }.encode(&mut buffer),
+            Err(Error::BufferTooSmall)
+        );
+    }
+}
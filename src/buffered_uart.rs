@@ -0,0 +1,388 @@
+//! Interrupt-driven UART with receive and transmit buffers.
+//!
+//! The `Tx` and `Rx` halves returned by [`serial_port()`] work a byte at a
+//! time: writing waits for the previous byte to be sent, and received bytes
+//! are lost if they aren't read before the next one arrives. [`BufferedUart`]
+//! takes over the UART from those halves and moves bytes between the UART
+//! and two ring buffers from the `UART0` interrupt handler, so that the rest
+//! of the program never has to wait for the serial port.
+//!
+//! [`read()`] and [`write()`] never block: they return how many bytes were
+//! actually taken from or added to the buffers. Bytes which arrive while the
+//! receive buffer is full are dropped and counted, as are the errors reported
+//! by the UART itself; see [`error_counts()`].
+//!
+//! The buffers are supplied by the caller, so their sizes can be chosen to
+//! suit the application. Neither may be empty.
+//!
+//! [`free()`] waits for the byte being sent to finish, and hands the UART
+//! back in the state the `Tx` and `Rx` halves expect, so they can be used
+//! directly again.
+//!
+//! # Sharing with the interrupt handler
+//!
+//! `BufferedUart` must be reachable both from the `UART0` interrupt handler
+//! (which calls [`handle_interrupt()`]) and from the code reading and writing
+//! it. In bare-metal code, keep it in a `Mutex<RefCell<Option<..>>>` static
+//! and use it inside `cortex_m::interrupt::free()`; with RTFM, make it a
+//! resource shared between the `UART0` task and the others.
+//!
+//! # Example
+//!
+//! ```ignore
+//! static mut RX_BUFFER: [u8; 64] = [0; 64];
+//! static mut TX_BUFFER: [u8; 256] = [0; 256];
+//!
+//! let (tx, rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+//! let uart = BufferedUart::new(tx, rx, unsafe { &mut RX_BUFFER }, unsafe { &mut TX_BUFFER });
+//!
+//! // Later, with interrupts enabled:
+//! let mut buf = [0; 16];
+//! let count = uart.read(&mut buf);
+//! uart.write(&buf[..count]);
+//!
+//! #[interrupt]
+//! fn UART0() {
+//!     uart.handle_interrupt();
+//! }
+//! ```
+//!
+//! [`serial_port()`]: serial_port
+//! [`BufferedUart`]: buffered_uart::BufferedUart
+//! [`read()`]: buffered_uart::BufferedUart::read
+//! [`write()`]: buffered_uart::BufferedUart::write
+//! [`error_counts()`]: buffered_uart::BufferedUart::error_counts
+//! [`handle_interrupt()`]: buffered_uart::BufferedUart::handle_interrupt
+//! [`free()`]: buffered_uart::BufferedUart::free
+
+use core::fmt;
+
+use crate::hal::nrf51::{uart0, UART0};
+use crate::hal::serial::{Rx, Tx};
+
+/// ERRORSRC bits.
+const ERROR_OVERRUN: u32 = 1 << 0;
+const ERROR_PARITY: u32 = 1 << 1;
+const ERROR_FRAMING: u32 = 1 << 2;
+const ERROR_BREAK: u32 = 1 << 3;
+
+/// INTEN bits.
+const INT_RXDRDY: u32 = 1 << 2;
+const INT_TXDRDY: u32 = 1 << 7;
+const INT_ERROR: u32 = 1 << 9;
+
+/// Numbers of errors seen by a [`BufferedUart`].
+///
+/// The counts wrap around if they overflow.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorCounts {
+    /// Bytes dropped because the receive buffer was full.
+    pub buffer_overruns: u32,
+    /// Bytes lost by the UART because they weren't collected in time.
+    pub overruns: u32,
+    /// Bytes received with a bad parity bit.
+    pub parity_errors: u32,
+    /// Bytes received without a valid stop bit.
+    pub framing_errors: u32,
+    /// Break conditions (the line held low for longer than a byte).
+    pub breaks: u32,
+}
+
+/// A fixed-size FIFO of bytes in a caller-supplied buffer.
+struct RingBuffer {
+    buffer: &'static mut [u8],
+    head: usize,
+    len: usize,
+}
+
+impl RingBuffer {
+    fn new(buffer: &'static mut [u8]) -> RingBuffer {
+        RingBuffer {
+            buffer,
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == self.buffer.len()
+    }
+
+    fn push(&mut self, byte: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        let index = (self.head + self.len) % self.buffer.len();
+        self.buffer[index] = byte;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.buffer[self.head];
+        self.head = (self.head + 1) % self.buffer.len();
+        self.len -= 1;
+        Some(byte)
+    }
+
+    /// Adds as many bytes from `data` as there's room for, and returns how
+    /// many were added.
+    fn push_slice(&mut self, data: &[u8]) -> usize {
+        data.iter().take_while(|&&byte| self.push(byte)).count()
+    }
+
+    /// Takes as many bytes as are waiting (up to the size of `buf`), and
+    /// returns how many were copied into `buf`.
+    fn pop_into(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        for slot in buf.iter_mut() {
+            match self.pop() {
+                Some(byte) => *slot = byte,
+                None => break,
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+/// UART0, with interrupt-driven receive and transmit buffers.
+///
+/// See the [module-level documentation](buffered_uart) for details.
+pub struct BufferedUart {
+    tx: Tx<UART0>,
+    rx: Rx<UART0>,
+    rx_buffer: RingBuffer,
+    tx_buffer: RingBuffer,
+    /// Set while a byte is being sent (so a TXDRDY event is to come).
+    ///
+    /// While nothing is being sent, the TXDRDY event for the last byte is
+    /// left set (with its interrupt disabled), as the HAL's `Tx` expects.
+    sending: bool,
+    errors: ErrorCounts,
+}
+
+impl BufferedUart {
+    /// Takes over a serial port opened with [`serial_port()`] (or
+    /// [`serial_port_on_pins()`]), and enables its interrupts.
+    ///
+    /// The `UART0` interrupt still needs to be unmasked in the NVIC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either buffer is empty.
+    ///
+    /// [`serial_port()`]: serial_port
+    /// [`serial_port_on_pins()`]: serial_port_on_pins
+    pub fn new(
+        tx: Tx<UART0>,
+        rx: Rx<UART0>,
+        rx_buffer: &'static mut [u8],
+        tx_buffer: &'static mut [u8],
+    ) -> BufferedUart {
+        assert!(!rx_buffer.is_empty() && !tx_buffer.is_empty());
+        let uart = uart();
+        uart.events_rxdrdy.write(|w| unsafe { w.bits(0) });
+        uart.events_error.write(|w| unsafe { w.bits(0) });
+        let errors = uart.errorsrc.read().bits();
+        uart.errorsrc.write(|w| unsafe { w.bits(errors) });
+        uart.intenset
+            .write(|w| unsafe { w.bits(INT_RXDRDY | INT_ERROR) });
+        // A byte written through `Tx` may still be on its way out.
+        let sending = uart.events_txdrdy.read().bits() == 0;
+        if sending {
+            uart.intenset.write(|w| unsafe { w.bits(INT_TXDRDY) });
+        }
+        BufferedUart {
+            tx,
+            rx,
+            rx_buffer: RingBuffer::new(rx_buffer),
+            tx_buffer: RingBuffer::new(tx_buffer),
+            sending,
+            errors: ErrorCounts::default(),
+        }
+    }
+
+    /// Disables the interrupts and gives back the `Tx` and `Rx` halves.
+    ///
+    /// This waits for the byte currently being sent, if any; the rest of
+    /// the bytes still in the buffers are discarded.
+    pub fn free(self) -> (Tx<UART0>, Rx<UART0>) {
+        let uart = uart();
+        uart.intenclr
+            .write(|w| unsafe { w.bits(INT_RXDRDY | INT_TXDRDY | INT_ERROR) });
+        // `Tx` waits for TXDRDY before each byte, so leave it set.
+        if self.sending {
+            while uart.events_txdrdy.read().bits() == 0 {}
+        }
+        (self.tx, self.rx)
+    }
+
+    /// Takes received bytes from the receive buffer, and returns how many
+    /// were copied into `buf`.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        self.rx_buffer.pop_into(buf)
+    }
+
+    /// Adds bytes to the transmit buffer, and returns how many there was
+    /// room for.
+    pub fn write(&mut self, data: &[u8]) -> usize {
+        let count = self.tx_buffer.push_slice(data);
+        if !self.sending {
+            self.send_next();
+        }
+        count
+    }
+
+    /// Returns the number of received bytes waiting to be read.
+    pub fn available(&self) -> usize {
+        self.rx_buffer.len
+    }
+
+    /// Reports whether all written bytes have been sent.
+    pub fn is_flushed(&self) -> bool {
+        !self.sending
+    }
+
+    /// Returns the numbers of errors seen so far.
+    pub fn error_counts(&self) -> ErrorCounts {
+        self.errors
+    }
+
+    /// Handles a UART interrupt.
+    ///
+    /// Call this from the `UART0` interrupt handler.
+    pub fn handle_interrupt(&mut self) {
+        let uart = uart();
+
+        if uart.events_error.read().bits() != 0 {
+            uart.events_error.write(|w| unsafe { w.bits(0) });
+            let source = uart.errorsrc.read().bits();
+            uart.errorsrc.write(|w| unsafe { w.bits(source) });
+            let errors = &mut self.errors;
+            if source & ERROR_OVERRUN != 0 {
+                errors.overruns = errors.overruns.wrapping_add(1);
+            }
+            if source & ERROR_PARITY != 0 {
+                errors.parity_errors = errors.parity_errors.wrapping_add(1);
+            }
+            if source & ERROR_FRAMING != 0 {
+                errors.framing_errors = errors.framing_errors.wrapping_add(1);
+            }
+            if source & ERROR_BREAK != 0 {
+                errors.breaks = errors.breaks.wrapping_add(1);
+            }
+        }
+
+        if uart.events_rxdrdy.read().bits() != 0 {
+            // The event must be cleared before reading RXD, or a byte
+            // arriving in between would be missed.
+            uart.events_rxdrdy.write(|w| unsafe { w.bits(0) });
+            let byte = uart.rxd.read().bits() as u8;
+            if !self.rx_buffer.push(byte) {
+                self.errors.buffer_overruns = self.errors.buffer_overruns.wrapping_add(1);
+            }
+        }
+
+        if self.sending && uart.events_txdrdy.read().bits() != 0 {
+            self.sending = false;
+            self.send_next();
+        }
+    }
+
+    /// Starts sending the next byte from the transmit buffer, if there is
+    /// one.
+    ///
+    /// Otherwise the TXDRDY event is left set, and its interrupt disabled.
+    fn send_next(&mut self) {
+        let uart = uart();
+        if let Some(byte) = self.tx_buffer.pop() {
+            self.sending = true;
+            uart.events_txdrdy.write(|w| unsafe { w.bits(0) });
+            uart.txd.write(|w| unsafe { w.bits(u32::from(byte)) });
+            uart.intenset.write(|w| unsafe { w.bits(INT_TXDRDY) });
+        } else {
+            uart.intenclr.write(|w| unsafe { w.bits(INT_TXDRDY) });
+        }
+    }
+}
+
+/// Writes as much of the string as fits in the transmit buffer.
+///
+/// Returns an error if some of it had to be dropped.
+impl fmt::Write for BufferedUart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.write(s.as_bytes()) == s.len() {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
+fn uart() -> &'static uart0::RegisterBlock {
+    unsafe { &*UART0::ptr() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring_buffer(size: usize) -> RingBuffer {
+        RingBuffer::new(Box::leak(vec![0; size].into_boxed_slice()))
+    }
+
+    #[test]
+    fn push_until_full() {
+        let mut ring = ring_buffer(3);
+        assert!(ring.push(1));
+        assert!(ring.push(2));
+        assert!(!ring.is_full());
+        assert!(ring.push(3));
+        assert!(ring.is_full());
+        assert!(!ring.push(4));
+        assert_eq!(ring.len, 3);
+        assert_eq!(ring.pop(), Some(1));
+        assert!(ring.push(4));
+    }
+
+    #[test]
+    fn pop_order_across_the_wrap_point() {
+        let mut ring = ring_buffer(3);
+        assert!(ring.push(200));
+        let mut popped = Vec::new();
+        // The buffer is full after each pair of pushes, and the start moves
+        // round it several times.
+        for byte in 0..10 {
+            assert!(ring.push(byte));
+            assert!(ring.push(byte + 100));
+            assert!(ring.is_full());
+            popped.extend(ring.pop());
+            popped.extend(ring.pop());
+        }
+        popped.extend(core::iter::from_fn(|| ring.pop()));
+        let mut expected = vec![200];
+        expected.extend((0..10).flat_map(|byte| vec![byte, byte + 100]));
+        assert_eq!(popped, expected);
+    }
+
+    #[test]
+    fn push_slice_returns_partial_count() {
+        let mut ring = ring_buffer(4);
+        assert_eq!(ring.push_slice(b"ab"), 2);
+        assert_eq!(ring.push_slice(b"cdef"), 2);
+        assert_eq!(ring.push_slice(b"g"), 0);
+        let mut buf = [0; 3];
+        assert_eq!(ring.pop_into(&mut buf), 3);
+        assert_eq!(&buf, b"abc");
+        // Fill across the wrap point.
+        assert_eq!(ring.push_slice(b"xyz"), 3);
+        let mut buf = [0; 8];
+        assert_eq!(ring.pop_into(&mut buf), 4);
+        assert_eq!(&buf[..4], b"dxyz");
+        assert_eq!(ring.pop_into(&mut buf), 0);
+    }
+}
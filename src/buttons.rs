@@ -0,0 +1,607 @@
+//! Debounced driver for the on-board buttons A and B.
+//!
+//! [`Buttons`] owns the two button pins (`PIN17` for A, `PIN26` for B) and
+//! turns their raw state into a stream of [`Event`]s:
+//! - [`Down`] and [`Up`] when a button's debounced state changes
+//! - [`Click`] after a short press, once it's clear no second press is
+//!   coming
+//! - [`DoubleClick`] after two short presses in quick succession
+//! - [`LongPress`] once a button has been held down long enough.
+//!
+//! Holding both buttons down at once produces the same events for the
+//! combined button [`Button::AB`]. While that happens, clicks and long
+//! presses of the individual buttons are suppressed (their `Down` and `Up`
+//! events are still reported).
+//!
+//! The timings are set using [`Timings`].
+//!
+//! # Time
+//!
+//! `Buttons` doesn't use a timer itself: each call to [`update()`] passes the
+//! current time in milliseconds, from whatever clock the application has
+//! (for example an RTC tick count). Times are allowed to wrap around.
+//!
+//! # Polling or interrupts
+//!
+//! The simplest way to use `Buttons` is to call [`update()`] regularly (every
+//! 10ms or so) and then take events with [`next_event()`].
+//!
+//! Alternatively, set up GPIOTE to interrupt when either pin changes and
+//! call `update()` from the interrupt handler. Debouncing and the click and
+//! long-press timeouts need `update()` to be called again after the pins
+//! have stopped changing, so as long as [`needs_update()`] returns `true`
+//! keep calling it from a periodic timer as well.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let gpio = p.GPIO.split();
+//! let mut buttons = Buttons::new(
+//!     gpio.pin17.into_floating_input(),
+//!     gpio.pin26.into_floating_input(),
+//!     Timings::default(),
+//! );
+//!
+//! // Every 10ms:
+//! buttons.update(now_ms);
+//! while let Some(event) = buttons.next_event() {
+//!     // ...
+//! }
+//! ```
+//!
+//! [`Buttons`]: buttons::Buttons
+//! [`Event`]: buttons::Event
+//! [`Down`]: buttons::Event::Down
+//! [`Up`]: buttons::Event::Up
+//! [`Click`]: buttons::Event::Click
+//! [`DoubleClick`]: buttons::Event::DoubleClick
+//! [`LongPress`]: buttons::Event::LongPress
+//! [`Button::AB`]: buttons::Button::AB
+//! [`Timings`]: buttons::Timings
+//! [`update()`]: buttons::Buttons::update
+//! [`next_event()`]: buttons::Buttons::next_event
+//! [`needs_update()`]: buttons::Buttons::needs_update
+
+use crate::hal::gpio::gpio::{PIN17, PIN26};
+use crate::hal::gpio::{Floating, Input};
+use crate::hal::prelude::*;
+
+/// Number of events which can be waiting to be taken by
+/// [`next_event()`](Buttons::next_event).
+const EVENT_QUEUE_LEN: usize = 8;
+
+/// One of the buttons.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Button {
+    /// Button A (on the left).
+    A,
+    /// Button B (on the right).
+    B,
+    /// Buttons A and B held down together.
+    AB,
+}
+
+/// Something that happened to a button.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The button was pressed.
+    Down(Button),
+    /// The button was released.
+    Up(Button),
+    /// The button was pressed and released once.
+    Click(Button),
+    /// The button was pressed and released twice in quick succession.
+    DoubleClick(Button),
+    /// The button has been held down for the long-press time.
+    LongPress(Button),
+}
+
+/// Timing parameters for [`Buttons`], in milliseconds.
+#[derive(Copy, Clone, Debug)]
+pub struct Timings {
+    /// How long a pin must stay at a new level before the change is
+    /// accepted.
+    pub debounce_ms: u32,
+    /// How long a button must be held down to report a long press.
+    pub long_press_ms: u32,
+    /// The longest gap between releasing a button and pressing it again for
+    /// the two presses to count as a double-click.
+    ///
+    /// A single click is only reported once this much time has passed
+    /// without a second press. Set it to 0 to disable double-clicks and
+    /// report clicks immediately.
+    pub double_click_ms: u32,
+}
+
+impl Default for Timings {
+    /// 20ms debounce, 1s long press, 300ms double-click gap.
+    fn default() -> Timings {
+        Timings {
+            debounce_ms: 20,
+            long_press_ms: 1000,
+            double_click_ms: 300,
+        }
+    }
+}
+
+/// Debounces the raw level of one pin.
+#[derive(Copy, Clone, Debug)]
+struct Debouncer {
+    stable: bool,
+    raw: bool,
+    raw_since: u32,
+}
+
+impl Debouncer {
+    const fn new() -> Debouncer {
+        Debouncer {
+            stable: false,
+            raw: false,
+            raw_since: 0,
+        }
+    }
+
+    /// Takes a new raw sample, and returns the debounced state.
+    fn update(&mut self, raw: bool, now: u32, debounce_ms: u32) -> bool {
+        if raw != self.raw {
+            self.raw = raw;
+            self.raw_since = now;
+        }
+        if self.raw != self.stable && now.wrapping_sub(self.raw_since) >= debounce_ms {
+            self.stable = self.raw;
+        }
+        self.stable
+    }
+
+    fn is_settling(&self) -> bool {
+        self.raw != self.stable
+    }
+}
+
+/// Click and long-press recognition for one (debounced) button.
+#[derive(Copy, Clone, Debug)]
+struct Recogniser {
+    pressed: bool,
+    pressed_at: u32,
+    long_reported: bool,
+    /// Set while a press is being ignored for clicks and long presses.
+    suppressed: bool,
+    /// Time of the release of a click which might become a double-click.
+    click_pending: Option<u32>,
+    /// Set while the second press of a double-click is held down.
+    second_press: bool,
+}
+
+impl Recogniser {
+    const fn new() -> Recogniser {
+        Recogniser {
+            pressed: false,
+            pressed_at: 0,
+            long_reported: false,
+            suppressed: false,
+            click_pending: None,
+            second_press: false,
+        }
+    }
+
+    /// Updates the state given the debounced button state, and reports any
+    /// events.
+    ///
+    /// If `suppress` is set, the current press won't produce a click or long
+    /// press.
+    fn update(
+        &mut self,
+        button: Button,
+        pressed: bool,
+        suppress: bool,
+        now: u32,
+        timings: &Timings,
+        mut report: impl FnMut(Event),
+    ) {
+        if let Some(released_at) = self.click_pending {
+            if !self.pressed && now.wrapping_sub(released_at) >= timings.double_click_ms {
+                self.click_pending = None;
+                report(Event::Click(button));
+            }
+        }
+
+        if pressed && !self.pressed {
+            self.pressed = true;
+            self.pressed_at = now;
+            self.long_reported = false;
+            self.suppressed = false;
+            self.second_press = self.click_pending.is_some();
+            report(Event::Down(button));
+        }
+
+        self.suppressed |= suppress;
+
+        if self.pressed
+            && !self.long_reported
+            && !self.suppressed
+            && now.wrapping_sub(self.pressed_at) >= timings.long_press_ms
+        {
+            self.long_reported = true;
+            // The first press of a would-be double-click was a click.
+            if self.click_pending.take().is_some() {
+                report(Event::Click(button));
+            }
+            report(Event::LongPress(button));
+        }
+
+        if !pressed && self.pressed {
+            self.pressed = false;
+            report(Event::Up(button));
+            if self.suppressed || self.long_reported {
+                if self.click_pending.take().is_some() {
+                    report(Event::Click(button));
+                }
+            } else if self.second_press && self.click_pending.is_some() {
+                self.click_pending = None;
+                report(Event::DoubleClick(button));
+            } else if timings.double_click_ms == 0 {
+                report(Event::Click(button));
+            } else {
+                self.click_pending = Some(now);
+            }
+            self.second_press = false;
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        !self.pressed && self.click_pending.is_none()
+    }
+}
+
+/// A small queue of events waiting to be taken.
+#[derive(Copy, Clone, Debug)]
+struct EventQueue {
+    events: [Option<Event>; EVENT_QUEUE_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl EventQueue {
+    const fn new() -> EventQueue {
+        EventQueue {
+            events: [None; EVENT_QUEUE_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Adds an event, dropping it if the queue is full.
+    fn push(&mut self, event: Event) {
+        if self.len < EVENT_QUEUE_LEN {
+            self.events[(self.head + self.len) % EVENT_QUEUE_LEN] = Some(event);
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<Event> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.events[self.head].take();
+        self.head = (self.head + 1) % EVENT_QUEUE_LEN;
+        self.len -= 1;
+        event
+    }
+}
+
+/// The state of both buttons, updated from raw samples.
+#[derive(Copy, Clone, Debug)]
+struct State {
+    timings: Timings,
+    debounce_a: Debouncer,
+    debounce_b: Debouncer,
+    a: Recogniser,
+    b: Recogniser,
+    ab: Recogniser,
+    events: EventQueue,
+}
+
+impl State {
+    const fn new(timings: Timings) -> State {
+        State {
+            timings,
+            debounce_a: Debouncer::new(),
+            debounce_b: Debouncer::new(),
+            a: Recogniser::new(),
+            b: Recogniser::new(),
+            ab: Recogniser::new(),
+            events: EventQueue::new(),
+        }
+    }
+
+    /// Updates the button states from raw samples of the two buttons
+    /// (`true` meaning pressed).
+    fn update_from_samples(&mut self, raw_a: bool, raw_b: bool, now: u32) {
+        let timings = self.timings;
+        let pressed_a = self.debounce_a.update(raw_a, now, timings.debounce_ms);
+        let pressed_b = self.debounce_b.update(raw_b, now, timings.debounce_ms);
+        let both = pressed_a && pressed_b;
+        // Once A+B is pressed, it stays active until both are released.
+        let ab_active = both || (self.ab.pressed && (pressed_a || pressed_b));
+
+        let events = &mut self.events;
+        self.a
+            .update(Button::A, pressed_a, ab_active, now, &timings, |e| {
+                events.push(e)
+            });
+        self.b
+            .update(Button::B, pressed_b, ab_active, now, &timings, |e| {
+                events.push(e)
+            });
+        self.ab
+            .update(Button::AB, ab_active, false, now, &timings, |e| {
+                events.push(e)
+            });
+    }
+
+    fn is_pressed(&self, button: Button) -> bool {
+        match button {
+            Button::A => self.a.pressed,
+            Button::B => self.b.pressed,
+            Button::AB => self.ab.pressed,
+        }
+    }
+
+    fn needs_update(&self) -> bool {
+        self.debounce_a.is_settling()
+            || self.debounce_b.is_settling()
+            || !self.a.is_idle()
+            || !self.b.is_idle()
+            || !self.ab.is_idle()
+    }
+}
+
+/// The on-board buttons A and B, producing debounced events.
+///
+/// See the [module-level documentation](buttons) for details.
+pub struct Buttons {
+    pin_a: PIN17<Input<Floating>>,
+    pin_b: PIN26<Input<Floating>>,
+    state: State,
+}
+
+impl Buttons {
+    /// Takes ownership of the button pins.
+    ///
+    /// The buttons have external pull-up resistors, so the pins should be
+    /// floating inputs.
+    pub fn new(
+        button_a: PIN17<Input<Floating>>,
+        button_b: PIN26<Input<Floating>>,
+        timings: Timings,
+    ) -> Buttons {
+        Buttons {
+            pin_a: button_a,
+            pin_b: button_b,
+            state: State::new(timings),
+        }
+    }
+
+    /// Gives the button pins back.
+    pub fn free(self) -> (PIN17<Input<Floating>>, PIN26<Input<Floating>>) {
+        (self.pin_a, self.pin_b)
+    }
+
+    /// Changes the timing parameters.
+    pub fn set_timings(&mut self, timings: Timings) {
+        self.state.timings = timings;
+    }
+
+    /// Samples the pins and updates the button states.
+    ///
+    /// `now_ms` is the current time in milliseconds.
+    pub fn update(&mut self, now_ms: u32) {
+        // The buttons pull their pins low when pressed.
+        let raw_a = self.pin_a.is_low().unwrap_or(false);
+        let raw_b = self.pin_b.is_low().unwrap_or(false);
+        self.state.update_from_samples(raw_a, raw_b, now_ms);
+    }
+
+    /// Returns the oldest event which hasn't been taken yet.
+    ///
+    /// Up to eight events are kept; if more arrive before they're taken, the
+    /// newest are dropped.
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.state.events.pop()
+    }
+
+    /// Reports whether a button is currently pressed (after debouncing).
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.state.is_pressed(button)
+    }
+
+    /// Reports whether [`update()`](Buttons::update) needs to be called
+    /// again even if the pins don't change.
+    ///
+    /// This is `true` while a pin is being debounced, while a button is held
+    /// down (to detect long presses), and while waiting to see whether a
+    /// click becomes a double-click.
+    pub fn needs_update(&self) -> bool {
+        self.state.needs_update()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMINGS: Timings = Timings {
+        debounce_ms: 20,
+        long_press_ms: 1000,
+        double_click_ms: 300,
+    };
+
+    /// Samples the buttons every 5ms from `from` (inclusive) to `to`
+    /// (exclusive).
+    fn hold(state: &mut State, from: u32, to: u32, a: bool, b: bool) {
+        for now in (from..to).step_by(5) {
+            state.update_from_samples(a, b, now);
+        }
+    }
+
+    fn events(state: &mut State) -> Vec<Event> {
+        core::iter::from_fn(|| state.events.pop()).collect()
+    }
+
+    #[test]
+    fn bouncing_is_ignored_until_stable() {
+        let mut state = State::new(TIMINGS);
+        for (i, now) in (0..35).step_by(5).enumerate() {
+            state.update_from_samples(i % 2 == 0, false, now);
+            assert!(!state.is_pressed(Button::A));
+        }
+        assert!(state.needs_update());
+        hold(&mut state, 35, 50, true, false);
+        assert!(!state.is_pressed(Button::A));
+        hold(&mut state, 50, 55, true, false);
+        assert!(state.is_pressed(Button::A));
+        assert_eq!(events(&mut state), [Event::Down(Button::A)]);
+    }
+
+    #[test]
+    fn debounce_delays_both_edges() {
+        let mut state = State::new(TIMINGS);
+        hold(&mut state, 0, 20, true, false);
+        assert!(!state.is_pressed(Button::A));
+        hold(&mut state, 20, 25, true, false);
+        assert!(state.is_pressed(Button::A));
+        hold(&mut state, 25, 45, false, false);
+        assert!(state.is_pressed(Button::A));
+        hold(&mut state, 45, 50, false, false);
+        assert!(!state.is_pressed(Button::A));
+    }
+
+    #[test]
+    fn click_is_reported_after_double_click_gap() {
+        let mut state = State::new(TIMINGS);
+        hold(&mut state, 0, 100, true, false);
+        hold(&mut state, 100, 300, false, false);
+        assert_eq!(
+            events(&mut state),
+            [Event::Down(Button::A), Event::Up(Button::A)]
+        );
+        assert!(state.needs_update());
+        hold(&mut state, 300, 500, false, false);
+        assert_eq!(events(&mut state), [Event::Click(Button::A)]);
+        assert!(!state.needs_update());
+    }
+
+    #[test]
+    fn click_is_immediate_without_double_clicks() {
+        let mut state = State::new(Timings {
+            double_click_ms: 0,
+            ..TIMINGS
+        });
+        hold(&mut state, 0, 100, false, true);
+        hold(&mut state, 100, 150, false, false);
+        assert_eq!(
+            events(&mut state),
+            [
+                Event::Down(Button::B),
+                Event::Up(Button::B),
+                Event::Click(Button::B),
+            ]
+        );
+    }
+
+    #[test]
+    fn double_click() {
+        let mut state = State::new(TIMINGS);
+        hold(&mut state, 0, 100, true, false);
+        hold(&mut state, 100, 200, false, false);
+        hold(&mut state, 200, 300, true, false);
+        hold(&mut state, 300, 800, false, false);
+        assert_eq!(
+            events(&mut state),
+            [
+                Event::Down(Button::A),
+                Event::Up(Button::A),
+                Event::Down(Button::A),
+                Event::Up(Button::A),
+                Event::DoubleClick(Button::A),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_press_is_not_a_click() {
+        let mut state = State::new(TIMINGS);
+        hold(&mut state, 0, 1030, true, false);
+        assert_eq!(
+            events(&mut state),
+            [Event::Down(Button::A), Event::LongPress(Button::A)]
+        );
+        hold(&mut state, 1030, 2000, true, false);
+        assert!(events(&mut state).is_empty());
+        hold(&mut state, 2000, 2500, false, false);
+        assert_eq!(events(&mut state), [Event::Up(Button::A)]);
+    }
+
+    #[test]
+    fn click_before_long_press_is_reported() {
+        let mut state = State::new(TIMINGS);
+        hold(&mut state, 0, 100, true, false);
+        hold(&mut state, 100, 200, false, false);
+        hold(&mut state, 200, 1300, true, false);
+        hold(&mut state, 1300, 1400, false, false);
+        assert_eq!(
+            events(&mut state),
+            [
+                Event::Down(Button::A),
+                Event::Up(Button::A),
+                Event::Down(Button::A),
+                Event::Click(Button::A),
+                Event::LongPress(Button::A),
+                Event::Up(Button::A),
+            ]
+        );
+    }
+
+    #[test]
+    fn both_buttons_suppress_single_clicks() {
+        let mut state = State::new(TIMINGS);
+        hold(&mut state, 0, 100, true, true);
+        hold(&mut state, 100, 600, false, false);
+        assert_eq!(
+            events(&mut state),
+            [
+                Event::Down(Button::A),
+                Event::Down(Button::B),
+                Event::Down(Button::AB),
+                Event::Up(Button::A),
+                Event::Up(Button::B),
+                Event::Up(Button::AB),
+                Event::Click(Button::AB),
+            ]
+        );
+    }
+
+    #[test]
+    fn full_queue_drops_newest_events() {
+        let mut state = State::new(Timings {
+            double_click_ms: 0,
+            ..TIMINGS
+        });
+        for i in 0..4 {
+            let start = i * 200;
+            hold(&mut state, start, start + 100, true, false);
+            hold(&mut state, start + 100, start + 200, false, false);
+        }
+        let events = events(&mut state);
+        assert_eq!(events.len(), EVENT_QUEUE_LEN);
+        assert_eq!(
+            events[..3],
+            [
+                Event::Down(Button::A),
+                Event::Up(Button::A),
+                Event::Click(Button::A),
+            ]
+        );
+        assert_eq!(events[EVENT_QUEUE_LEN - 1], Event::Up(Button::A));
+    }
+}
@@ -1,9 +1,13 @@
-//! Implementation of [`DisplayControl`] for the micro:bit's GPIO peripheral.
+//! Implementation of [`DisplayControl`] for the micro:bit's display pins.
 //!
 //! This controls the micro:bit's 5×5 LED display.
 //!
 //! [`DisplayControl`]: tiny_led_matrix::DisplayControl
 
+use crate::hal::gpio::gpio::{
+    PIN10, PIN11, PIN12, PIN13, PIN14, PIN15, PIN4, PIN5, PIN6, PIN7, PIN8, PIN9,
+};
+use crate::hal::gpio::{Output, PushPull};
 use crate::hal::nrf51;
 use tiny_led_matrix::DisplayControl;
 
@@ -12,52 +16,112 @@ const fn bit_range(lo: usize, count: usize) -> u32 {
 }
 
 pub(crate) const MATRIX_COLS: usize = 9;
-const FIRST_COL_PIN: usize = 4;
-const LAST_COL_PIN: usize = FIRST_COL_PIN + MATRIX_COLS - 1;
-const COL_BITS: u32 = bit_range(FIRST_COL_PIN, MATRIX_COLS);
+pub(crate) const FIRST_COL_PIN: usize = 4;
+pub(crate) const COL_BITS: u32 = bit_range(FIRST_COL_PIN, MATRIX_COLS);
 
 pub(crate) const MATRIX_ROWS: usize = 3;
-const FIRST_ROW_PIN: usize = 13;
-const LAST_ROW_PIN: usize = FIRST_ROW_PIN + MATRIX_ROWS - 1;
-const ROW_BITS: u32 = bit_range(FIRST_ROW_PIN, MATRIX_ROWS);
+pub(crate) const FIRST_ROW_PIN: usize = 13;
+pub(crate) const ROW_BITS: u32 = bit_range(FIRST_ROW_PIN, MATRIX_ROWS);
 
-/// Wrapper for `nrf51::GPIO` for passing to the display code.
+/// The twelve GPIO pins connected to the LED matrix.
 ///
-/// This implements the `DisplayControl` trait.
+/// The display code only touches these pins, so the rest of the GPIO port
+/// stays available to other code (including code running at other interrupt
+/// priorities).
 ///
+/// The fields are public so that the pins can be given back after use.
+///
+/// The [`display_pins!`] macro builds a `DisplayPins` from the
+/// `gpio::Parts` returned by `GPIO.split()`, leaving the other pins in
+/// place:
+///
+/// ```ignore
+/// let gpio = p.GPIO.split();
+/// let mut pins = microbit::display_pins!(gpio);
+/// let button_a = gpio.pin17.into_floating_input();
+/// ```
+///
+/// `DisplayPins` implements [`DisplayControl`].
+///
+/// [`display_pins!`]: crate::display_pins
 /// [`DisplayControl`]: tiny_led_matrix::DisplayControl
-pub(crate) struct MicrobitGpio<'a>(pub &'a nrf51::GPIO);
+pub struct DisplayPins {
+    pub col1: PIN4<Output<PushPull>>,
+    pub col2: PIN5<Output<PushPull>>,
+    pub col3: PIN6<Output<PushPull>>,
+    pub col4: PIN7<Output<PushPull>>,
+    pub col5: PIN8<Output<PushPull>>,
+    pub col6: PIN9<Output<PushPull>>,
+    pub col7: PIN10<Output<PushPull>>,
+    pub col8: PIN11<Output<PushPull>>,
+    pub col9: PIN12<Output<PushPull>>,
+    pub row1: PIN13<Output<PushPull>>,
+    pub row2: PIN14<Output<PushPull>>,
+    pub row3: PIN15<Output<PushPull>>,
+}
+
+/// Takes the display pins out of a `gpio::Parts` and returns them as
+/// [`DisplayPins`], configured as push-pull outputs.
+///
+/// The argument must be a place (typically a local variable) holding the
+/// result of `GPIO.split()`; the remaining pins can still be used
+/// afterwards.
+///
+/// [`DisplayPins`]: crate::display::DisplayPins
+#[macro_export]
+macro_rules! display_pins {
+    ($gpio:expr) => {
+        $crate::display::DisplayPins {
+            col1: $gpio.pin4.into_push_pull_output(),
+            col2: $gpio.pin5.into_push_pull_output(),
+            col3: $gpio.pin6.into_push_pull_output(),
+            col4: $gpio.pin7.into_push_pull_output(),
+            col5: $gpio.pin8.into_push_pull_output(),
+            col6: $gpio.pin9.into_push_pull_output(),
+            col7: $gpio.pin10.into_push_pull_output(),
+            col8: $gpio.pin11.into_push_pull_output(),
+            col9: $gpio.pin12.into_push_pull_output(),
+            row1: $gpio.pin13.into_push_pull_output(),
+            row2: $gpio.pin14.into_push_pull_output(),
+            row3: $gpio.pin15.into_push_pull_output(),
+        }
+    };
+}
+
+impl DisplayPins {
+    /// Returns the GPIO registers.
+    ///
+    /// Only the OUTSET and OUTCLR registers are written, and only with bits
+    /// for the pins owned by the `DisplayPins`, so this doesn't interfere
+    /// with other users of the port.
+    pub(crate) fn gpio(&self) -> &'static nrf51::gpio::RegisterBlock {
+        unsafe { &*nrf51::GPIO::ptr() }
+    }
+}
 
 /// Returns the GPIO pin numbers corresponding to the columns in a ColumnSet.
-fn column_pins(cols: u32) -> u32 {
+pub(crate) fn column_pins(cols: u32) -> u32 {
     cols << FIRST_COL_PIN
 }
 
-/// Implementation of [`DisplayControl`] for the micro:bit's GPIO peripheral.
+/// Implementation of [`DisplayControl`] for the micro:bit's display pins.
 ///
 /// This controls the micro:bit's 5×5 LED display.
 ///
-/// The `initialise_for display` implementation assumes the port is in the
-/// state it would have after system reset.
+/// The pins are already outputs, so `initialise_for_display` only has to
+/// switch all the LEDs off.
 ///
 /// [`DisplayControl`]: tiny_led_matrix::DisplayControl
-impl DisplayControl for MicrobitGpio<'_> {
+impl DisplayControl for DisplayPins {
     fn initialise_for_display(&mut self) {
-        let gpio = &self.0;
-        for ii in FIRST_COL_PIN..=LAST_COL_PIN {
-            gpio.pin_cnf[ii].write(|w| w.dir().output());
-        }
-        for ii in FIRST_ROW_PIN..=LAST_ROW_PIN {
-            gpio.pin_cnf[ii].write(|w| w.dir().output());
-        }
-
-        // Set all cols high.
-        gpio.outset
-            .write(|w| unsafe { w.bits((FIRST_COL_PIN..=LAST_COL_PIN).map(|pin| 1 << pin).sum()) });
+        let gpio = self.gpio();
+        // Set all cols high, and all rows low.
+        gpio.outset.write(|w| unsafe { w.bits(COL_BITS) });
+        gpio.outclr.write(|w| unsafe { w.bits(ROW_BITS) });
     }
 
     fn display_row_leds(&mut self, row: usize, cols: u32) {
-        let gpio = &self.0;
+        let gpio = self.gpio();
         // To light an LED, we set the row bit and clear the col bit.
         let rows_to_set = 1 << (FIRST_ROW_PIN + row);
         let rows_to_clear = ROW_BITS ^ rows_to_set;
@@ -71,7 +135,7 @@ impl DisplayControl for MicrobitGpio<'_> {
     }
 
     fn light_current_row_leds(&mut self, cols: u32) {
-        let gpio = &self.0;
+        let gpio = self.gpio();
         gpio.outclr.write(|w| unsafe { w.bits(column_pins(cols)) });
     }
 }
@@ -0,0 +1,158 @@
+//! Support for drawing on the display with `embedded-graphics`.
+//!
+//! This module is only available with the `embedded-graphics` feature.
+//!
+//! [`GreyscaleImage`] implements [`DrawTarget`], using [`Brightness`] as its
+//! colour type, so the primitives, fonts and image formats from the
+//! `embedded-graphics` ecosystem can draw into a 5×5 image. The image can
+//! then be put in a [`MicrobitFrame`] in the usual way.
+//!
+//! `Brightness` can be converted from `BinaryColor` (off or fully on) and
+//! from the greyscale colour types (scaled down to the 0 to 9 range), so
+//! drawables using those colours can be shown using
+//! [`color_converted()`][color_converted].
+//!
+//! # Example
+//!
+//! ```ignore
+//! use embedded_graphics::prelude::*;
+//! use embedded_graphics::primitives::{Circle, PrimitiveStyle};
+//! use microbit::display::graphics::Brightness;
+//!
+//! let mut image = GreyscaleImage::blank();
+//! Circle::new(Point::new(0, 0), 5)
+//!     .into_styled(PrimitiveStyle::with_stroke(Brightness::new(6), 1))
+//!     .draw(&mut image)?;
+//! frame.set(&image);
+//! ```
+//!
+//! [`Brightness`]: display::graphics::Brightness
+//! [`DrawTarget`]: embedded_graphics_core::draw_target::DrawTarget
+//! [`GreyscaleImage`]: display::image::GreyscaleImage
+//! [`MicrobitFrame`]: display::MicrobitFrame
+//! [color_converted]: embedded_graphics_core::draw_target::DrawTargetExt::color_converted
+
+use core::convert::Infallible;
+
+use embedded_graphics_core::draw_target::DrawTarget;
+use embedded_graphics_core::geometry::{OriginDimensions, Size};
+use embedded_graphics_core::pixelcolor::raw::{RawData, RawU4};
+use embedded_graphics_core::pixelcolor::{BinaryColor, Gray2, Gray4, Gray8, GrayColor, PixelColor};
+use embedded_graphics_core::Pixel;
+use tiny_led_matrix::{Frame, MAX_BRIGHTNESS};
+
+use crate::display::image::GreyscaleImage;
+use crate::display::MicrobitFrame;
+
+/// The brightness of one LED, as an `embedded-graphics` colour.
+///
+/// Values range from 0 (off) to 9 (brightest), as described in the
+/// [greyscale model](display#greyscale-model).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Brightness(u8);
+
+impl Brightness {
+    /// The LED switched off.
+    pub const OFF: Brightness = Brightness(0);
+
+    /// The LED at full brightness.
+    pub const MAX: Brightness = Brightness(MAX_BRIGHTNESS);
+
+    /// Returns a `Brightness` for the given level.
+    ///
+    /// Levels greater than 9 are treated as 9.
+    pub const fn new(level: u8) -> Brightness {
+        if level > MAX_BRIGHTNESS {
+            Brightness::MAX
+        } else {
+            Brightness(level)
+        }
+    }
+
+    /// Returns the brightness level, from 0 to 9.
+    pub const fn level(self) -> u8 {
+        self.0
+    }
+
+    /// Scales a luma value in `0..=max` to a brightness level.
+    fn from_luma(luma: u8, max: u8) -> Brightness {
+        let level = (u16::from(luma) * MAX_BRIGHTNESS as u16 + u16::from(max) / 2) / u16::from(max);
+        Brightness(level as u8)
+    }
+}
+
+impl PixelColor for Brightness {
+    type Raw = RawU4;
+}
+
+impl From<RawU4> for Brightness {
+    fn from(raw: RawU4) -> Brightness {
+        Brightness::new(raw.into_inner())
+    }
+}
+
+impl From<Brightness> for RawU4 {
+    fn from(brightness: Brightness) -> RawU4 {
+        RawU4::new(brightness.0)
+    }
+}
+
+impl From<BinaryColor> for Brightness {
+    fn from(color: BinaryColor) -> Brightness {
+        match color {
+            BinaryColor::Off => Brightness::OFF,
+            BinaryColor::On => Brightness::MAX,
+        }
+    }
+}
+
+impl From<Gray2> for Brightness {
+    fn from(color: Gray2) -> Brightness {
+        Brightness::from_luma(color.luma(), 3)
+    }
+}
+
+impl From<Gray4> for Brightness {
+    fn from(color: Gray4) -> Brightness {
+        Brightness::from_luma(color.luma(), 15)
+    }
+}
+
+impl From<Gray8> for Brightness {
+    fn from(color: Gray8) -> Brightness {
+        Brightness::from_luma(color.luma(), 255)
+    }
+}
+
+impl OriginDimensions for GreyscaleImage {
+    fn size(&self) -> Size {
+        Size::new(5, 5)
+    }
+}
+
+/// Pixels outside the 5×5 area are ignored.
+impl DrawTarget for GreyscaleImage {
+    type Color = Brightness;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if (0..5).contains(&point.x) && (0..5).contains(&point.y) {
+                self.set_brightness(point.x as usize, point.y as usize, color.level());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<&GreyscaleImage> for MicrobitFrame {
+    /// Returns a frame holding the image.
+    fn from(image: &GreyscaleImage) -> MicrobitFrame {
+        let mut frame = MicrobitFrame::default();
+        frame.set(image);
+        frame
+    }
+}
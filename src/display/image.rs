@@ -32,6 +32,11 @@ impl GreyscaleImage {
     pub const fn blank() -> GreyscaleImage {
         GreyscaleImage([[0; 5]; 5])
     }
+
+    /// Sets the brightness of the pixel at (x, y).
+    pub(crate) fn set_brightness(&mut self, x: usize, y: usize, brightness: u8) {
+        self.0[y][x] = brightness;
+    }
 }
 
 impl Render for GreyscaleImage {
@@ -0,0 +1,298 @@
+//! Mirroring displayed images to a text sink, for debugging.
+//!
+//! A [`MirroredDisplay`] wraps the [`Display`], and its [`set_frame()`]
+//! both shows an image and writes a 5×5 text rendering of it to anything
+//! implementing `core::fmt::Write` (for example a [`BufferedUart`], or the
+//! UART `Tx` returned by [`serial_port()`]). Every image which reaches the
+//! display through it is mirrored. The rendering and rate limiting are done
+//! by a [`FrameMirror`], which can also be used on its own.
+//!
+//! Each LED is shown as one character, from `' '` (off) to `'@'`
+//! (brightness 9):
+//!
+//! ```text
+//! +-----+
+//! | # # |
+//! |#:#:#|
+//! |#:::#|
+//! | #:# |
+//! |  #  |
+//! +-----+
+//! ```
+//!
+//! Writing to a serial port is slow compared to updating the display, so the
+//! mirror is rate-limited: it writes at most one rendering in each
+//! `min_interval`, and only the most recent image is kept while it waits.
+//! Times are given by the caller, in whatever units it likes (for example
+//! RTC ticks); they're allowed to wrap around.
+//!
+//! The `MirroredDisplay` is shared with the display's interrupt handler
+//! (which reaches the `Display` through [`display_mut()`]), so the mirror
+//! writes to its sink while the display interrupt is held off. Use a sink
+//! which doesn't wait, such as a `BufferedUart` (whose own interrupt
+//! handler is reached through [`sink_mut()`]): a blocking `Tx` stalls the
+//! display for as long as a rendering takes to send.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mirror = FrameMirror::new(uart, 16);
+//! let mut display = MirroredDisplay::new(Display::new(), mirror);
+//!
+//! // In the code which updates the display:
+//! display.set_frame(&image, now)?;
+//!
+//! // Periodically, to write out images which arrived too quickly:
+//! display.poll(now)?;
+//!
+//! // In the display timer's interrupt handler:
+//! display::handle_display_event(display.display_mut(), timer, pins);
+//! ```
+//!
+//! [`MirroredDisplay`]: display::mirror::MirroredDisplay
+//! [`Display`]: display::Display
+//! [`set_frame()`]: display::mirror::MirroredDisplay::set_frame
+//! [`display_mut()`]: display::mirror::MirroredDisplay::display_mut
+//! [`sink_mut()`]: display::mirror::FrameMirror::sink_mut
+//! [`FrameMirror`]: display::mirror::FrameMirror
+//! [`BufferedUart`]: buffered_uart::BufferedUart
+//! [`serial_port()`]: serial_port
+
+use core::fmt::{self, Write};
+
+use tiny_led_matrix::{Display, Frame, Render, MAX_BRIGHTNESS};
+
+use crate::display::MicrobitFrame;
+
+/// Characters used for each brightness level, from 0 to 9.
+const GREYSCALE_CHARS: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+/// Writes text renderings of displayed images to a `core::fmt::Write` sink.
+///
+/// See the [module-level documentation](display::mirror) for details.
+pub struct FrameMirror<W: Write> {
+    sink: W,
+    min_interval: u32,
+    last_written: Option<u32>,
+    image: [[u8; 5]; 5],
+    pending: bool,
+}
+
+impl<W: Write> FrameMirror<W> {
+    /// Returns a new `FrameMirror` writing to `sink`, at most once in each
+    /// `min_interval`.
+    pub fn new(sink: W, min_interval: u32) -> FrameMirror<W> {
+        FrameMirror {
+            sink,
+            min_interval,
+            last_written: None,
+            image: [[0; 5]; 5],
+            pending: false,
+        }
+    }
+
+    /// Gives the sink back.
+    pub fn free(self) -> W {
+        self.sink
+    }
+
+    /// Returns the sink (for example to handle a `BufferedUart`'s
+    /// interrupt).
+    pub fn sink_mut(&mut self) -> &mut W {
+        &mut self.sink
+    }
+
+    /// Records a new image, and writes it out if `min_interval` has passed
+    /// since the last rendering was written.
+    ///
+    /// Otherwise the image is kept until a later call to [`poll()`] or
+    /// `mirror()`. Images identical to the last one recorded are ignored.
+    ///
+    /// [`poll()`]: FrameMirror::poll
+    pub fn mirror<R: Render + ?Sized>(&mut self, image: &R, now: u32) -> fmt::Result {
+        let mut changed = false;
+        for (y, row) in self.image.iter_mut().enumerate() {
+            for (x, value) in row.iter_mut().enumerate() {
+                let brightness = image.brightness_at(x, y).min(MAX_BRIGHTNESS);
+                changed |= *value != brightness;
+                *value = brightness;
+            }
+        }
+        if changed || self.last_written.is_none() {
+            self.pending = true;
+        }
+        self.poll(now)
+    }
+
+    /// Writes out the most recent image, if it hasn't been written yet and
+    /// `min_interval` has passed since the last rendering was written.
+    pub fn poll(&mut self, now: u32) -> fmt::Result {
+        if !self.pending {
+            return Ok(());
+        }
+        if let Some(last) = self.last_written {
+            if now.wrapping_sub(last) < self.min_interval {
+                return Ok(());
+            }
+        }
+        self.pending = false;
+        self.last_written = Some(now);
+        self.write_image()
+    }
+
+    fn write_image(&mut self) -> fmt::Result {
+        self.sink.write_str("+-----+\r\n")?;
+        for row in self.image.iter() {
+            self.sink.write_char('|')?;
+            for &brightness in row.iter() {
+                self.sink.write_char(GREYSCALE_CHARS[brightness as usize])?;
+            }
+            self.sink.write_str("|\r\n")?;
+        }
+        self.sink.write_str("+-----+\r\n")
+    }
+}
+
+/// A [`Display`] which mirrors every image it's given.
+///
+/// See the [module-level documentation](display::mirror) for details.
+///
+/// [`Display`]: display::Display
+pub struct MirroredDisplay<W: Write> {
+    display: Display<MicrobitFrame>,
+    mirror: FrameMirror<W>,
+    frame: MicrobitFrame,
+}
+
+impl<W: Write> MirroredDisplay<W> {
+    /// Wraps `display`, mirroring its images with `mirror`.
+    pub fn new(display: Display<MicrobitFrame>, mirror: FrameMirror<W>) -> MirroredDisplay<W> {
+        MirroredDisplay {
+            display,
+            mirror,
+            frame: MicrobitFrame::default(),
+        }
+    }
+
+    /// Gives back the `Display` and the `FrameMirror`.
+    pub fn free(self) -> (Display<MicrobitFrame>, FrameMirror<W>) {
+        (self.display, self.mirror)
+    }
+
+    /// Shows an image, and mirrors it (see [`FrameMirror::mirror()`]).
+    ///
+    /// The image is shown even if writing the rendering fails.
+    ///
+    /// [`FrameMirror::mirror()`]: display::mirror::FrameMirror::mirror
+    pub fn set_frame<R: Render + ?Sized>(&mut self, image: &R, now: u32) -> fmt::Result {
+        self.frame.set(image);
+        self.display.set_frame(&self.frame);
+        self.mirror.mirror(image, now)
+    }
+
+    /// Writes out an image which arrived too soon after the last one (see
+    /// [`FrameMirror::poll()`]).
+    ///
+    /// [`FrameMirror::poll()`]: display::mirror::FrameMirror::poll
+    pub fn poll(&mut self, now: u32) -> fmt::Result {
+        self.mirror.poll(now)
+    }
+
+    /// Returns the `Display`, for [`handle_display_event()`].
+    ///
+    /// Images set directly on it aren't mirrored.
+    ///
+    /// [`handle_display_event()`]: display::handle_display_event
+    pub fn display_mut(&mut self) -> &mut Display<MicrobitFrame> {
+        &mut self.display
+    }
+
+    /// Returns the `FrameMirror`.
+    pub fn mirror_mut(&mut self) -> &mut FrameMirror<W> {
+        &mut self.mirror
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::image::GreyscaleImage;
+
+    const BLANK: &str =
+        "+-----+\r\n|     |\r\n|     |\r\n|     |\r\n|     |\r\n|     |\r\n+-----+\r\n";
+
+    fn dot(brightness: u8) -> GreyscaleImage {
+        let mut image = GreyscaleImage::blank();
+        image.set_brightness(2, 2, brightness);
+        image
+    }
+
+    /// Returns how many renderings have been written.
+    fn renderings(mirror: &mut FrameMirror<String>) -> usize {
+        mirror.sink_mut().matches("+-----+\r\n|").count()
+    }
+
+    #[test]
+    fn renders_every_brightness() {
+        let mut image = GreyscaleImage::blank();
+        for brightness in 0..10 {
+            image.set_brightness(brightness as usize % 5, brightness as usize / 5, brightness);
+        }
+        let mut mirror = FrameMirror::new(String::new(), 1);
+        mirror.mirror(&image, 0).unwrap();
+        assert_eq!(
+            mirror.free(),
+            "+-----+\r\n| .:-=|\r\n|+*#%@|\r\n|     |\r\n|     |\r\n|     |\r\n+-----+\r\n"
+        );
+    }
+
+    #[test]
+    fn first_image_is_written_straight_away() {
+        let mut mirror = FrameMirror::new(String::new(), 100);
+        mirror.mirror(&GreyscaleImage::blank(), 5).unwrap();
+        assert_eq!(mirror.free(), BLANK);
+    }
+
+    #[test]
+    fn rate_limit_keeps_the_latest_image() {
+        let mut mirror = FrameMirror::new(String::new(), 10);
+        mirror.mirror(&dot(1), 0).unwrap();
+        mirror.mirror(&dot(2), 3).unwrap();
+        mirror.mirror(&dot(3), 6).unwrap();
+        mirror.poll(9).unwrap();
+        assert_eq!(renderings(&mut mirror), 1);
+
+        // The second image was replaced before it could be written.
+        mirror.poll(10).unwrap();
+        assert_eq!(renderings(&mut mirror), 2);
+        assert!(mirror
+            .sink_mut()
+            .ends_with("|  -  |\r\n|     |\r\n|     |\r\n+-----+\r\n"));
+        assert!(!mirror.sink_mut().contains("|  :  |"));
+
+        // Nothing is pending, so polling writes nothing more.
+        mirror.poll(100).unwrap();
+        assert_eq!(renderings(&mut mirror), 2);
+    }
+
+    #[test]
+    fn rate_limit_across_wrap_around() {
+        let mut mirror = FrameMirror::new(String::new(), 10);
+        mirror.mirror(&dot(1), u32::MAX - 4).unwrap();
+        mirror.mirror(&dot(2), 2).unwrap();
+        assert_eq!(renderings(&mut mirror), 1);
+        mirror.poll(5).unwrap();
+        assert_eq!(renderings(&mut mirror), 2);
+    }
+
+    #[test]
+    fn unchanged_images_are_skipped() {
+        let mut mirror = FrameMirror::new(String::new(), 10);
+        mirror.mirror(&dot(4), 0).unwrap();
+        mirror.mirror(&dot(4), 20).unwrap();
+        mirror.mirror(&dot(4), 40).unwrap();
+        assert_eq!(renderings(&mut mirror), 1);
+        mirror.mirror(&dot(5), 60).unwrap();
+        assert_eq!(renderings(&mut mirror), 2);
+    }
+}
@@ -55,6 +55,10 @@
 //! - [`GreyscaleImage`], allowing all 9 levels (using one byte for each LED)
 //! - [`BitImage`], allowing only 'on' and 'off' (using five bytes)
 //!
+//! With the `embedded-graphics` feature enabled, the [`graphics`] submodule
+//! lets you draw into a `GreyscaleImage` using the `embedded-graphics`
+//! primitives, fonts and image formats.
+//!
 //! # Display
 //!
 //! A [`Display`] instance controls the LEDs and programs a timer. There
@@ -92,6 +96,44 @@
 //! The timer is set to 16-bit mode, using a 62.5kHz clock (16 µs ticks). It
 //! resets every 375 ticks.
 //!
+//! ## Sharing the timer
+//!
+//! The display only uses the timer's CC0 and CC1 compare registers. If you
+//! create the timer with [`MicrobitDisplayTimer::new_with_channels()`], you
+//! also get [`CompareChannel`] handles for CC2 and CC3, each of which
+//! generates an event once every 6ms cycle. The timer's type is then
+//! `MicrobitDisplayTimer<TIMER1, ChannelsLent>`, and it must be freed with
+//! [`free_with_channels()`], which takes the handles back.
+//!
+//! These share the timer's interrupt with the display:
+//!
+//! ```ignore
+//! #[interrupt(priority = 2, resources = [DISPLAY_TIMER, DISPLAY_PINS, DISPLAY, TICK])]
+//! fn TIMER1() {
+//!     microbit::display::handle_display_event(
+//!         &mut resources.DISPLAY,
+//!         resources.DISPLAY_TIMER,
+//!         resources.DISPLAY_PINS,
+//!     );
+//!     if resources.TICK.poll_event() {
+//!         // ...
+//!     }
+//! }
+//! ```
+//!
+//! ## Lower-overhead alternative
+//!
+//! The [`ppi`] submodule provides [`PpiDisplay`], which uses PPI and GPIOTE
+//! to switch LEDs off in hardware, so that it only needs one interrupt per
+//! row however many greyscale levels are being displayed.
+//!
+//! # Debugging
+//!
+//! The [`mirror`] submodule provides [`MirroredDisplay`], a `Display` which
+//! also writes a text rendering of each new image to a serial port (or any
+//! other `core::fmt::Write` sink). `examples/led_mirror.rs` shows it in
+//! use.
+//!
 //! # Usage
 //!
 //! Choose a timer to drive the display from (`TIMER0`, `TIMER1`, or
@@ -100,8 +142,9 @@
 //! When your program starts:
 //! * create a [`MicrobitDisplayTimer`] struct, passing the timer you chose to
 //! [`MicrobitDisplayTimer::new()`]
+//! * take the display pins from the split GPIO port using [`display_pins!`]
 //! * call [`initialise_display()`], passing it the `MicrobitDisplayTimer` and
-//! the gpio peripheral
+//! the [`DisplayPins`]
 //! * create a [`Display`] struct (a `Display<MicrobitFrame>`).
 //!
 //! In an interrupt handler for the timer, call [`handle_display_event()`].
@@ -128,10 +171,20 @@
 //! [`Frame`]: display::Frame
 //! [`Matrix`]: display::Matrix
 //! [`MicrobitFrame`]: display::MicrobitFrame
+//! [`DisplayPins`]: display::DisplayPins
+//! [`display_pins!`]: display_pins
 //! [`MicrobitDisplayTimer`]: display::MicrobitDisplayTimer
 //! [`MicrobitDisplayTimer::new()`]: display::MicrobitDisplayTimer::new
+//! [`MicrobitDisplayTimer::new_with_channels()`]: display::MicrobitDisplayTimer::new_with_channels
+//! [`free_with_channels()`]: display::MicrobitDisplayTimer::free_with_channels
+//! [`CompareChannel`]: display::CompareChannel
 //! [`Render`]: display::Render
 //! [`image`]: display::image
+//! [`graphics`]: display::graphics
+//! [`mirror`]: display::mirror
+//! [`MirroredDisplay`]: display::mirror::MirroredDisplay
+//! [`ppi`]: display::ppi
+//! [`PpiDisplay`]: display::ppi::PpiDisplay
 //! [`handle_display_event()`]: display::handle_display_event
 //! [`initialise_display()`]: display::initialise_display
 //! [`DisplayTimer`]: tiny_led_matrix::DisplayTimer
@@ -145,37 +198,41 @@ mod control;
 mod matrix;
 mod timer;
 
+#[cfg(feature = "embedded-graphics")]
+pub mod graphics;
 pub mod image;
+pub mod mirror;
+pub mod ppi;
 
+pub use control::DisplayPins;
 pub use matrix::MicrobitFrame;
-pub use timer::MicrobitDisplayTimer;
+pub use timer::{ChannelsLent, CompareChannel, MicrobitDisplayTimer, SpareChannels};
 
 use crate::hal::hi_res_timer::Nrf51Timer;
-use control::MicrobitGpio;
 
 /// Initialises the micro:bit hardware to use the display driver.
 ///
-/// Assumes the GPIO port is in the state it would have after system reset.
-///
 /// # Example
 ///
 /// ```ignore
-/// let mut p: nrf51::Peripherals = _;
+/// let p: nrf51::Peripherals = _;
+/// let gpio = p.GPIO.split();
+/// let mut pins = microbit::display_pins!(gpio);
 /// let mut timer = microbit::display::MicrobitDisplayTimer::new(p.TIMER1);
-/// microbit::display::initialise_display(&mut timer, &mut p.GPIO);
+/// microbit::display::initialise_display(&mut timer, &mut pins);
 /// ```
-pub fn initialise_display<T: Nrf51Timer>(
-    timer: &mut MicrobitDisplayTimer<T>,
-    gpio: &mut crate::hal::nrf51::GPIO,
+pub fn initialise_display<T: Nrf51Timer, C>(
+    timer: &mut MicrobitDisplayTimer<T, C>,
+    pins: &mut DisplayPins,
 ) {
-    tiny_led_matrix::initialise_control(&mut MicrobitGpio(gpio));
+    tiny_led_matrix::initialise_control(pins);
     tiny_led_matrix::initialise_timer(timer);
 }
 
 /// Updates the LEDs and timer state during a timer interrupt.
 ///
-/// The timer parameter must be the same `MicrobitDisplayTimer` you used for
-/// [`initialise_display()`].
+/// The timer and pins parameters must be the same `MicrobitDisplayTimer`
+/// and `DisplayPins` you used for [`initialise_display()`].
 ///
 /// Call this in an interrupt handler for the timer you're using.
 ///
@@ -188,19 +245,19 @@ pub fn initialise_display<T: Nrf51Timer>(
 /// In the style of `cortex-m-rtfm` v0.4:
 ///
 /// ```ignore
-/// #[interrupt(priority = 2, resources = [DISPLAY_TIMER, GPIO, DISPLAY])]
+/// #[interrupt(priority = 2, resources = [DISPLAY_TIMER, DISPLAY_PINS, DISPLAY])]
 /// fn TIMER1() {
 ///     microbit::display::handle_display_event(
 ///         &mut resources.DISPLAY,
 ///         resources.DISPLAY_TIMER,
-///         resources.GPIO,
+///         resources.DISPLAY_PINS,
 ///     );
 /// }
 /// ```
-pub fn handle_display_event<T: Nrf51Timer>(
+pub fn handle_display_event<T: Nrf51Timer, C>(
     display: &mut Display<MicrobitFrame>,
-    timer: &mut MicrobitDisplayTimer<T>,
-    gpio: &mut crate::hal::nrf51::GPIO,
+    timer: &mut MicrobitDisplayTimer<T, C>,
+    pins: &mut DisplayPins,
 ) {
-    display.handle_event(timer, &mut MicrobitGpio(gpio));
+    display.handle_event(timer, pins);
 }
@@ -0,0 +1,429 @@
+//! A lower-overhead display driver using PPI and GPIOTE.
+//!
+//! [`PpiDisplay`] drives the same LED matrix as the interrupt-driven
+//! [`Display`], but switches LEDs off in hardware: TIMER compare events are
+//! routed through PPI channels to GPIOTE tasks, each of which releases one
+//! column pin. The CPU is only interrupted once per matrix row (every 6ms) to
+//! select the next row and light its LEDs.
+//!
+//! # Resources
+//!
+//! A `PpiDisplay` takes ownership of:
+//! - the [`DisplayPins`]
+//! - one of `TIMER0`, `TIMER1` or `TIMER2` (all four compare registers are
+//!   used)
+//! - the `GPIOTE` peripheral (channels 0 to 3 are used)
+//! - the `PPI` peripheral (channels 0 to 3 are used).
+//!
+//! # Greyscale model
+//!
+//! Brightness levels use the same time slices as the interrupt-driven
+//! driver. Instead of lighting dimmer LEDs late in each row period, the
+//! `PpiDisplay` lights every LED in the row at the start of the period and
+//! switches the dimmer ones off early.
+//!
+//! The interrupt handler restarts the timer once the row's LEDs are lit, so
+//! the switch-off times are measured from when the LEDs came on, however
+//! late the handler ran. A row period is lengthened by the interrupt
+//! latency, but the brightness levels stay accurate.
+//!
+//! The hardware can switch off at most four columns, at up to three
+//! different times, in each row. Rows needing more than that (for example a
+//! row with five different brightness levels) fall back to switching
+//! columns off from the interrupt handler, exactly as the standard driver
+//! does, so such rows cost one extra interrupt for each brightness level
+//! below [`MAX_BRIGHTNESS`].
+//!
+//! # Interrupt counts
+//!
+//! The standard driver takes one interrupt per row plus one for each
+//! distinct brightness level below 9 present in that row. For a row using
+//! three intermediate levels that's four interrupts every 6ms (about 670 per
+//! second); the `PpiDisplay` needs one (about 167 per second).
+//!
+//! [`PpiDisplay::interrupt_count()`] reports how many times the interrupt
+//! handler has run; `examples/led_ppi.rs` prints it once a second, and can
+//! be switched to the standard driver to compare the two.
+//!
+//! # Usage
+//!
+//! ```ignore
+//! let p: nrf51::Peripherals = _;
+//! let gpio = p.GPIO.split();
+//! let pins = microbit::display_pins!(gpio);
+//! let mut display = PpiDisplay::new(p.TIMER1, p.GPIOTE, p.PPI, pins);
+//!
+//! // In the TIMER1 interrupt handler:
+//! display.handle_event();
+//!
+//! // Elsewhere:
+//! let mut frame = PpiFrame::default();
+//! frame.set(&image);
+//! display.set_frame(&frame);
+//! ```
+//!
+//! [`Display`]: tiny_led_matrix::Display
+//! [`DisplayPins`]: display::DisplayPins
+//! [`MAX_BRIGHTNESS`]: tiny_led_matrix::MAX_BRIGHTNESS
+//! [`PpiDisplay`]: display::ppi::PpiDisplay
+//! [`PpiDisplay::interrupt_count()`]: display::ppi::PpiDisplay::interrupt_count
+
+use core::ops::Deref;
+
+use tiny_led_matrix::{Matrix, Render, MAX_BRIGHTNESS};
+
+use crate::display::control::{
+    column_pins, DisplayPins, COL_BITS, FIRST_COL_PIN, FIRST_ROW_PIN, MATRIX_COLS, MATRIX_ROWS,
+    ROW_BITS,
+};
+use crate::display::matrix::MicrobitMatrix;
+use crate::hal::nrf51::{self, GPIOTE, PPI};
+
+/// Ticks in the primary cycle (6ms at 62.5kHz).
+const CYCLE_TICKS: u16 = 375;
+
+/// How long an LED of each brightness level is lit, in 16µs ticks.
+///
+/// These are the same timings as the interrupt-driven driver uses.
+const GREYSCALE_TICKS: [u16; 10] = [0, 2, 4, 8, 15, 28, 53, 100, 189, CYCLE_TICKS];
+
+/// Number of GPIOTE and PPI channels used.
+const HW_CHANNELS: usize = 4;
+const HW_CHANNEL_BITS: u32 = (1 << HW_CHANNELS) - 1;
+
+/// Number of compare registers available for switching columns off (CC1 to
+/// CC3).
+const HW_COMPARES: usize = 3;
+
+/// Intermediate brightness levels (1 to 8).
+const DIM_LEVELS: usize = MAX_BRIGHTNESS as usize - 1;
+
+const TIMER_PRESCALER_62500HZ: u32 = 8;
+const TIMER_SHORTS_COMPARE0_CLEAR: u32 = 1 << 0;
+const TIMER_INT_COMPARE0: u32 = 1 << 16;
+const TIMER_INT_COMPARE1: u32 = 1 << 17;
+
+/// How the LEDs in one matrix row are switched off.
+#[derive(Copy, Clone, Debug)]
+struct RowPlan {
+    /// Columns lit by the CPU at the start of the row.
+    cpu_cols: u32,
+    /// Number of columns switched off by the hardware.
+    hw_count: usize,
+    /// GPIO pin for each hardware-controlled column.
+    hw_pins: [u8; HW_CHANNELS],
+    /// Compare register (1 to 3) switching off each hardware-controlled
+    /// column.
+    hw_compare: [usize; HW_CHANNELS],
+    /// Values for CC1 to CC3.
+    compare_ticks: [u16; HW_COMPARES],
+    /// Number of switch-off steps handled by the interrupt handler.
+    sw_count: usize,
+    /// Time and columns of each software switch-off step, earliest first.
+    sw_steps: [(u16, u32); DIM_LEVELS],
+}
+
+impl RowPlan {
+    const fn blank() -> RowPlan {
+        RowPlan {
+            cpu_cols: 0,
+            hw_count: 0,
+            hw_pins: [0; HW_CHANNELS],
+            hw_compare: [0; HW_CHANNELS],
+            compare_ticks: [CYCLE_TICKS; HW_COMPARES],
+            sw_count: 0,
+            sw_steps: [(0, 0); DIM_LEVELS],
+        }
+    }
+
+    /// Builds the plan for a row from the set of columns lit at each
+    /// brightness level.
+    fn from_levels(levels: &[u32; 10]) -> RowPlan {
+        let mut plan = RowPlan::blank();
+        let dim_cols: u32 = dim_levels(levels).map(|b| levels[b].count_ones()).sum();
+
+        if dim_levels(levels).count() <= HW_COMPARES && dim_cols as usize <= HW_CHANNELS {
+            plan.cpu_cols = levels[MAX_BRIGHTNESS as usize];
+            for (cc, b) in dim_levels(levels).enumerate() {
+                plan.compare_ticks[cc] = GREYSCALE_TICKS[b];
+                for col in (0..MATRIX_COLS).filter(|col| levels[b] & (1 << col) != 0) {
+                    plan.hw_pins[plan.hw_count] = (FIRST_COL_PIN + col) as u8;
+                    plan.hw_compare[plan.hw_count] = cc + 1;
+                    plan.hw_count += 1;
+                }
+            }
+        } else {
+            plan.cpu_cols = levels[1..].iter().fold(0, |acc, cols| acc | cols);
+            for b in dim_levels(levels) {
+                plan.sw_steps[plan.sw_count] = (GREYSCALE_TICKS[b], levels[b]);
+                plan.sw_count += 1;
+            }
+        }
+        plan
+    }
+}
+
+/// Returns the intermediate brightness levels (1 to 8) which have any LEDs
+/// lit, dimmest first.
+fn dim_levels(levels: &[u32; 10]) -> impl Iterator<Item = usize> + '_ {
+    (1..MAX_BRIGHTNESS as usize).filter(move |&b| levels[b] != 0)
+}
+
+/// A 'compiled' representation of a 5×5 image for the [`PpiDisplay`].
+///
+/// Use [`.set()`](PpiFrame::set) to store an image (something implementing
+/// [`Render`]) in the frame.
+///
+/// This works out in advance which LEDs can be switched off by the hardware,
+/// so that the interrupt handler has as little to do as possible.
+///
+/// [`Render`]: tiny_led_matrix::Render
+#[derive(Copy, Clone, Debug)]
+pub struct PpiFrame([RowPlan; MATRIX_ROWS]);
+
+impl PpiFrame {
+    /// Returns a new frame, initially blank.
+    pub const fn const_default() -> PpiFrame {
+        PpiFrame([RowPlan::blank(); MATRIX_ROWS])
+    }
+
+    /// Stores a new image into the frame.
+    ///
+    /// Brightness levels greater than [`MAX_BRIGHTNESS`] are treated as
+    /// `MAX_BRIGHTNESS`.
+    ///
+    /// [`MAX_BRIGHTNESS`]: tiny_led_matrix::MAX_BRIGHTNESS
+    pub fn set<R: Render + ?Sized>(&mut self, image: &R) {
+        for (row, plan) in self.0.iter_mut().enumerate() {
+            let mut levels = [0; 10];
+            for col in 0..MATRIX_COLS {
+                if let Some((x, y)) = MicrobitMatrix::image_coordinates(col, row) {
+                    let brightness = image.brightness_at(x, y).min(MAX_BRIGHTNESS);
+                    levels[brightness as usize] |= 1 << col;
+                }
+            }
+            *plan = RowPlan::from_levels(&levels);
+        }
+    }
+
+    /// Returns the number of matrix rows which need more than the hardware
+    /// can do, and so fall back to switching columns off from the interrupt
+    /// handler (see the [module-level documentation](display::ppi)).
+    pub fn software_rows(&self) -> usize {
+        self.0.iter().filter(|plan| plan.sw_count > 0).count()
+    }
+}
+
+impl Default for PpiFrame {
+    /// Returns a new frame, initially blank.
+    fn default() -> PpiFrame {
+        PpiFrame::const_default()
+    }
+}
+
+/// The LED display driven by TIMER compare events through PPI and GPIOTE.
+///
+/// See the [module-level documentation](display::ppi) for details.
+pub struct PpiDisplay<T> {
+    pins: DisplayPins,
+    timer: T,
+    gpiote: GPIOTE,
+    ppi: PPI,
+    frame: PpiFrame,
+    pending: Option<PpiFrame>,
+    row: usize,
+    sw_step: usize,
+    interrupt_count: u32,
+}
+
+impl<T> PpiDisplay<T>
+where
+    T: Deref<Target = nrf51::timer0::RegisterBlock>,
+{
+    /// Takes ownership of the peripherals, initialises the display pins and
+    /// starts the timer.
+    ///
+    /// The display is initially blank.
+    pub fn new(timer: T, gpiote: GPIOTE, ppi: PPI, mut pins: DisplayPins) -> PpiDisplay<T> {
+        tiny_led_matrix::initialise_control(&mut pins);
+
+        timer.tasks_stop.write(|w| unsafe { w.bits(1) });
+        timer.mode.write(|w| unsafe { w.bits(0) });
+        timer.bitmode.write(|w| unsafe { w.bits(0) });
+        timer
+            .prescaler
+            .write(|w| unsafe { w.bits(TIMER_PRESCALER_62500HZ) });
+        timer.cc[0].write(|w| unsafe { w.bits(u32::from(CYCLE_TICKS)) });
+        timer
+            .shorts
+            .write(|w| unsafe { w.bits(TIMER_SHORTS_COMPARE0_CLEAR) });
+        timer
+            .intenset
+            .write(|w| unsafe { w.bits(TIMER_INT_COMPARE0) });
+        timer.tasks_clear.write(|w| unsafe { w.bits(1) });
+        timer.tasks_start.write(|w| unsafe { w.bits(1) });
+
+        PpiDisplay {
+            pins,
+            timer,
+            gpiote,
+            ppi,
+            frame: PpiFrame::const_default(),
+            pending: None,
+            row: 0,
+            sw_step: 0,
+            interrupt_count: 0,
+        }
+    }
+
+    /// Stops the timer and gives the peripherals back.
+    ///
+    /// The display pins are left switched off.
+    pub fn free(self) -> (T, GPIOTE, PPI, DisplayPins) {
+        self.timer.tasks_stop.write(|w| unsafe { w.bits(1) });
+        self.timer
+            .intenclr
+            .write(|w| unsafe { w.bits(TIMER_INT_COMPARE0 | TIMER_INT_COMPARE1) });
+        self.timer.shorts.write(|w| unsafe { w.bits(0) });
+        self.release_row();
+        (self.timer, self.gpiote, self.ppi, self.pins)
+    }
+
+    /// Accepts a new image to be displayed.
+    ///
+    /// The code that calls this method must not be interrupting, or
+    /// interruptable by, [`handle_event()`](PpiDisplay::handle_event).
+    ///
+    /// After calling this, it's safe to modify the frame again (its data is
+    /// copied into the `PpiDisplay`).
+    ///
+    /// The new image is held back until the next row starts, so a row which
+    /// is already lit finishes with the old image. If this is called again
+    /// before then, only the latest image is kept.
+    pub fn set_frame(&mut self, frame: &PpiFrame) {
+        self.pending = Some(*frame);
+    }
+
+    /// Returns the number of times [`handle_event()`] has been called.
+    ///
+    /// The count wraps around on overflow.
+    ///
+    /// [`handle_event()`]: PpiDisplay::handle_event
+    pub fn interrupt_count(&self) -> u32 {
+        self.interrupt_count
+    }
+
+    /// Updates the LEDs and timer state during a timer interrupt.
+    ///
+    /// Call this in an interrupt handler for the timer you passed to
+    /// [`new()`](PpiDisplay::new).
+    ///
+    /// Takes care of clearing the timer's event registers.
+    pub fn handle_event(&mut self) {
+        self.interrupt_count = self.interrupt_count.wrapping_add(1);
+        if self.timer.events_compare[0].read().bits() != 0 {
+            self.timer.events_compare[0].write(|w| unsafe { w.bits(0) });
+            self.start_next_row();
+        }
+        if self.timer.events_compare[1].read().bits() != 0 {
+            self.timer.events_compare[1].write(|w| unsafe { w.bits(0) });
+            self.software_step();
+        }
+    }
+
+    /// Switches off all columns, including those owned by GPIOTE.
+    fn release_row(&self) {
+        let gpio = self.pins.gpio();
+        self.ppi
+            .chenclr
+            .write(|w| unsafe { w.bits(HW_CHANNEL_BITS) });
+        for config in self.gpiote.config.iter().take(HW_CHANNELS) {
+            config.write(|w| unsafe { w.bits(0) });
+        }
+        gpio.outset.write(|w| unsafe { w.bits(COL_BITS) });
+    }
+
+    fn start_next_row(&mut self) {
+        self.release_row();
+        let gpio = self.pins.gpio();
+
+        if let Some(frame) = self.pending.take() {
+            self.frame = frame;
+        }
+        self.row = (self.row + 1) % MATRIX_ROWS;
+        let plan = &self.frame.0[self.row];
+        let row_bit = 1 << (FIRST_ROW_PIN + self.row);
+        gpio.outclr.write(|w| unsafe { w.bits(ROW_BITS ^ row_bit) });
+        gpio.outset.write(|w| unsafe { w.bits(row_bit) });
+
+        for (cc, &ticks) in plan.compare_ticks.iter().enumerate() {
+            self.timer.cc[cc + 1].write(|w| unsafe { w.bits(u32::from(ticks)) });
+        }
+        for ch in 0..plan.hw_count {
+            let event = &self.timer.events_compare[plan.hw_compare[ch]] as *const _ as u32;
+            let task = &self.gpiote.tasks_out[ch] as *const _ as u32;
+            self.ppi.ch[ch].eep.write(|w| unsafe { w.bits(event) });
+            self.ppi.ch[ch].tep.write(|w| unsafe { w.bits(task) });
+        }
+        // Taking over a pin in task mode drives it to OUTINIT (low), which
+        // lights the LED; the OUT task later drives it high again.
+        for ch in 0..plan.hw_count {
+            let pin = plan.hw_pins[ch];
+            self.gpiote.config[ch].write(|w| unsafe {
+                w.mode()
+                    .task()
+                    .psel()
+                    .bits(pin)
+                    .polarity()
+                    .lo_to_hi()
+                    .outinit()
+                    .low()
+            });
+        }
+        gpio.outclr
+            .write(|w| unsafe { w.bits(column_pins(plan.cpu_cols)) });
+
+        // Restart the timer now the row is lit, so the compare events are
+        // timed from here. Nothing may interrupt between clearing the timer
+        // and enabling the PPI channels, or an early compare event could be
+        // missed and leave LEDs lit for the whole row.
+        self.sw_step = 0;
+        let timer = &self.timer;
+        let ppi = &self.ppi;
+        cortex_m::interrupt::free(|_| {
+            timer.tasks_clear.write(|w| unsafe { w.bits(1) });
+            ppi.chenset
+                .write(|w| unsafe { w.bits((1 << plan.hw_count) - 1) });
+            if plan.sw_count > 0 {
+                timer.cc[1].write(|w| unsafe { w.bits(u32::from(plan.sw_steps[0].0)) });
+                timer.events_compare[1].write(|w| unsafe { w.bits(0) });
+                timer
+                    .intenset
+                    .write(|w| unsafe { w.bits(TIMER_INT_COMPARE1) });
+            } else {
+                timer
+                    .intenclr
+                    .write(|w| unsafe { w.bits(TIMER_INT_COMPARE1) });
+            }
+        });
+    }
+
+    fn software_step(&mut self) {
+        let gpio = self.pins.gpio();
+        let plan = &self.frame.0[self.row];
+        if self.sw_step >= plan.sw_count {
+            return;
+        }
+        let (_, cols) = plan.sw_steps[self.sw_step];
+        gpio.outset.write(|w| unsafe { w.bits(column_pins(cols)) });
+        self.sw_step += 1;
+        if self.sw_step < plan.sw_count {
+            let ticks = plan.sw_steps[self.sw_step].0;
+            self.timer.cc[1].write(|w| unsafe { w.bits(u32::from(ticks)) });
+        } else {
+            self.timer
+                .intenclr
+                .write(|w| unsafe { w.bits(TIMER_INT_COMPARE1) });
+        }
+    }
+}
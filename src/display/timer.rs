@@ -2,9 +2,13 @@
 //!
 //! [`DisplayTimer`]: tiny_led_matrix::DisplayTimer
 
+use core::marker::PhantomData;
+use core::ops::Deref;
+
 use tiny_led_matrix::DisplayTimer;
 
 use crate::hal::hi_res_timer::{As16BitTimer, HiResTimer, Nrf51Timer, TimerCc, TimerFrequency};
+use crate::hal::nrf51::timer0;
 
 /// A TIMER peripheral programmed to manage the display.
 ///
@@ -16,49 +20,190 @@ use crate::hal::hi_res_timer::{As16BitTimer, HiResTimer, Nrf51Timer, TimerCc, Ti
 /// Uses CC0 for the primary cycle and CC1 for the secondary alarm. Uses the
 /// CC0_CLEAR shortcut to implement the primary cycle.
 ///
+/// CC2 and CC3 are not used by the display. Create the timer with
+/// [`new_with_channels()`] to get them as [`CompareChannel`]s for
+/// application use. The timer's type then has the [`ChannelsLent`] marker,
+/// and it can only be freed by handing the channels back to
+/// [`free_with_channels()`].
+///
 /// [`DisplayTimer`]: tiny_led_matrix::DisplayTimer
-pub struct MicrobitDisplayTimer<T: Nrf51Timer>(HiResTimer<T, u16>);
+/// [`new_with_channels()`]: MicrobitDisplayTimer::new_with_channels
+/// [`free_with_channels()`]: MicrobitDisplayTimer::free_with_channels
+pub struct MicrobitDisplayTimer<T: Nrf51Timer, C = ()> {
+    timer: HiResTimer<T, u16>,
+    _channels: PhantomData<C>,
+}
+
+/// Marks a [`MicrobitDisplayTimer`] whose spare compare channels have been
+/// lent out by [`new_with_channels()`].
+///
+/// [`new_with_channels()`]: MicrobitDisplayTimer::new_with_channels
+pub struct ChannelsLent(());
 
 impl<T: As16BitTimer> MicrobitDisplayTimer<T> {
     /// Returns a new `MicrobitDisplayTimer` wrapping the passed TIMER.
     ///
     /// Takes ownership of the TIMER peripheral.
     pub fn new(timer: T) -> MicrobitDisplayTimer<T> {
-        MicrobitDisplayTimer(timer.as_16bit_timer())
+        MicrobitDisplayTimer {
+            timer: timer.as_16bit_timer(),
+            _channels: PhantomData,
+        }
     }
 
     /// Gives the underlying `nrf51::TIMER`*n* instance back.
     pub fn free(self) -> T {
-        self.0.free()
+        self.timer.free()
+    }
+}
+
+impl<T> MicrobitDisplayTimer<T, ChannelsLent>
+where
+    T: As16BitTimer + Deref<Target = timer0::RegisterBlock>,
+{
+    /// Returns a new `MicrobitDisplayTimer` wrapping the passed TIMER,
+    /// together with handles for the compare channels the display doesn't
+    /// use.
+    ///
+    /// Takes ownership of the TIMER peripheral.
+    pub fn new_with_channels(
+        timer: T,
+    ) -> (MicrobitDisplayTimer<T, ChannelsLent>, SpareChannels<T>) {
+        let regs: *const timer0::RegisterBlock = &*timer;
+        let display_timer = MicrobitDisplayTimer {
+            timer: timer.as_16bit_timer(),
+            _channels: PhantomData,
+        };
+        let channels = SpareChannels {
+            cc2: CompareChannel::new(regs, 2),
+            cc3: CompareChannel::new(regs, 3),
+        };
+        (display_timer, channels)
+    }
+
+    /// Gives the underlying `nrf51::TIMER`*n* instance back, taking back the
+    /// spare channels handed out by [`new_with_channels()`].
+    ///
+    /// [`new_with_channels()`]: MicrobitDisplayTimer::new_with_channels
+    pub fn free_with_channels(self, channels: SpareChannels<T>) -> T {
+        let SpareChannels { mut cc2, mut cc3 } = channels;
+        cc2.disable_interrupt();
+        cc3.disable_interrupt();
+        self.timer.free()
+    }
+}
+
+/// The compare channels of a [`MicrobitDisplayTimer`] which the display
+/// doesn't use.
+///
+/// Returned by [`MicrobitDisplayTimer::new_with_channels()`].
+pub struct SpareChannels<T> {
+    /// The timer's CC2 channel.
+    pub cc2: CompareChannel<T>,
+    /// The timer's CC3 channel.
+    pub cc3: CompareChannel<T>,
+}
+
+/// One compare channel of a TIMER being used by the display.
+///
+/// The display timer restarts every 6ms (375 ticks of 16µs), so a compare
+/// channel set to a value less than 375 generates an event once in every 6ms
+/// cycle. Application code can use this for periodic callbacks while the
+/// display keeps running, for example by counting events to make a longer
+/// period.
+///
+/// The channel's interrupt is the same as the display's, so the interrupt
+/// handler for the TIMER should call [`handle_display_event()`] and then
+/// [`poll_event()`] for each channel it has enabled. The display code only
+/// clears its own events.
+///
+/// [`handle_display_event()`]: crate::display::handle_display_event
+/// [`poll_event()`]: CompareChannel::poll_event
+pub struct CompareChannel<T> {
+    regs: *const timer0::RegisterBlock,
+    index: usize,
+    _timer: PhantomData<T>,
+}
+
+// The register block is only touched through the write-to-set and
+// write-to-clear registers and this channel's own CC and EVENTS registers.
+unsafe impl<T> Send for CompareChannel<T> {}
+
+impl<T> CompareChannel<T> {
+    fn new(regs: *const timer0::RegisterBlock, index: usize) -> CompareChannel<T> {
+        CompareChannel {
+            regs,
+            index,
+            _timer: PhantomData,
+        }
+    }
+
+    fn regs(&self) -> &timer0::RegisterBlock {
+        unsafe { &*self.regs }
+    }
+
+    /// Returns the bit for this channel in the INTENSET and INTENCLR
+    /// registers.
+    fn interrupt_bit(&self) -> u32 {
+        1 << (16 + self.index)
+    }
+
+    /// Sets the tick within the 6ms display cycle at which the event fires.
+    ///
+    /// Values of 375 or more never fire.
+    pub fn set_compare(&mut self, ticks: u16) {
+        self.regs().cc[self.index].write(|w| unsafe { w.bits(u32::from(ticks)) });
+    }
+
+    /// Enables the TIMER interrupt for this channel's compare event.
+    pub fn enable_interrupt(&mut self) {
+        let bit = self.interrupt_bit();
+        self.regs().intenset.write(|w| unsafe { w.bits(bit) });
+    }
+
+    /// Disables the TIMER interrupt for this channel's compare event.
+    pub fn disable_interrupt(&mut self) {
+        let bit = self.interrupt_bit();
+        self.regs().intenclr.write(|w| unsafe { w.bits(bit) });
+    }
+
+    /// Checks whether the compare event has fired, and clears it if so.
+    pub fn poll_event(&mut self) -> bool {
+        let event = &self.regs().events_compare[self.index];
+        if event.read().bits() == 0 {
+            return false;
+        }
+        event.write(|w| unsafe { w.bits(0) });
+        true
     }
 }
 
-impl<T: Nrf51Timer> DisplayTimer for MicrobitDisplayTimer<T> {
+impl<T: Nrf51Timer, C> DisplayTimer for MicrobitDisplayTimer<T, C> {
     fn initialise_cycle(&mut self, ticks: u16) {
-        self.0.set_frequency(TimerFrequency::Freq62500Hz);
-        self.0.set_compare_register(TimerCc::CC0, ticks);
-        self.0.enable_auto_clear(TimerCc::CC0);
-        self.0.enable_compare_interrupt(TimerCc::CC0);
-        self.0.start();
+        self.timer.set_frequency(TimerFrequency::Freq62500Hz);
+        self.timer.set_compare_register(TimerCc::CC0, ticks);
+        self.timer.enable_auto_clear(TimerCc::CC0);
+        self.timer.enable_compare_interrupt(TimerCc::CC0);
+        self.timer.start();
     }
 
     fn enable_secondary(&mut self) {
-        self.0.enable_compare_interrupt(TimerCc::CC1);
+        self.timer.enable_compare_interrupt(TimerCc::CC1);
     }
 
     fn disable_secondary(&mut self) {
-        self.0.disable_compare_interrupt(TimerCc::CC1);
+        self.timer.disable_compare_interrupt(TimerCc::CC1);
     }
 
     fn program_secondary(&mut self, ticks: u16) {
-        self.0.set_compare_register(TimerCc::CC1, ticks);
+        self.timer.set_compare_register(TimerCc::CC1, ticks);
     }
 
     fn check_primary(&mut self) -> bool {
-        self.0.poll_compare_event(TimerCc::CC0)
+        self.timer.poll_compare_event(TimerCc::CC0)
     }
 
     fn check_secondary(&mut self) -> bool {
-        self.0.poll_compare_event(TimerCc::CC1)
+        self.timer.poll_compare_event(TimerCc::CC1)
     }
 }
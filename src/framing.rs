@@ -0,0 +1,193 @@
+//! Sending and receiving framed binary messages over a serial port.
+//!
+//! This uses the protocol defined by the [`microbit_protocol`] crate (which
+//! is re-exported here): each [`Message`] is sent with a CRC, COBS-encoded
+//! and terminated by a zero byte. The host side can use the same crate, or
+//! the `microbit-host` crate which wraps it for `std` serial ports.
+//!
+//! [`send()`] and [`MessageReceiver`] work with anything implementing the
+//! embedded-hal serial traits, such as the `Tx` and `Rx` returned by
+//! [`serial_port()`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! let (mut tx, mut rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+//! let mut receiver = MessageReceiver::new();
+//!
+//! loop {
+//!     if let Ok(Ok(Message::Ping { seq })) = block!(receiver.receive(&mut rx)) {
+//!         framing::send(&mut tx, &Message::Pong { seq })?;
+//!     }
+//! }
+//! ```
+//!
+//! [`microbit_protocol`]: microbit_protocol
+//! [`Message`]: microbit_protocol::Message
+//! [`send()`]: framing::send
+//! [`MessageReceiver`]: framing::MessageReceiver
+//! [`serial_port()`]: serial_port
+
+use embedded_hal::serial;
+
+pub use microbit_protocol::{frame, Error, FrameDecoder, Message};
+
+/// Sends a message, waiting until each byte has been accepted.
+pub fn send<W: serial::Write<u8>>(tx: &mut W, message: &Message) -> Result<(), W::Error> {
+    let mut buffer = [0; frame::MAX_FRAME_LEN];
+    for &byte in frame::encode(message, &mut buffer) {
+        nb::block!(tx.write(byte))?;
+    }
+    Ok(())
+}
+
+/// Receives messages from a serial port.
+#[derive(Default)]
+pub struct MessageReceiver {
+    decoder: FrameDecoder,
+}
+
+impl MessageReceiver {
+    /// Returns a new `MessageReceiver`.
+    pub const fn new() -> MessageReceiver {
+        MessageReceiver {
+            decoder: FrameDecoder::new(),
+        }
+    }
+
+    /// Reads whatever bytes are available, until a frame is complete.
+    ///
+    /// Returns `WouldBlock` if no frame has been completed yet. Otherwise
+    /// returns the received message, or the reason the frame was rejected.
+    pub fn receive<R: serial::Read<u8>>(
+        &mut self,
+        rx: &mut R,
+    ) -> nb::Result<Result<Message, Error>, R::Error> {
+        loop {
+            let byte = rx.read()?;
+            if let Some(result) = self.decoder.push(byte) {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Handles one received byte, for use where bytes arrive some other way
+    /// (such as from a [`BufferedUart`]).
+    ///
+    /// [`BufferedUart`]: buffered_uart::BufferedUart
+    pub fn push(&mut self, byte: u8) -> Option<Result<Message, Error>> {
+        self.decoder.push(byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use std::collections::VecDeque;
+
+    /// A serial port which receives the bytes it's given, and records the
+    /// bytes sent.
+    #[derive(Default)]
+    struct FakeSerial {
+        received: VecDeque<u8>,
+        sent: Vec<u8>,
+    }
+
+    impl serial::Read<u8> for FakeSerial {
+        type Error = Infallible;
+
+        fn read(&mut self) -> nb::Result<u8, Infallible> {
+            self.received.pop_front().ok_or(nb::Error::WouldBlock)
+        }
+    }
+
+    impl serial::Write<u8> for FakeSerial {
+        type Error = Infallible;
+
+        fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+            self.sent.push(byte);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> nb::Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    fn encoded(message: &Message) -> Vec<u8> {
+        let mut buffer = [0; frame::MAX_FRAME_LEN];
+        frame::encode(message, &mut buffer).to_vec()
+    }
+
+    fn push_all(receiver: &mut MessageReceiver, bytes: &[u8]) -> Vec<Result<Message, Error>> {
+        bytes
+            .iter()
+            .filter_map(|&byte| receiver.push(byte))
+            .collect()
+    }
+
+    const IMAGE: Message = Message::SetImage {
+        brightness: [[9, 0, 9, 0, 9]; 5],
+    };
+
+    #[test]
+    fn push_collects_frames_split_into_bytes() {
+        let mut receiver = MessageReceiver::new();
+        let mut bytes = encoded(&Message::Ping { seq: 7 });
+        bytes.extend(encoded(&IMAGE));
+        let (first, second) = bytes.split_at(5);
+        assert!(push_all(&mut receiver, first).is_empty());
+        assert_eq!(
+            push_all(&mut receiver, second),
+            [Ok(Message::Ping { seq: 7 }), Ok(IMAGE)]
+        );
+    }
+
+    #[test]
+    fn push_rejects_corrupted_frames_and_recovers() {
+        let mut receiver = MessageReceiver::new();
+        let mut bytes = encoded(&IMAGE);
+        bytes[3] ^= 0x01;
+        let results = push_all(&mut receiver, &bytes);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+
+        // A frame which lost its start (say to a glitch on the line).
+        let bytes = encoded(&Message::Ping { seq: 1 });
+        let results = push_all(&mut receiver, &bytes[2..]);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+        // Repeated zeros (empty frames) are ignored.
+        assert!(receiver.push(0).is_none());
+
+        assert_eq!(
+            push_all(&mut receiver, &encoded(&Message::ReadButtons)),
+            [Ok(Message::ReadButtons)]
+        );
+    }
+
+    #[test]
+    fn receive_waits_for_a_whole_frame() {
+        let mut receiver = MessageReceiver::new();
+        let mut serial = FakeSerial::default();
+        let bytes = encoded(&Message::ReadRandom);
+        let (first, second) = bytes.split_at(2);
+
+        serial.received.extend(first);
+        assert_eq!(receiver.receive(&mut serial), Err(nb::Error::WouldBlock));
+        serial.received.extend(second);
+        serial.received.extend(&bytes);
+        assert_eq!(receiver.receive(&mut serial), Ok(Ok(Message::ReadRandom)));
+        assert_eq!(receiver.receive(&mut serial), Ok(Ok(Message::ReadRandom)));
+        assert_eq!(receiver.receive(&mut serial), Err(nb::Error::WouldBlock));
+    }
+
+    #[test]
+    fn send_writes_one_frame() {
+        let mut serial = FakeSerial::default();
+        send(&mut serial, &IMAGE).unwrap();
+        assert_eq!(serial.sent, encoded(&IMAGE));
+        assert_eq!(serial.sent.iter().filter(|&&byte| byte == 0).count(), 1);
+    }
+}
@@ -0,0 +1,373 @@
+//! Pin-change events using the GPIOTE peripheral.
+//!
+//! The nRF51 has four GPIOTE channels, each of which can watch one pin and
+//! raise an event (and an interrupt) when its level changes. [`Gpiote`] owns
+//! the peripheral and hands the four channels out as [`Channel`] handles, so
+//! two drivers can't end up configuring the same channel.
+//!
+//! A channel is bound to a typed input pin with [`Gpiote::listen()`], along
+//! with the [`Polarity`] to watch for and a handler function. Calling
+//! [`Gpiote::handle_interrupt()`] from the `GPIOTE` interrupt clears the
+//! events and calls the handlers of the channels which fired.
+//!
+//! A [`Channel`] can also be given to another driver which uses GPIOTE
+//! itself (such as [`SoftUart`]). `handle_interrupt()` leaves channels which
+//! weren't bound with `listen()` alone, so call that driver's handler from
+//! the `GPIOTE` interrupt as well.
+//!
+//! # Watching more than four pins
+//!
+//! Pins can also be watched using the PORT event with [`Gpiote::watch()`].
+//! This works for any number of pins, but it uses a single shared event, so
+//! it reports changes rather than edges, and a change which is undone before
+//! the interrupt is handled is missed. `handle_interrupt()` works out which
+//! watched pins have changed since it last ran and calls the port handler set
+//! with [`Gpiote::set_port_handler()`] for each of them.
+//!
+//! # Example
+//!
+//! ```ignore
+//! fn button_a_pressed() {
+//!     // ...
+//! }
+//!
+//! let (mut gpiote, channels) = Gpiote::new(p.GPIOTE);
+//! let button_a = gpiote.listen(
+//!     channels.ch0,
+//!     gpio.pin17.into_floating_input(),
+//!     Polarity::HiToLo,
+//!     button_a_pressed,
+//! );
+//!
+//! #[interrupt]
+//! fn GPIOTE() {
+//!     // with `gpiote` moved into a static
+//!     gpiote.handle_interrupt();
+//! }
+//! ```
+//!
+//! [`Gpiote`]: gpiote::Gpiote
+//! [`Channel`]: gpiote::Channel
+//! [`Polarity`]: gpiote::Polarity
+//! [`Gpiote::listen()`]: gpiote::Gpiote::listen
+//! [`Gpiote::watch()`]: gpiote::Gpiote::watch
+//! [`Gpiote::handle_interrupt()`]: gpiote::Gpiote::handle_interrupt
+//! [`Gpiote::set_port_handler()`]: gpiote::Gpiote::set_port_handler
+//! [`SoftUart`]: soft_uart::SoftUart
+
+use crate::hal::gpio::gpio::*;
+use crate::hal::gpio::Input;
+use crate::hal::nrf51::{GPIO, GPIOTE};
+
+/// Number of GPIOTE channels.
+const CHANNELS: usize = 4;
+
+/// A GPIO pin whose number is known from its type.
+pub trait PinNumber {
+    /// The pin's number (0 to 31).
+    const NUMBER: u8;
+}
+
+macro_rules! pin_numbers {
+    ($($PIN:ident => $number:expr,)+) => {
+        $(
+            impl<MODE> PinNumber for $PIN<MODE> {
+                const NUMBER: u8 = $number;
+            }
+
+            impl<MODE> EventPin for $PIN<Input<MODE>> {}
+        )+
+    }
+}
+
+pin_numbers! {
+    PIN0 => 0, PIN1 => 1, PIN2 => 2, PIN3 => 3,
+    PIN4 => 4, PIN5 => 5, PIN6 => 6, PIN7 => 7,
+    PIN8 => 8, PIN9 => 9, PIN10 => 10, PIN11 => 11,
+    PIN12 => 12, PIN13 => 13, PIN14 => 14, PIN15 => 15,
+    PIN16 => 16, PIN17 => 17, PIN18 => 18, PIN19 => 19,
+    PIN20 => 20, PIN21 => 21, PIN22 => 22, PIN23 => 23,
+    PIN24 => 24, PIN25 => 25, PIN26 => 26, PIN27 => 27,
+    PIN28 => 28, PIN29 => 29, PIN30 => 30, PIN31 => 31,
+}
+
+/// An input pin which can be watched for changes.
+///
+/// This is implemented for each `PINn` in one of the `Input` modes.
+pub trait EventPin: PinNumber {}
+
+/// Which pin changes generate an event.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Polarity {
+    /// Rising edges.
+    LoToHi,
+    /// Falling edges (for example a micro:bit button being pressed).
+    HiToLo,
+    /// Both edges.
+    Toggle,
+}
+
+/// One of the four GPIOTE channels, not currently in use.
+#[derive(Debug)]
+pub struct Channel {
+    index: usize,
+}
+
+impl Channel {
+    /// Returns the channel's number (0 to 3).
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// The four GPIOTE channels, as returned by [`Gpiote::new()`].
+pub struct Channels {
+    pub ch0: Channel,
+    pub ch1: Channel,
+    pub ch2: Channel,
+    pub ch3: Channel,
+}
+
+/// A GPIOTE channel bound to an input pin.
+///
+/// Give it to [`Gpiote::unlisten()`] to get the channel and pin back.
+pub struct InputChannel<P> {
+    channel: Channel,
+    pin: P,
+}
+
+impl<P> InputChannel<P> {
+    /// Returns the number of the channel being used.
+    pub fn index(&self) -> usize {
+        self.channel.index
+    }
+
+    /// Gives access to the pin, for example to read its level.
+    pub fn pin(&self) -> &P {
+        &self.pin
+    }
+}
+
+/// An input pin watched using the PORT event.
+///
+/// Give it to [`Gpiote::unwatch()`] to stop watching the pin and get it
+/// back.
+pub struct PortPin<P> {
+    pin: P,
+}
+
+impl<P> PortPin<P> {
+    /// Gives access to the pin, for example to read its level.
+    pub fn pin(&self) -> &P {
+        &self.pin
+    }
+}
+
+/// The GPIOTE peripheral, dispatching pin-change events to handlers.
+///
+/// See the [module-level documentation](gpiote) for details.
+pub struct Gpiote {
+    gpiote: GPIOTE,
+    handlers: [Option<fn()>; CHANNELS],
+    port_handler: Option<fn(u8, bool)>,
+    /// Pins being watched using the PORT event.
+    port_pins: u32,
+    /// Levels of the watched pins when the PORT event was last handled.
+    port_levels: u32,
+}
+
+impl Gpiote {
+    /// Takes ownership of the GPIOTE peripheral, and returns it along with
+    /// handles for its four channels.
+    ///
+    /// Any existing channel configuration is cleared.
+    pub fn new(gpiote: GPIOTE) -> (Gpiote, Channels) {
+        gpiote.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        for config in gpiote.config.iter() {
+            config.write(|w| unsafe { w.bits(0) });
+        }
+        for event in gpiote.events_in.iter() {
+            event.write(|w| unsafe { w.bits(0) });
+        }
+        gpiote.events_port.write(|w| unsafe { w.bits(0) });
+        let channels = Channels {
+            ch0: Channel { index: 0 },
+            ch1: Channel { index: 1 },
+            ch2: Channel { index: 2 },
+            ch3: Channel { index: 3 },
+        };
+        let gpiote = Gpiote {
+            gpiote,
+            handlers: [None; CHANNELS],
+            port_handler: None,
+            port_pins: 0,
+            port_levels: 0,
+        };
+        (gpiote, channels)
+    }
+
+    /// Gives the GPIOTE peripheral back, with all channels and the PORT
+    /// event disabled.
+    ///
+    /// Any pins still bound to channels or being watched stay owned by their
+    /// `InputChannel` or `PortPin`; their sense configuration is cleared.
+    pub fn free(self) -> GPIOTE {
+        let gpio = gpio();
+        for pin in 0..32 {
+            if self.port_pins & (1 << pin) != 0 {
+                gpio.pin_cnf[pin].modify(|_, w| w.sense().disabled());
+            }
+        }
+        self.gpiote
+            .intenclr
+            .write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        for config in self.gpiote.config.iter() {
+            config.write(|w| unsafe { w.bits(0) });
+        }
+        self.gpiote
+    }
+
+    /// Binds a channel to an input pin, calling `handler` from
+    /// [`handle_interrupt()`] each time the pin changes as described by
+    /// `polarity`.
+    ///
+    /// [`handle_interrupt()`]: Gpiote::handle_interrupt
+    pub fn listen<P: EventPin>(
+        &mut self,
+        channel: Channel,
+        pin: P,
+        polarity: Polarity,
+        handler: fn(),
+    ) -> InputChannel<P> {
+        let index = channel.index;
+        self.handlers[index] = Some(handler);
+        self.gpiote.config[index].write(|w| {
+            let w = unsafe { w.mode().event().psel().bits(P::NUMBER) };
+            match polarity {
+                Polarity::LoToHi => w.polarity().lo_to_hi(),
+                Polarity::HiToLo => w.polarity().hi_to_lo(),
+                Polarity::Toggle => w.polarity().toggle(),
+            }
+        });
+        self.gpiote.events_in[index].write(|w| unsafe { w.bits(0) });
+        self.gpiote
+            .intenset
+            .write(|w| unsafe { w.bits(1 << index) });
+        InputChannel { channel, pin }
+    }
+
+    /// Stops listening to a pin, and gives back the channel and the pin.
+    pub fn unlisten<P>(&mut self, input: InputChannel<P>) -> (Channel, P) {
+        let index = input.channel.index;
+        self.gpiote
+            .intenclr
+            .write(|w| unsafe { w.bits(1 << index) });
+        self.gpiote.config[index].write(|w| unsafe { w.bits(0) });
+        self.gpiote.events_in[index].write(|w| unsafe { w.bits(0) });
+        self.handlers[index] = None;
+        (input.channel, input.pin)
+    }
+
+    /// Sets the function called from [`handle_interrupt()`] when a pin
+    /// being watched with [`watch()`] changes.
+    ///
+    /// The handler is given the pin number and its new level (`true` for
+    /// high).
+    ///
+    /// [`handle_interrupt()`]: Gpiote::handle_interrupt
+    /// [`watch()`]: Gpiote::watch
+    pub fn set_port_handler(&mut self, handler: fn(u8, bool)) {
+        self.port_handler = Some(handler);
+    }
+
+    /// Starts watching an input pin using the PORT event.
+    pub fn watch<P: EventPin>(&mut self, pin: P) -> PortPin<P> {
+        let bit = 1 << P::NUMBER;
+        let high = gpio().in_.read().bits() & bit != 0;
+        self.port_pins |= bit;
+        if high {
+            self.port_levels |= bit;
+        } else {
+            self.port_levels &= !bit;
+        }
+        sense_change(P::NUMBER, high);
+        self.gpiote.intenset.write(|w| w.port().set_bit());
+        PortPin { pin }
+    }
+
+    /// Stops watching a pin, and gives it back.
+    pub fn unwatch<P: EventPin>(&mut self, watched: PortPin<P>) -> P {
+        self.port_pins &= !(1 << P::NUMBER);
+        gpio().pin_cnf[P::NUMBER as usize].modify(|_, w| w.sense().disabled());
+        if self.port_pins == 0 {
+            self.gpiote.intenclr.write(|w| w.port().set_bit());
+        }
+        watched.pin
+    }
+
+    /// Handles a GPIOTE interrupt.
+    ///
+    /// Call this from the `GPIOTE` interrupt handler. It clears the events
+    /// which have fired and calls the corresponding handlers.
+    pub fn handle_interrupt(&mut self) {
+        for index in 0..CHANNELS {
+            // Channels which aren't bound here may have been given to
+            // another driver, which handles its own events.
+            if let Some(handler) = self.handlers[index] {
+                if self.gpiote.events_in[index].read().bits() != 0 {
+                    self.gpiote.events_in[index].write(|w| unsafe { w.bits(0) });
+                    handler();
+                }
+            }
+        }
+
+        if self.gpiote.events_port.read().bits() != 0 {
+            self.gpiote.events_port.write(|w| unsafe { w.bits(0) });
+            self.handle_port_event();
+        }
+    }
+
+    /// Reports changes to the watched pins, and rearms their sense
+    /// configuration to detect the next change.
+    ///
+    /// DETECT only rises (raising the PORT event) when no pin is already
+    /// matching its sense setting, so a pin which changes again while the
+    /// others are being rearmed would block every later PORT event. Keep
+    /// going until every watched pin is at the level it was rearmed for.
+    fn handle_port_event(&mut self) {
+        loop {
+            let levels = gpio().in_.read().bits() & self.port_pins;
+            let changed = levels ^ self.port_levels;
+            if changed == 0 {
+                break;
+            }
+            self.port_levels = levels;
+            for pin in 0..32 {
+                let bit = 1 << pin;
+                if changed & bit != 0 {
+                    let high = levels & bit != 0;
+                    sense_change(pin, high);
+                    if let Some(handler) = self.port_handler {
+                        handler(pin, high);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Configures a pin to raise the PORT event when it leaves its current
+/// level.
+fn sense_change(pin: u8, high: bool) {
+    gpio().pin_cnf[pin as usize].modify(|_, w| {
+        if high {
+            w.sense().low()
+        } else {
+            w.sense().high()
+        }
+    });
+}
+
+fn gpio() -> &'static crate::hal::nrf51::gpio::RegisterBlock {
+    unsafe { &*GPIO::ptr() }
+}
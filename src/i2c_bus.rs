@@ -0,0 +1,429 @@
+//! Recovering a stuck I2C bus, and finding the devices on a bus.
+//!
+//! If the micro:bit is reset in the middle of an I2C transfer, a sensor can
+//! be left holding SDA low, waiting for clock pulses which never come. From
+//! then on every transfer fails (the HAL's `I2c` waits forever) until the
+//! board is power-cycled.
+//!
+//! [`I2cBus`] wraps the HAL's `I2c`, and does the transfers itself. Before
+//! each transfer it checks that SCL and SDA are both high, and if not it
+//! recovers the bus: it takes the pins back from the `TWI1` peripheral,
+//! clocks SCL up to nine times until the device lets go of SDA, and sends a
+//! STOP condition. If a line is still low after that, the transfer fails
+//! with [`Error::StuckBus`] instead of hanging.
+//!
+//! Each transfer is also timed, with any embedded-hal `CountDown` (such as
+//! the HAL's `CountDownTimer` or `CountDownRtc`). If a device holds the bus
+//! in the middle of a transfer and the timeout runs out (25ms unless
+//! changed with [`set_timeout()`]), the transfer is abandoned, the bus
+//! recovered, and the transfer fails with `Error::StuckBus`.
+//!
+//! `I2cBus` also tells a device which isn't there ([`Error::AddressNack`])
+//! apart from one which rejects the data ([`Error::DataNack`]), which the
+//! HAL's errors don't, and it supports reads without a write first
+//! (embedded-hal's `Read`), which the HAL's `I2c` doesn't.
+//!
+//! [`scan()`] lists the addresses which respond on a bus, for finding out
+//! what's connected.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let scl = gpio.pin0.into_open_drain_input().into();
+//! let sda = gpio.pin30.into_open_drain_input().into();
+//! let timer = CountDownTimer::new(p.TIMER2, TimerFrequency::Freq62500Hz);
+//! let mut bus = I2cBus::new(i2c::I2c::i2c1(p.TWI1, sda, scl), timer);
+//! for address in i2c_bus::scan(&mut bus).iter() {
+//!     write!(tx, "Found a device at {:#04x}\n\r", address)?;
+//! }
+//! let mut magnetometer = Magnetometer::new(bus)?;
+//! ```
+//!
+//! [`I2cBus`]: i2c_bus::I2cBus
+//! [`set_timeout()`]: i2c_bus::I2cBus::set_timeout
+//! [`Error::StuckBus`]: i2c_bus::Error::StuckBus
+//! [`Error::AddressNack`]: i2c_bus::Error::AddressNack
+//! [`Error::DataNack`]: i2c_bus::Error::DataNack
+//! [`scan()`]: i2c_bus::scan
+
+use core::time::Duration;
+
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+use embedded_hal::timer::CountDown;
+
+use crate::hal::i2c::I2c;
+use crate::hal::nrf51::{gpio, twi0, GPIO, TWI1};
+
+/// ERRORSRC bits.
+const ERROR_OVERRUN: u32 = 1 << 0;
+const ERROR_ANACK: u32 = 1 << 1;
+
+/// CPU cycles in half a clock period while recovering the bus (5µs, for
+/// 100kHz).
+const HALF_PERIOD_CYCLES: u32 = 80;
+
+/// Half periods to wait for a device to stop stretching the clock (10ms).
+const CLOCK_STRETCH_TIMEOUT: u32 = 2000;
+
+/// How long a transfer may take before the bus is taken to be stuck.
+const TRANSFER_TIMEOUT: Duration = Duration::from_millis(25);
+
+/// Errors from an [`I2cBus`].
+///
+/// [`I2cBus`]: i2c_bus::I2cBus
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// SCL or SDA is held low, and recovering the bus didn't release it; or
+    /// a transfer took longer than the timeout (and the bus was recovered).
+    StuckBus,
+    /// No device acknowledged the address.
+    AddressNack,
+    /// The device didn't acknowledge a byte sent to it.
+    DataNack,
+    /// A received byte was overwritten before it was read.
+    Overrun,
+}
+
+/// Addresses which responded to a [`scan()`].
+///
+/// [`scan()`]: i2c_bus::scan
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Addresses {
+    bits: u128,
+}
+
+impl Addresses {
+    /// Returns whether a device responded at `address`.
+    pub fn contains(&self, address: u8) -> bool {
+        address < 128 && self.bits & (1 << address) != 0
+    }
+
+    /// Returns the number of devices which responded.
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    /// Returns whether no devices responded.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Returns the addresses which responded, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u8> {
+        let bits = self.bits;
+        (0..128).filter(move |address| bits & (1 << address) != 0)
+    }
+
+    fn insert(&mut self, address: u8) {
+        self.bits |= 1 << address;
+    }
+}
+
+/// Finds the devices on a bus, by writing nothing to each 7-bit address
+/// from `0x08` to `0x77` and noting which acknowledge it.
+///
+/// The reserved addresses at either end aren't tried. On the internal bus
+/// of a v1.3 board this finds `0x0e` (the magnetometer) and `0x1d` (the
+/// accelerometer); on a v1.5 board, `0x19` and `0x1e`.
+pub fn scan<I2C: Write>(i2c: &mut I2C) -> Addresses {
+    let mut found = Addresses::default();
+    for address in 0x08..=0x77 {
+        if i2c.write(address, &[]).is_ok() {
+            found.insert(address);
+        }
+    }
+    found
+}
+
+/// Why a transfer stopped early.
+enum Failure {
+    /// The TWI peripheral reported an error (see ERRORSRC).
+    Twi,
+    /// The timeout ran out.
+    Timeout,
+}
+
+/// The HAL's `I2c`, with timeouts, bus recovery and more precise errors.
+///
+/// `T` is the timer used to time transfers.
+pub struct I2cBus<T> {
+    i2c: I2c<TWI1>,
+    timer: T,
+    timeout: Duration,
+}
+
+impl<T> I2cBus<T>
+where
+    T: CountDown,
+    T::Time: From<Duration>,
+{
+    /// Wraps the HAL's `I2c`, timing transfers with `timer`.
+    ///
+    /// The bus isn't checked until the first transfer.
+    pub fn new(i2c: I2c<TWI1>, timer: T) -> I2cBus<T> {
+        I2cBus {
+            i2c,
+            timer,
+            timeout: TRANSFER_TIMEOUT,
+        }
+    }
+
+    /// Gives back the HAL's `I2c` and the timer.
+    pub fn free(self) -> (I2c<TWI1>, T) {
+        (self.i2c, self.timer)
+    }
+
+    /// Sets how long a transfer may take before the bus is taken to be
+    /// stuck.
+    ///
+    /// The timer must be able to count this long (for a 16-bit `TIMER`,
+    /// that depends on its frequency).
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns whether SCL and SDA are both high, as they should be between
+    /// transfers.
+    pub fn is_idle(&self) -> bool {
+        let (scl, sda) = pin_masks();
+        gpio().in_.read().bits() & (scl | sda) == scl | sda
+    }
+
+    /// Releases the bus from a device holding SDA low.
+    ///
+    /// This clocks SCL until SDA goes high (at most nine times: enough for
+    /// a device to finish sending a byte and see a NACK), then sends a STOP
+    /// condition so every device goes back to waiting for a START.
+    ///
+    /// Transfers call this themselves when the bus isn't idle or a transfer
+    /// times out, so it's only needed to recover the bus straight away.
+    pub fn recover(&mut self) -> Result<(), Error> {
+        let twi = twi();
+        let gpio = gpio();
+        let scl_pin = twi.pselscl.read().bits() as usize;
+        let sda_pin = twi.pselsda.read().bits() as usize;
+        let (scl, sda) = (1 << scl_pin, 1 << sda_pin);
+
+        /* Take over the pins: they're open drain, so setting the output
+         * high releases the line */
+        twi.enable.write(|w| w.enable().disabled());
+        gpio.outset.write(|w| unsafe { w.bits(scl | sda) });
+        gpio.pin_cnf[scl_pin].modify(|_, w| w.dir().output());
+        gpio.pin_cnf[sda_pin].modify(|_, w| w.dir().output());
+
+        let result = release_bus(gpio, scl, sda);
+
+        gpio.pin_cnf[scl_pin].modify(|_, w| w.dir().input());
+        gpio.pin_cnf[sda_pin].modify(|_, w| w.dir().input());
+        twi.enable.write(|w| w.enable().enabled());
+        result
+    }
+
+    /// Writes `bytes` (if any) to the device at `address`, then reads into
+    /// `buffer` (if it isn't empty) after a repeated START.
+    fn transfer(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        if !self.is_idle() {
+            self.recover()?;
+        }
+
+        let twi = twi();
+        let errors = twi.errorsrc.read().bits();
+        twi.errorsrc.write(|w| unsafe { w.bits(errors) });
+        twi.events_error.write(|w| unsafe { w.bits(0) });
+        twi.events_stopped.write(|w| unsafe { w.bits(0) });
+        twi.shorts.write(|w| unsafe { w.bits(0) });
+        twi.address.write(|w| unsafe { w.address().bits(address) });
+
+        self.timer.start(self.timeout);
+        match self.exchange(bytes, buffer) {
+            Ok(()) => Ok(()),
+            Err(Failure::Timeout) => {
+                /* Whatever is holding the bus, free it for the next
+                 * transfer */
+                twi.shorts.write(|w| unsafe { w.bits(0) });
+                let _ = self.recover();
+                Err(Error::StuckBus)
+            }
+            Err(Failure::Twi) => {
+                let errors = twi.errorsrc.read().bits();
+                twi.errorsrc.write(|w| unsafe { w.bits(errors) });
+                twi.events_error.write(|w| unsafe { w.bits(0) });
+
+                /* Send a STOP to free the bus for the next transfer */
+                twi.shorts.write(|w| unsafe { w.bits(0) });
+                twi.events_stopped.write(|w| unsafe { w.bits(0) });
+                twi.tasks_stop.write(|w| unsafe { w.bits(1) });
+                if let Err(Failure::Timeout) =
+                    self.wait_for(|twi| twi.events_stopped.read().bits() != 0)
+                {
+                    let _ = self.recover();
+                }
+
+                /* The only other error is a data NACK */
+                Err(if errors & ERROR_ANACK != 0 {
+                    Error::AddressNack
+                } else if errors & ERROR_OVERRUN != 0 {
+                    Error::Overrun
+                } else {
+                    Error::DataNack
+                })
+            }
+        }
+    }
+
+    /// Does the transfer set up by `transfer()`.
+    fn exchange(&mut self, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Failure> {
+        let twi = twi();
+
+        if !bytes.is_empty() || buffer.is_empty() {
+            twi.tasks_starttx.write(|w| unsafe { w.bits(1) });
+            for &byte in bytes {
+                twi.events_txdsent.write(|w| unsafe { w.bits(0) });
+                twi.txd.write(|w| unsafe { w.bits(u32::from(byte)) });
+                self.wait_for(|twi| twi.events_txdsent.read().bits() != 0)?;
+            }
+        }
+
+        if let Some((last, before)) = buffer.split_last_mut() {
+            /* Each byte is received once RESUME is triggered: suspend after
+             * each but the last, and stop after that */
+            if before.is_empty() {
+                twi.shorts.write(|w| w.bb_stop().enabled());
+            } else {
+                twi.shorts.write(|w| w.bb_suspend().enabled());
+            }
+            twi.events_rxdready.write(|w| unsafe { w.bits(0) });
+            twi.tasks_startrx.write(|w| unsafe { w.bits(1) });
+            for slot in before.iter_mut() {
+                twi.tasks_resume.write(|w| unsafe { w.bits(1) });
+                *slot = self.receive()?;
+            }
+            twi.shorts.write(|w| w.bb_stop().enabled());
+            twi.tasks_resume.write(|w| unsafe { w.bits(1) });
+            *last = self.receive()?;
+        } else {
+            twi.tasks_stop.write(|w| unsafe { w.bits(1) });
+        }
+
+        self.wait_for(|twi| twi.events_stopped.read().bits() != 0)
+    }
+
+    fn receive(&mut self) -> Result<u8, Failure> {
+        let twi = twi();
+        self.wait_for(|twi| twi.events_rxdready.read().bits() != 0)?;
+        twi.events_rxdready.write(|w| unsafe { w.bits(0) });
+        Ok(twi.rxd.read().bits() as u8)
+    }
+
+    /// Waits for an event, failing if the TWI reports an error first or the
+    /// timeout runs out.
+    fn wait_for(&mut self, event: impl Fn(&twi0::RegisterBlock) -> bool) -> Result<(), Failure> {
+        let twi = twi();
+        loop {
+            if event(twi) {
+                return Ok(());
+            }
+            if twi.events_error.read().bits() != 0 {
+                return Err(Failure::Twi);
+            }
+            if self.timer.wait().is_ok() {
+                return Err(Failure::Timeout);
+            }
+        }
+    }
+}
+
+impl<T> Write for I2cBus<T>
+where
+    T: CountDown,
+    T::Time: From<Duration>,
+{
+    type Error = Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.transfer(address, bytes, &mut [])
+    }
+}
+
+impl<T> Read for I2cBus<T>
+where
+    T: CountDown,
+    T::Time: From<Duration>,
+{
+    type Error = Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        self.transfer(address, &[], buffer)
+    }
+}
+
+impl<T> WriteRead for I2cBus<T>
+where
+    T: CountDown,
+    T::Time: From<Duration>,
+{
+    type Error = Error;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        self.transfer(address, bytes, buffer)
+    }
+}
+
+/// Clocks SCL until SDA is released, then sends a STOP condition.
+fn release_bus(gpio: &gpio::RegisterBlock, scl: u32, sda: u32) -> Result<(), Error> {
+    wait_for_high(gpio, scl)?;
+    for _ in 0..9 {
+        if gpio.in_.read().bits() & sda != 0 {
+            break;
+        }
+        gpio.outclr.write(|w| unsafe { w.bits(scl) });
+        cortex_m::asm::delay(HALF_PERIOD_CYCLES);
+        gpio.outset.write(|w| unsafe { w.bits(scl) });
+        wait_for_high(gpio, scl)?;
+    }
+
+    /* STOP: SDA goes high while SCL is high */
+    gpio.outclr.write(|w| unsafe { w.bits(scl) });
+    cortex_m::asm::delay(HALF_PERIOD_CYCLES);
+    gpio.outclr.write(|w| unsafe { w.bits(sda) });
+    cortex_m::asm::delay(HALF_PERIOD_CYCLES);
+    gpio.outset.write(|w| unsafe { w.bits(scl) });
+    wait_for_high(gpio, scl)?;
+    gpio.outset.write(|w| unsafe { w.bits(sda) });
+    cortex_m::asm::delay(HALF_PERIOD_CYCLES);
+
+    if gpio.in_.read().bits() & (scl | sda) == scl | sda {
+        Ok(())
+    } else {
+        Err(Error::StuckBus)
+    }
+}
+
+/// Waits half a clock period, and then for a device which is stretching
+/// the clock to release SCL.
+fn wait_for_high(gpio: &gpio::RegisterBlock, scl: u32) -> Result<(), Error> {
+    for _ in 0..CLOCK_STRETCH_TIMEOUT {
+        cortex_m::asm::delay(HALF_PERIOD_CYCLES);
+        if gpio.in_.read().bits() & scl != 0 {
+            return Ok(());
+        }
+    }
+    Err(Error::StuckBus)
+}
+
+/// Returns the GPIO bits for the SCL and SDA pins which `TWI1` uses.
+fn pin_masks() -> (u32, u32) {
+    let twi = twi();
+    (
+        1 << twi.pselscl.read().bits(),
+        1 << twi.pselsda.read().bits(),
+    )
+}
+
+fn gpio() -> &'static gpio::RegisterBlock {
+    unsafe { &*GPIO::ptr() }
+}
+
+fn twi() -> &'static twi0::RegisterBlock {
+    unsafe { &*TWI1::ptr() }
+}
@@ -1,4 +1,4 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![allow(non_camel_case_types)]
 
 pub use nrf51_hal as hal;
@@ -7,23 +7,91 @@ pub use nb::*;
 
 pub use crate::hal::nrf51::*;
 
-use crate::hal::gpio::gpio::Parts;
+use crate::hal::gpio::gpio::{PIN, PIN24, PIN25};
+use crate::hal::gpio::{Floating, Input, Output, PushPull};
 use crate::hal::serial::*;
 
+pub mod buffered_uart;
+pub mod buttons;
 pub mod display;
+pub mod framing;
+pub mod gpiote;
+pub mod i2c_bus;
 pub mod led;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod plotter;
+pub mod ppi;
+pub mod remote;
+pub mod sensors;
+pub mod shared_i2c;
+pub mod shell;
+pub mod soft_uart;
+pub mod temperature;
+pub mod units;
 
-// FIXME: Rewrite as macro to prevent problems consuming parts of gpio
-pub fn serial_port(
-    gpio: Parts,
+/// Opens the serial port connected to the USB interface chip (the DAPLink
+/// "mbed serial port"), on pins 24 (TX) and 25 (RX).
+///
+/// The pins may be in any mode; they're reconfigured as needed. To open the
+/// port while keeping the rest of the GPIO pins available, use the
+/// [`serial_port!`] macro, which takes the two pins out of `gpio::Parts`:
+///
+/// ```ignore
+/// let gpio = p.GPIO.split();
+/// let (mut tx, mut rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+/// let button_a = gpio.pin17.into_floating_input();
+/// ```
+///
+/// [`serial_port!`]: serial_port!
+pub fn serial_port<TXMODE, RXMODE>(
+    tx: PIN24<TXMODE>,
+    rx: PIN25<RXMODE>,
     uart: hal::nrf51::UART0,
     speed: BAUDRATE_A,
 ) -> (Tx<hal::nrf51::UART0>, Rx<hal::nrf51::UART0>) {
     /* Configure RX and TX pins accordingly */
-    let tx = gpio.pin24.into_push_pull_output().into();
-    let rx = gpio.pin25.into_floating_input().into();
+    let tx = tx.into_push_pull_output().into();
+    let rx = rx.into_floating_input().into();
 
+    serial_port_on_pins(tx, rx, uart, speed)
+}
+
+/// Opens a serial port on any pair of pins, for example edge connector pins
+/// 0 and 1 (GPIO pins 3 and 2) for an external serial device.
+///
+/// The nRF51 has only one UART, so this can't be used at the same time as
+/// [`serial_port()`].
+///
+/// ```ignore
+/// let gpio = p.GPIO.split();
+/// let tx = gpio.pin3.into_push_pull_output().into();
+/// let rx = gpio.pin2.into_floating_input().into();
+/// let (mut tx, mut rx) = microbit::serial_port_on_pins(tx, rx, p.UART0, BAUD9600);
+/// ```
+pub fn serial_port_on_pins(
+    tx: PIN<Output<PushPull>>,
+    rx: PIN<Input<Floating>>,
+    uart: hal::nrf51::UART0,
+    speed: BAUDRATE_A,
+) -> (Tx<hal::nrf51::UART0>, Rx<hal::nrf51::UART0>) {
     /* Set up serial port using the prepared pins */
     let serial = Serial::uart0(uart, tx, rx, speed);
     serial.split()
 }
+
+/// Opens the serial port connected to the USB interface chip, taking pins 24
+/// and 25 out of a `gpio::Parts`.
+///
+/// The remaining pins in the `gpio::Parts` can still be used.
+///
+/// ```ignore
+/// let gpio = p.GPIO.split();
+/// let (mut tx, mut rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+/// ```
+#[macro_export]
+macro_rules! serial_port {
+    ($gpio:expr, $uart:expr, $speed:expr) => {
+        $crate::serial_port($gpio.pin24, $gpio.pin25, $uart, $speed)
+    };
+}
@@ -0,0 +1,385 @@
+//! A [`log`] backend writing to the serial port.
+//!
+//! This module is only available with the `log` feature.
+//!
+//! Once [`init()`] has been called, messages from the `log` macros
+//! (`info!()`, `debug!()` and so on) anywhere in the program, including in
+//! other crates, are written to the UART `Tx` returned by [`serial_port()`],
+//! one line each:
+//!
+//! ```text
+//! [   12.34] INFO  my_app::sensors: calibrated
+//! ```
+//!
+//! The timestamp is the time since `init()` in seconds, with 10ms
+//! resolution, taken from `RTC1`. It wraps around after about 46 hours.
+//!
+//! Each message is formatted into a line of at most 96 characters (longer
+//! ones are cut short), which is added to a queue and then sent by the code
+//! that logged it. Interrupts are only held off while the line is queued, not
+//! while it's sent, but logging still waits for the UART (about 90µs per
+//! character at 115200 baud), so avoid it in code with tight timing, such as
+//! the display's interrupt handler.
+//!
+//! Logging is safe from interrupt handlers, and lines are never mixed up: a
+//! message logged while another is being sent waits in the queue and is sent
+//! by the code it interrupted, once that code's own line is finished. If the
+//! queue (256 bytes) is full, the message is dropped.
+//!
+//! # Filtering
+//!
+//! Messages are filtered by level: [`set_level()`] sets the level for the
+//! whole program, and [`set_module_level()`] overrides it for a module and
+//! its submodules (for example to see `debug!()` messages from one driver
+//! only). Both can be changed at any time.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let (tx, _) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+//! microbit::logger::init(tx, p.RTC1, LevelFilter::Info);
+//! microbit::logger::set_module_level("microbit::buttons", LevelFilter::Debug);
+//!
+//! log::info!("started");
+//! ```
+//!
+//! The low-frequency clock must be running for the timestamps to advance.
+//!
+//! [`log`]: log
+//! [`init()`]: logger::init
+//! [`serial_port()`]: serial_port
+//! [`set_level()`]: logger::set_level
+//! [`set_module_level()`]: logger::set_module_level
+
+use core::cell::RefCell;
+use core::fmt::Write;
+
+use cortex_m::interrupt::{self, Mutex};
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::hal::nrf51::{RTC1, UART0};
+use crate::hal::prelude::*;
+use crate::hal::serial::Tx;
+
+/// Number of per-module levels which can be set.
+const MODULE_LEVELS: usize = 8;
+
+/// Longest line written, including the line ending.
+const LINE_LENGTH: usize = 98;
+
+/// Size of the queue of lines waiting to be sent.
+const QUEUE_LENGTH: usize = 256;
+
+/// RTC prescaler giving a 100Hz tick from the 32768Hz clock.
+const RTC_PRESCALER: u32 = 327;
+
+/// One formatted line, cut short if it doesn't fit.
+struct Line {
+    bytes: [u8; LINE_LENGTH],
+    len: usize,
+}
+
+impl Line {
+    fn new() -> Line {
+        Line {
+            bytes: [0; LINE_LENGTH],
+            len: 0,
+        }
+    }
+
+    /// Ends the line, replacing its last characters if it's full.
+    fn finish(&mut self) -> &[u8] {
+        self.len = self.len.min(LINE_LENGTH - 2);
+        self.bytes[self.len..self.len + 2].copy_from_slice(b"\r\n");
+        self.len += 2;
+        &self.bytes[..self.len]
+    }
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        // Leave room for the line ending, and don't split a character.
+        let mut count = s.len().min(LINE_LENGTH - 2 - self.len);
+        while !s.is_char_boundary(count) {
+            count -= 1;
+        }
+        self.bytes[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        if count < s.len() {
+            Err(core::fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Bytes waiting to be sent, oldest first.
+struct Queue {
+    bytes: [u8; QUEUE_LENGTH],
+    start: usize,
+    len: usize,
+}
+
+impl Queue {
+    const fn new() -> Queue {
+        Queue {
+            bytes: [0; QUEUE_LENGTH],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// Adds a whole line, or nothing if there isn't room for it. Returns
+    /// whether the line was added.
+    fn push_line(&mut self, line: &[u8]) -> bool {
+        if line.len() > QUEUE_LENGTH - self.len {
+            return false;
+        }
+        for &byte in line {
+            self.bytes[(self.start + self.len) % QUEUE_LENGTH] = byte;
+            self.len += 1;
+        }
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.bytes[self.start];
+        self.start = (self.start + 1) % QUEUE_LENGTH;
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+struct State {
+    /// The UART, or `None` while a `log()` call is sending from the queue.
+    tx: Option<Tx<UART0>>,
+    queue: Queue,
+    rtc: RTC1,
+    level: LevelFilter,
+    modules: [Option<(&'static str, LevelFilter)>; MODULE_LEVELS],
+}
+
+impl State {
+    /// Returns the level which applies to messages from `target`.
+    // `Option::is_none_or()` needs Rust 1.82.
+    #[allow(clippy::unnecessary_map_or)]
+    fn level_for(&self, target: &str) -> LevelFilter {
+        let mut best: Option<(&str, LevelFilter)> = None;
+        for &(prefix, level) in self.modules.iter().flatten() {
+            let matches = target.starts_with(prefix)
+                && (target.len() == prefix.len() || target[prefix.len()..].starts_with("::"));
+            if matches && best.map_or(true, |(p, _)| prefix.len() > p.len()) {
+                best = Some((prefix, level));
+            }
+        }
+        best.map_or(self.level, |(_, level)| level)
+    }
+
+    /// Tells `log` the most verbose level which might be logged, so it can
+    /// skip formatting anything beyond that.
+    fn update_max_level(&self) {
+        let max = self
+            .modules
+            .iter()
+            .flatten()
+            .map(|&(_, level)| level)
+            .fold(self.level, |a, b| a.max(b));
+        log::set_max_level(max);
+    }
+}
+
+struct UartLogger {
+    state: Mutex<RefCell<Option<State>>>,
+}
+
+static LOGGER: UartLogger = UartLogger {
+    state: Mutex::new(RefCell::new(None)),
+};
+
+impl Log for UartLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        interrupt::free(|cs| match self.state.borrow(cs).try_borrow() {
+            Ok(state) => state
+                .as_ref()
+                .is_some_and(|state| metadata.level() <= state.level_for(metadata.target())),
+            Err(_) => false,
+        })
+    }
+
+    fn log(&self, record: &Record) {
+        let ticks = interrupt::free(|cs| {
+            let state = self.state.borrow(cs).borrow();
+            let state = state.as_ref()?;
+            if record.level() > state.level_for(record.target()) {
+                return None;
+            }
+            Some(state.rtc.counter.read().bits())
+        });
+        let ticks = match ticks {
+            Some(ticks) => ticks,
+            None => return,
+        };
+
+        // Format outside the critical section, so other interrupts (and
+        // messages logged while formatting) aren't held up.
+        let mut line = Line::new();
+        let _ = write!(
+            line,
+            "[{:5}.{:02}] {:5} {}: {}",
+            ticks / 100,
+            ticks % 100,
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        // If the UART is already taken, this call interrupted another one,
+        // which will send the queued line once its own is finished.
+        let mut tx = interrupt::free(|cs| {
+            let mut state = self.state.borrow(cs).borrow_mut();
+            let state = state.as_mut()?;
+            state.queue.push_line(line.finish());
+            state.tx.take()
+        });
+        while tx.is_some() {
+            // Hand the UART back in the same critical section as finding the
+            // queue empty, so that a line queued in between isn't stranded.
+            let byte = interrupt::free(|cs| {
+                let mut state = self.state.borrow(cs).borrow_mut();
+                let state = state.as_mut()?;
+                let byte = state.queue.pop();
+                if byte.is_none() {
+                    state.tx = tx.take();
+                }
+                byte
+            });
+            if let (Some(byte), Some(tx)) = (byte, tx.as_mut()) {
+                let _ = nb::block!(tx.write(byte));
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the logger, writing to `tx`, and starts `RTC1` for the
+/// timestamps.
+///
+/// Messages less important than `level` are discarded.
+///
+/// If a logger has already been installed (by this function or any other),
+/// `tx` and `rtc` are dropped and nothing changes.
+pub fn init(tx: Tx<UART0>, rtc: RTC1, level: LevelFilter) {
+    rtc.tasks_stop.write(|w| unsafe { w.bits(1) });
+    rtc.tasks_clear.write(|w| unsafe { w.bits(1) });
+    rtc.prescaler.write(|w| unsafe { w.bits(RTC_PRESCALER) });
+    rtc.tasks_start.write(|w| unsafe { w.bits(1) });
+
+    interrupt::free(|cs| {
+        // thumbv6m has no compare-and-swap, so `log::set_logger()` isn't
+        // available. The critical section makes the racy version safe.
+        if unsafe { log::set_logger_racy(&LOGGER) }.is_err() {
+            return;
+        }
+        let state = State {
+            tx: Some(tx),
+            queue: Queue::new(),
+            rtc,
+            level,
+            modules: [None; MODULE_LEVELS],
+        };
+        state.update_max_level();
+        *LOGGER.state.borrow(cs).borrow_mut() = Some(state);
+    });
+}
+
+/// Sets the level for modules without a level of their own.
+pub fn set_level(level: LevelFilter) {
+    interrupt::free(|cs| {
+        if let Some(state) = LOGGER.state.borrow(cs).borrow_mut().as_mut() {
+            state.level = level;
+            state.update_max_level();
+        }
+    });
+}
+
+/// Sets the level for a module and its submodules, given its path (for
+/// example `"my_app::sensors"`).
+///
+/// The most specific matching module's level applies. Returns `false` if
+/// levels have already been set for as many modules as there's room for (8),
+/// or if the logger hasn't been installed.
+pub fn set_module_level(module: &'static str, level: LevelFilter) -> bool {
+    interrupt::free(|cs| {
+        let mut state = LOGGER.state.borrow(cs).borrow_mut();
+        let state = match state.as_mut() {
+            Some(state) => state,
+            None => return false,
+        };
+        let slot = match state
+            .modules
+            .iter()
+            .position(|entry| entry.is_some_and(|(m, _)| m == module))
+            .or_else(|| state.modules.iter().position(Option::is_none))
+        {
+            Some(slot) => slot,
+            None => return false,
+        };
+        state.modules[slot] = Some((module, level));
+        state.update_max_level();
+        true
+    })
+}
+
+/// Removes a level set with [`set_module_level()`], so that the module
+/// follows the overall level again.
+pub fn clear_module_level(module: &str) {
+    interrupt::free(|cs| {
+        if let Some(state) = LOGGER.state.borrow(cs).borrow_mut().as_mut() {
+            for entry in state.modules.iter_mut() {
+                if entry.is_some_and(|(m, _)| m == module) {
+                    *entry = None;
+                }
+            }
+            state.update_max_level();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_lines_are_cut_short() {
+        let mut line = Line::new();
+        assert!(write!(line, "{}", "x".repeat(200)).is_err());
+        let bytes = line.finish();
+        assert_eq!(bytes.len(), LINE_LENGTH);
+        assert!(bytes.ends_with(b"x\r\n"));
+    }
+
+    #[test]
+    fn lines_are_not_cut_inside_a_character() {
+        let mut line = Line::new();
+        let _ = write!(line, "{}é", "x".repeat(LINE_LENGTH - 3));
+        assert_eq!(line.finish().len(), LINE_LENGTH - 1);
+    }
+
+    #[test]
+    fn queue_keeps_whole_lines_in_order() {
+        let mut queue = Queue::new();
+        assert!(queue.push_line(&[1; QUEUE_LENGTH - 2]));
+        assert!(!queue.push_line(b"abc"));
+        for _ in 0..QUEUE_LENGTH - 2 {
+            assert_eq!(queue.pop(), Some(1));
+        }
+        assert!(queue.push_line(b"abc"));
+        assert!(queue.push_line(b"def"));
+        let sent: Vec<u8> = core::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(sent, b"abcdef");
+    }
+}
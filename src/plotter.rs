@@ -0,0 +1,233 @@
+//! Printing values in a format which serial plotters can graph.
+//!
+//! The Mu editor and the MakeCode console plot any serial line that looks
+//! like a tuple of numbers, such as `(12, -3, 4.5)`, as MicroPython's
+//! `print((x, y, z))` produces. [`Plotter`] prints lines in that format, or
+//! as CSV with a header row for spreadsheets and other tools.
+//!
+//! A `Plotter` writes to anything implementing [`core::fmt::Write`], such as
+//! the `Tx` returned by [`serial_port()`]. Plotters work best with a steady
+//! rate of readings (and can't keep up with too many), so [`plot()`] only
+//! prints a line when the configured interval has passed since the last
+//! one.
+//!
+//! # Time
+//!
+//! Like [`Buttons`], `Plotter` doesn't use a timer itself: each call to
+//! [`plot()`] passes the current time in milliseconds, from whatever clock
+//! the application has. Times are allowed to wrap around.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let (tx, _rx) = microbit::serial_port!(gpio, p.UART0, BAUD115200);
+//! let mut plotter = Plotter::csv(tx, &["x", "y", "z"], 100);
+//!
+//! loop {
+//!     if plotter.is_due(now_ms) {
+//!         let (x, y, z) = read_sensor();
+//!         plotter.plot(now_ms, &[x, y, z])?;
+//!     }
+//! }
+//! ```
+//!
+//! [`Plotter`]: plotter::Plotter
+//! [`plot()`]: plotter::Plotter::plot
+//! [`Buttons`]: buttons::Buttons
+//! [`serial_port()`]: serial_port
+
+use core::fmt::{self, Display, Write};
+
+/// How a [`Plotter`] prints its values.
+///
+/// [`Plotter`]: plotter::Plotter
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A tuple per line, like `(1, 2, 3)`, as the Mu editor and the
+    /// MakeCode console expect.
+    Tuple,
+    /// Comma-separated values, like `1,2,3`, after a header row naming
+    /// the columns.
+    Csv {
+        /// The column names.
+        header: &'static [&'static str],
+    },
+}
+
+/// Prints rows of values at a fixed rate.
+pub struct Plotter<W> {
+    out: W,
+    format: Format,
+    interval_ms: u32,
+    next_due: Option<u32>,
+    header_written: bool,
+}
+
+impl<W: Write> Plotter<W> {
+    /// Returns a `Plotter` printing to `out` in the given format, at most
+    /// once every `interval_ms` milliseconds.
+    pub fn new(out: W, format: Format, interval_ms: u32) -> Plotter<W> {
+        Plotter {
+            out,
+            format,
+            interval_ms,
+            next_due: None,
+            header_written: false,
+        }
+    }
+
+    /// Returns a `Plotter` printing tuples for the Mu editor or MakeCode.
+    pub fn tuples(out: W, interval_ms: u32) -> Plotter<W> {
+        Plotter::new(out, Format::Tuple, interval_ms)
+    }
+
+    /// Returns a `Plotter` printing CSV, with `header` as the first row.
+    pub fn csv(out: W, header: &'static [&'static str], interval_ms: u32) -> Plotter<W> {
+        Plotter::new(out, Format::Csv { header }, interval_ms)
+    }
+
+    /// Gives back the writer.
+    pub fn free(self) -> W {
+        self.out
+    }
+
+    /// Returns whether [`plot()`] would print a line at time `now_ms`.
+    ///
+    /// Use this to avoid taking readings which wouldn't be printed.
+    ///
+    /// [`plot()`]: plotter::Plotter::plot
+    pub fn is_due(&self, now_ms: u32) -> bool {
+        match self.next_due {
+            None => true,
+            Some(due) => now_ms.wrapping_sub(due) as i32 >= 0,
+        }
+    }
+
+    /// Prints a row of values if the interval has passed since the last
+    /// one, and returns whether it did.
+    ///
+    /// Rows are spaced exactly `interval_ms` apart as long as `plot()` is
+    /// called often enough; if it falls more than a whole interval behind,
+    /// the schedule restarts from `now_ms`.
+    pub fn plot<T: Display>(&mut self, now_ms: u32, values: &[T]) -> Result<bool, fmt::Error> {
+        if !self.is_due(now_ms) {
+            return Ok(false);
+        }
+        let next_due = match self.next_due {
+            Some(due) if now_ms.wrapping_sub(due) < self.interval_ms => {
+                due.wrapping_add(self.interval_ms)
+            }
+            _ => now_ms.wrapping_add(self.interval_ms),
+        };
+        self.next_due = Some(next_due);
+        self.write_row(values)?;
+        Ok(true)
+    }
+
+    /// Prints a row of values straight away, ignoring the interval.
+    pub fn write_row<T: Display>(&mut self, values: &[T]) -> fmt::Result {
+        match self.format {
+            Format::Tuple => {
+                self.out.write_char('(')?;
+                write_separated(&mut self.out, values, ", ")?;
+                // A one-element tuple is printed as `(x,)` by Python.
+                if values.len() == 1 {
+                    self.out.write_char(',')?;
+                }
+                self.out.write_str(")\r\n")
+            }
+            Format::Csv { header } => {
+                if !self.header_written {
+                    write_separated(&mut self.out, header, ",")?;
+                    self.out.write_str("\r\n")?;
+                    self.header_written = true;
+                }
+                write_separated(&mut self.out, values, ",")?;
+                self.out.write_str("\r\n")
+            }
+        }
+    }
+}
+
+fn write_separated<W: Write, T: Display>(
+    out: &mut W,
+    values: &[T],
+    separator: &str,
+) -> fmt::Result {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            out.write_str(separator)?;
+        }
+        write!(out, "{}", value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tuples() {
+        let mut plotter = Plotter::tuples(String::new(), 100);
+        plotter.write_row(&[12, -3]).unwrap();
+        plotter.write_row(&[4.5]).unwrap();
+        plotter.write_row::<i32>(&[]).unwrap();
+        assert_eq!(plotter.free(), "(12, -3)\r\n(4.5,)\r\n()\r\n");
+    }
+
+    #[test]
+    fn csv_header_is_written_once() {
+        let mut plotter = Plotter::csv(String::new(), &["x", "y"], 100);
+        assert!(plotter.plot(0, &[1, 2]).unwrap());
+        assert!(plotter.plot(100, &[3, 4]).unwrap());
+        plotter.write_row(&[5, 6]).unwrap();
+        assert_eq!(plotter.free(), "x,y\r\n1,2\r\n3,4\r\n5,6\r\n");
+    }
+
+    #[test]
+    fn plots_once_per_interval() {
+        let mut plotter = Plotter::tuples(String::new(), 100);
+        assert!(plotter.is_due(5));
+        assert!(plotter.plot(5, &[1]).unwrap());
+        assert!(!plotter.is_due(104));
+        assert!(!plotter.plot(104, &[2]).unwrap());
+        // Late calls don't push the schedule back.
+        assert!(plotter.plot(130, &[3]).unwrap());
+        assert!(!plotter.is_due(204));
+        assert!(plotter.is_due(205));
+        assert_eq!(plotter.free(), "(1,)\r\n(3,)\r\n");
+    }
+
+    #[test]
+    fn schedule_restarts_after_falling_behind() {
+        let mut plotter = Plotter::tuples(String::new(), 100);
+        assert!(plotter.plot(0, &[1]).unwrap());
+        // More than a whole interval after the row due at 100.
+        assert!(plotter.plot(250, &[2]).unwrap());
+        assert!(!plotter.is_due(349));
+        assert!(plotter.plot(350, &[3]).unwrap());
+    }
+
+    #[test]
+    fn schedule_across_wrap_around() {
+        let mut plotter = Plotter::tuples(String::new(), 100);
+        assert!(plotter.plot(u32::MAX - 49, &[1]).unwrap());
+        assert!(!plotter.is_due(u32::MAX));
+        assert!(!plotter.is_due(49));
+        assert!(plotter.is_due(50));
+        assert!(plotter.plot(60, &[2]).unwrap());
+        assert!(!plotter.is_due(149));
+        assert!(plotter.is_due(150));
+    }
+
+    #[test]
+    fn write_row_ignores_the_interval() {
+        let mut plotter = Plotter::tuples(String::new(), 100);
+        assert!(plotter.plot(0, &[1]).unwrap());
+        plotter.write_row(&[2]).unwrap();
+        assert!(!plotter.is_due(99));
+        assert!(plotter.plot(100, &[3]).unwrap());
+        assert_eq!(plotter.free(), "(1,)\r\n(2,)\r\n(3,)\r\n");
+    }
+}
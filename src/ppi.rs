@@ -0,0 +1,97 @@
+//! Handles for the programmable PPI channels.
+//!
+//! PPI (the programmable peripheral interconnect) connects an event in one
+//! peripheral to a task in another, so the task is triggered by the hardware
+//! as soon as the event happens, without waiting for an interrupt handler.
+//! [`split()`] takes the `PPI` peripheral and hands its 16 programmable
+//! channels out as [`Channel`] handles, so two drivers can't end up
+//! configuring the same channel.
+//!
+//! Drivers which use PPI (such as [`SoftUart`]) take a `Channel`. The
+//! [`PpiDisplay`] takes the whole `PPI` peripheral instead, so it can't be
+//! used at the same time.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let ppi_channels = microbit::ppi::split(p.PPI);
+//! let (_gpiote, channels) = Gpiote::new(p.GPIOTE);
+//! let gps = SoftUart::new(p.TIMER2, channels.ch0, ppi_channels.ch0, tx, rx, 9600);
+//! ```
+//!
+//! [`split()`]: ppi::split
+//! [`Channel`]: ppi::Channel
+//! [`SoftUart`]: soft_uart::SoftUart
+//! [`PpiDisplay`]: display::ppi::PpiDisplay
+
+use crate::hal::nrf51::{ppi, PPI};
+
+/// One of the 16 programmable PPI channels, not currently in use.
+#[derive(Debug)]
+pub struct Channel {
+    index: usize,
+}
+
+impl Channel {
+    /// Returns the channel's number (0 to 15).
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Connects the event register at address `event` to the task register
+    /// at address `task`, and enables the channel.
+    pub(crate) fn connect(&self, event: u32, task: u32) {
+        let ppi = ppi();
+        ppi.ch[self.index].eep.write(|w| unsafe { w.bits(event) });
+        ppi.ch[self.index].tep.write(|w| unsafe { w.bits(task) });
+        ppi.chenset.write(|w| unsafe { w.bits(1 << self.index) });
+    }
+
+    /// Disables the channel.
+    pub(crate) fn disconnect(&self) {
+        ppi().chenclr.write(|w| unsafe { w.bits(1 << self.index) });
+    }
+}
+
+macro_rules! channels {
+    ($($ch:ident => $index:expr,)+) => {
+        /// The 16 programmable PPI channels, as returned by [`split()`].
+        pub struct Channels {
+            $(pub $ch: Channel,)+
+        }
+
+        /// Takes ownership of the PPI peripheral, and returns handles for
+        /// its programmable channels.
+        ///
+        /// All the programmable channels are disabled.
+        pub fn split(ppi: PPI) -> Channels {
+            ppi.chenclr.write(|w| unsafe { w.bits(0xFFFF) });
+            Channels {
+                $($ch: Channel { index: $index },)+
+            }
+        }
+    }
+}
+
+channels! {
+    ch0 => 0,
+    ch1 => 1,
+    ch2 => 2,
+    ch3 => 3,
+    ch4 => 4,
+    ch5 => 5,
+    ch6 => 6,
+    ch7 => 7,
+    ch8 => 8,
+    ch9 => 9,
+    ch10 => 10,
+    ch11 => 11,
+    ch12 => 12,
+    ch13 => 13,
+    ch14 => 14,
+    ch15 => 15,
+}
+
+fn ppi() -> &'static ppi::RegisterBlock {
+    unsafe { &*PPI::ptr() }
+}
@@ -0,0 +1,398 @@
+//! Remote control of the board from a host, over the serial port.
+//!
+//! A host program sends requests (such as `SetImage` or `ReadMagnetometer`)
+//! as framed messages, and [`RemoteControl`] carries them out and replies.
+//! The wire format is documented in [`microbit_protocol::message`]; the
+//! `microbit-host` crate provides a matching client.
+//!
+//! The application decides how each request is carried out by implementing
+//! [`RemoteBoard`]. Every method has a default which refuses the request, so
+//! only the features the application supports need implementing.
+//!
+//! Pins are numbered as on the edge connector; [`edge_pin_to_gpio()`] gives
+//! the corresponding GPIO pin numbers.
+//!
+//! # Example
+//!
+//! ```ignore
+//! struct Board { rng: rng::Rng }
+//!
+//! impl RemoteBoard for Board {
+//!     fn random(&mut self) -> Result<u32, Refusal> {
+//!         let mut bytes = [0; 4];
+//!         self.rng.read(&mut bytes).map_err(|_| Refusal::Failed)?;
+//!         Ok(u32::from_le_bytes(bytes))
+//!     }
+//! }
+//!
+//! let mut remote = RemoteControl::new();
+//! loop {
+//!     remote.poll(&mut rx, &mut tx, &mut board)?;
+//! }
+//! ```
+//!
+//! [`microbit_protocol::message`]: microbit_protocol::message
+//! [`RemoteControl`]: remote::RemoteControl
+//! [`RemoteBoard`]: remote::RemoteBoard
+//! [`edge_pin_to_gpio()`]: remote::edge_pin_to_gpio
+
+use embedded_hal::serial;
+
+use crate::framing::{self, Message, MessageReceiver};
+use microbit_protocol::message::{NAK_BAD_ARGUMENT, NAK_FAILED, NAK_UNSUPPORTED};
+
+/// GPIO pin numbers of edge connector pins 0 to 20 (`None` for the power
+/// pins 17 and 18).
+const EDGE_PINS: [Option<u8>; 21] = [
+    Some(3),
+    Some(2),
+    Some(1),
+    Some(4),
+    Some(5),
+    Some(17),
+    Some(12),
+    Some(11),
+    Some(18),
+    Some(10),
+    Some(6),
+    Some(26),
+    Some(20),
+    Some(23),
+    Some(22),
+    Some(21),
+    Some(16),
+    None,
+    None,
+    Some(0),
+    Some(30),
+];
+
+/// Returns the GPIO pin number of an edge connector pin.
+///
+/// Returns `None` for pins 17 and 18 (which are 3V) and for numbers above 20.
+/// Note that many edge pins are shared with the display, the buttons or the
+/// internal I2C bus.
+pub fn edge_pin_to_gpio(pin: u8) -> Option<u8> {
+    EDGE_PINS.get(pin as usize).cloned().flatten()
+}
+
+/// Why a request couldn't be carried out.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Refusal {
+    /// The application doesn't support this request.
+    Unsupported,
+    /// An argument is out of range.
+    BadArgument,
+    /// The hardware didn't respond.
+    Failed,
+}
+
+impl Refusal {
+    fn reason(self) -> u8 {
+        match self {
+            Refusal::Unsupported => NAK_UNSUPPORTED,
+            Refusal::BadArgument => NAK_BAD_ARGUMENT,
+            Refusal::Failed => NAK_FAILED,
+        }
+    }
+}
+
+/// Carries out remote requests.
+///
+/// Each method has a default implementation returning
+/// `Err(Refusal::Unsupported)`.
+pub trait RemoteBoard {
+    /// Shows an image; `brightness` gives each LED's brightness from 0 to 9.
+    fn set_image(&mut self, brightness: &[[u8; 5]; 5]) -> Result<(), Refusal> {
+        let _ = brightness;
+        Err(Refusal::Unsupported)
+    }
+
+    /// Returns whether buttons A and B are pressed.
+    fn buttons(&mut self) -> Result<(bool, bool), Refusal> {
+        Err(Refusal::Unsupported)
+    }
+
+    /// Returns a magnetometer reading (x, y, z).
+    fn magnetometer(&mut self) -> Result<(i16, i16, i16), Refusal> {
+        Err(Refusal::Unsupported)
+    }
+
+    /// Returns an accelerometer reading (x, y, z) in milli-g.
+    fn accelerometer(&mut self) -> Result<(i16, i16, i16), Refusal> {
+        Err(Refusal::Unsupported)
+    }
+
+    /// Returns the temperature in units of 0.25°C.
+    fn temperature(&mut self) -> Result<i16, Refusal> {
+        Err(Refusal::Unsupported)
+    }
+
+    /// Drives an edge connector pin high or low.
+    fn set_pin(&mut self, pin: u8, high: bool) -> Result<(), Refusal> {
+        let _ = (pin, high);
+        Err(Refusal::Unsupported)
+    }
+
+    /// Reads the level of an edge connector pin.
+    fn read_pin(&mut self, pin: u8) -> Result<bool, Refusal> {
+        let _ = pin;
+        Err(Refusal::Unsupported)
+    }
+
+    /// Returns a random number.
+    fn random(&mut self) -> Result<u32, Refusal> {
+        Err(Refusal::Unsupported)
+    }
+}
+
+/// Returns the reply to a message, or `None` if it isn't a request.
+///
+/// `Ping` is answered with `Pong` without involving `board`.
+pub fn respond<B: RemoteBoard + ?Sized>(request: &Message, board: &mut B) -> Option<Message> {
+    let request_type = request.message_type();
+    let ack = |result: Result<(), Refusal>| {
+        result.map(|()| Message::Ack {
+            request: request_type,
+        })
+    };
+    let result =
+        match *request {
+            Message::Ping { seq } => Ok(Message::Pong { seq }),
+            Message::SetImage { ref brightness } => ack(board.set_image(brightness)),
+            Message::ReadButtons => board.buttons().map(|(a, b)| Message::Buttons { a, b }),
+            Message::ReadMagnetometer => board
+                .magnetometer()
+                .map(|(x, y, z)| Message::Magnetometer { x, y, z }),
+            Message::ReadAccelerometer => board
+                .accelerometer()
+                .map(|(x, y, z)| Message::Accelerometer { x, y, z }),
+            Message::ReadTemperature => board
+                .temperature()
+                .map(|quarter_degrees| Message::Temperature { quarter_degrees }),
+            Message::SetPin { pin, high } => ack(board.set_pin(pin, high)),
+            Message::ReadPin { pin } => board
+                .read_pin(pin)
+                .map(|high| Message::PinLevel { pin, high }),
+            Message::ReadRandom => board.random().map(|value| Message::Random { value }),
+            _ => return None,
+        };
+    Some(result.unwrap_or_else(|refusal| Message::Nak {
+        request: request_type,
+        reason: refusal.reason(),
+    }))
+}
+
+/// Receives requests from a serial port and replies to them.
+#[derive(Default)]
+pub struct RemoteControl {
+    receiver: MessageReceiver,
+}
+
+impl RemoteControl {
+    /// Returns a new `RemoteControl`.
+    pub const fn new() -> RemoteControl {
+        RemoteControl {
+            receiver: MessageReceiver::new(),
+        }
+    }
+
+    /// Handles all the requests which have arrived, without waiting for
+    /// more.
+    ///
+    /// Frames which can't be decoded are ignored, and a receive error ends
+    /// this poll; either way the host will time out and retry.
+    pub fn poll<R, W, B>(&mut self, rx: &mut R, tx: &mut W, board: &mut B) -> Result<(), W::Error>
+    where
+        R: serial::Read<u8>,
+        W: serial::Write<u8>,
+        B: RemoteBoard + ?Sized,
+    {
+        loop {
+            match self.receiver.receive(rx) {
+                Ok(Ok(request)) => {
+                    if let Some(reply) = respond(&request, board) {
+                        framing::send(tx, &reply)?;
+                    }
+                }
+                Ok(Err(_)) => {}
+                Err(_) => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Supports every request, refusing pins which aren't on the edge
+    /// connector and failing to read the temperature.
+    #[derive(Default)]
+    struct FakeBoard {
+        image: Option<[[u8; 5]; 5]>,
+        pins: u32,
+    }
+
+    impl RemoteBoard for FakeBoard {
+        fn set_image(&mut self, brightness: &[[u8; 5]; 5]) -> Result<(), Refusal> {
+            self.image = Some(*brightness);
+            Ok(())
+        }
+
+        fn buttons(&mut self) -> Result<(bool, bool), Refusal> {
+            Ok((true, false))
+        }
+
+        fn magnetometer(&mut self) -> Result<(i16, i16, i16), Refusal> {
+            Ok((-300, 20, 1))
+        }
+
+        fn accelerometer(&mut self) -> Result<(i16, i16, i16), Refusal> {
+            Ok((0, 0, 1000))
+        }
+
+        fn temperature(&mut self) -> Result<i16, Refusal> {
+            Err(Refusal::Failed)
+        }
+
+        fn set_pin(&mut self, pin: u8, high: bool) -> Result<(), Refusal> {
+            let gpio = edge_pin_to_gpio(pin).ok_or(Refusal::BadArgument)?;
+            if high {
+                self.pins |= 1 << gpio;
+            } else {
+                self.pins &= !(1 << gpio);
+            }
+            Ok(())
+        }
+
+        fn read_pin(&mut self, pin: u8) -> Result<bool, Refusal> {
+            let gpio = edge_pin_to_gpio(pin).ok_or(Refusal::BadArgument)?;
+            Ok(self.pins & (1 << gpio) != 0)
+        }
+
+        fn random(&mut self) -> Result<u32, Refusal> {
+            Ok(0xDEAD_BEEF)
+        }
+    }
+
+    /// Uses the default for every request.
+    struct NoFeatures;
+
+    impl RemoteBoard for NoFeatures {}
+
+    const REQUESTS: [Message; 9] = [
+        Message::Ping { seq: 9 },
+        Message::SetImage {
+            brightness: [[5; 5]; 5],
+        },
+        Message::ReadButtons,
+        Message::ReadMagnetometer,
+        Message::ReadAccelerometer,
+        Message::ReadTemperature,
+        Message::SetPin { pin: 0, high: true },
+        Message::ReadPin { pin: 0 },
+        Message::ReadRandom,
+    ];
+
+    fn nak(request: &Message, reason: u8) -> Option<Message> {
+        Some(Message::Nak {
+            request: request.message_type(),
+            reason,
+        })
+    }
+
+    #[test]
+    fn replies_to_each_request() {
+        let mut board = FakeBoard::default();
+        let replies: Vec<_> = REQUESTS
+            .iter()
+            .map(|request| respond(request, &mut board))
+            .collect();
+        assert_eq!(
+            replies,
+            [
+                Some(Message::Pong { seq: 9 }),
+                Some(Message::Ack { request: 0x20 }),
+                Some(Message::Buttons { a: true, b: false }),
+                Some(Message::Magnetometer {
+                    x: -300,
+                    y: 20,
+                    z: 1
+                }),
+                Some(Message::Accelerometer {
+                    x: 0,
+                    y: 0,
+                    z: 1000
+                }),
+                nak(&Message::ReadTemperature, NAK_FAILED),
+                Some(Message::Ack { request: 0x25 }),
+                Some(Message::PinLevel { pin: 0, high: true }),
+                Some(Message::Random { value: 0xDEAD_BEEF }),
+            ]
+        );
+        assert_eq!(board.image, Some([[5; 5]; 5]));
+        assert_eq!(board.pins, 1 << 3);
+    }
+
+    #[test]
+    fn bad_pins_are_refused() {
+        let mut board = FakeBoard::default();
+        for &pin in &[17, 18, 21, 255] {
+            let request = Message::SetPin { pin, high: true };
+            assert_eq!(
+                respond(&request, &mut board),
+                nak(&request, NAK_BAD_ARGUMENT)
+            );
+            let request = Message::ReadPin { pin };
+            assert_eq!(
+                respond(&request, &mut board),
+                nak(&request, NAK_BAD_ARGUMENT)
+            );
+        }
+        assert_eq!(board.pins, 0);
+    }
+
+    #[test]
+    fn defaults_are_unsupported() {
+        assert_eq!(
+            respond(&REQUESTS[0], &mut NoFeatures),
+            Some(Message::Pong { seq: 9 })
+        );
+        for request in &REQUESTS[1..] {
+            assert_eq!(
+                respond(request, &mut NoFeatures),
+                nak(request, NAK_UNSUPPORTED)
+            );
+        }
+    }
+
+    #[test]
+    fn replies_are_not_answered() {
+        let replies = [
+            Message::Pong { seq: 1 },
+            Message::Ack { request: 0x20 },
+            Message::Nak {
+                request: 0x20,
+                reason: NAK_FAILED,
+            },
+            Message::Random { value: 1 },
+        ];
+        for reply in &replies {
+            assert_eq!(respond(reply, &mut FakeBoard::default()), None);
+        }
+    }
+
+    #[test]
+    fn edge_pins() {
+        assert_eq!(edge_pin_to_gpio(0), Some(3));
+        assert_eq!(edge_pin_to_gpio(1), Some(2));
+        assert_eq!(edge_pin_to_gpio(16), Some(16));
+        assert_eq!(edge_pin_to_gpio(17), None);
+        assert_eq!(edge_pin_to_gpio(18), None);
+        assert_eq!(edge_pin_to_gpio(19), Some(0));
+        assert_eq!(edge_pin_to_gpio(20), Some(30));
+        assert_eq!(edge_pin_to_gpio(21), None);
+        assert_eq!(edge_pin_to_gpio(255), None);
+    }
+}
@@ -0,0 +1,392 @@
+//! Driver for the accelerometer.
+//!
+//! micro:bit v1.3 boards have an NXP MMA8653FC accelerometer (at address
+//! `0x1D` on the internal I2C bus), and most v1.5 boards have an ST
+//! LSM303AGR (whose accelerometer is at address `0x19`). [`Accelerometer`]
+//! drives either, using the settings they have in common:
+//! - ranges of ±2g, ±4g and ±8g (see [`Range`])
+//! - output rates from 1 to 400 readings a second (see [`DataRate`])
+//! - readings in [`MilliG`] (thousandths of the acceleration due to
+//!   gravity).
+//!
+//! [`Accelerometer::new()`] finds out which model the board has (see also
+//! [`board_revision()`]). The MMA8653 driver also accepts the
+//! register-compatible MMA8652.
+//!
+//! Some v1.5 boards have an NXP FXOS8700 (a combined accelerometer and
+//! magnetometer at address `0x1E`) instead of the LSM303AGR. It isn't
+//! supported: on those boards `Accelerometer::new()` returns
+//! `Error::NotFound`.
+//!
+//! Readings are in the sensor's own axes; [`Vector::to_board_axes()`]
+//! converts them into the board's. Lying still, the accelerometer measures
+//! 1g pointing upwards.
+//!
+//! # Data-ready interrupt
+//!
+//! The accelerometer's interrupt output is connected to GPIO pin 28
+//! ([`INTERRUPT_PIN`]). After
+//! [`Accelerometer::set_data_ready_interrupt(true)`][set_data_ready_interrupt],
+//! the pin goes high when a new reading is ready and low again once it has
+//! been read, so a GPIOTE channel listening for `Polarity::LoToHi` on pin 28
+//! gives an event for each reading.
+//!
+//! [`Accelerometer`]: sensors::accelerometer::Accelerometer
+//! [`Accelerometer::new()`]: sensors::accelerometer::Accelerometer::new
+//! [`board_revision()`]: sensors::board_revision
+//! [`Range`]: sensors::accelerometer::Range
+//! [`DataRate`]: sensors::accelerometer::DataRate
+//! [`INTERRUPT_PIN`]: sensors::accelerometer::INTERRUPT_PIN
+//! [set_data_ready_interrupt]: sensors::accelerometer::Accelerometer::set_data_ready_interrupt
+//! [`MilliG`]: units::MilliG
+//! [`Vector::to_board_axes()`]: sensors::Vector::to_board_axes
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+use super::{Error, Vector};
+use crate::units::MilliG;
+
+/// The GPIO pin connected to the accelerometer's interrupt output.
+pub const INTERRUPT_PIN: u8 = 28;
+
+/// Registers of the MMA8653.
+mod mma8653 {
+    pub const ADDRESS: u8 = 0x1D;
+    /// WHO_AM_I values of the MMA8653 and of the MMA8652, which has the
+    /// same registers.
+    pub const DEVICE_IDS: &[u8] = &[0x5A, 0x4A];
+
+    pub const STATUS: u8 = 0x00;
+    pub const OUT_X_MSB: u8 = 0x01;
+    pub const WHO_AM_I: u8 = 0x0D;
+    pub const XYZ_DATA_CFG: u8 = 0x0E;
+    pub const CTRL_REG1: u8 = 0x2A;
+    pub const CTRL_REG3: u8 = 0x2C;
+    pub const CTRL_REG4: u8 = 0x2D;
+    pub const CTRL_REG5: u8 = 0x2E;
+
+    /// STATUS: new data is available on all three axes.
+    pub const STATUS_ZYXDR: u8 = 1 << 3;
+    /// CTRL_REG1: active mode.
+    pub const CTRL_REG1_ACTIVE: u8 = 1 << 0;
+    /// CTRL_REG3: interrupt pins are active high.
+    pub const CTRL_REG3_IPOL: u8 = 1 << 1;
+    /// CTRL_REG4 and CTRL_REG5: the data-ready interrupt (enabled, and
+    /// routed to INT1).
+    pub const DRDY: u8 = 1 << 0;
+}
+
+/// Registers of the LSM303AGR's accelerometer.
+mod lsm303agr {
+    pub const ADDRESS: u8 = 0x19;
+    pub const DEVICE_IDS: &[u8] = &[0x33];
+
+    pub const WHO_AM_I_A: u8 = 0x0F;
+    pub const CTRL_REG1_A: u8 = 0x20;
+    pub const CTRL_REG3_A: u8 = 0x22;
+    pub const CTRL_REG4_A: u8 = 0x23;
+    pub const STATUS_REG_A: u8 = 0x27;
+    pub const OUT_X_L_A: u8 = 0x28;
+
+    /// Register address flag to read several registers in one transfer.
+    pub const AUTO_INCREMENT: u8 = 0x80;
+    /// CTRL_REG1_A: X, Y and Z axes enabled.
+    pub const CTRL_REG1_A_XYZEN: u8 = 0x07;
+    /// CTRL_REG3_A: data-ready interrupt on INT1.
+    pub const CTRL_REG3_A_I1_DRDY1: u8 = 1 << 4;
+    /// CTRL_REG4_A: block data update, high-resolution mode.
+    pub const CTRL_REG4_A_BDU_HR: u8 = 1 << 7 | 1 << 3;
+    /// STATUS_REG_A: new data is available on all three axes.
+    pub const STATUS_REG_A_ZYXDA: u8 = 1 << 3;
+}
+
+/// Which accelerometer the board has.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Model {
+    /// The MMA8653 (or the compatible MMA8652), on micro:bit v1.3.
+    Mma8653,
+    /// The LSM303AGR, on micro:bit v1.5.
+    Lsm303agr,
+}
+
+/// The range of accelerations which can be measured.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Range {
+    /// ±2g, with a resolution of about 1mg (4mg on the MMA8653).
+    G2,
+    /// ±4g, with a resolution of about 2mg (8mg on the MMA8653).
+    G4,
+    /// ±8g, with a resolution of about 4mg (16mg on the MMA8653).
+    G8,
+}
+
+impl Range {
+    /// Returns the largest acceleration in the range, in milli-g.
+    pub fn milli_g(self) -> i32 {
+        match self {
+            Range::G2 => 2000,
+            Range::G4 => 4000,
+            Range::G8 => 8000,
+        }
+    }
+}
+
+/// The number of readings per second.
+///
+/// The two models support slightly different rates; where they differ, the
+/// MMA8653's rate is given.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DataRate {
+    /// 1Hz (1.56Hz on the MMA8653).
+    Hz1,
+    /// 10Hz (12.5Hz on the MMA8653).
+    Hz10,
+    /// 50Hz.
+    Hz50,
+    /// 100Hz.
+    Hz100,
+    /// 200Hz.
+    Hz200,
+    /// 400Hz.
+    Hz400,
+}
+
+/// The accelerometer.
+pub struct Accelerometer<I2C> {
+    i2c: I2C,
+    model: Model,
+    range: Range,
+    rate: DataRate,
+    data_ready_interrupt: bool,
+}
+
+/// Returns which accelerometer answers on the bus, if either does.
+pub(super) fn probe<I2C, E>(i2c: &mut I2C) -> Option<Model>
+where
+    I2C: WriteRead<Error = E>,
+{
+    let mut id = [0];
+    let probes = [
+        (
+            Model::Mma8653,
+            mma8653::ADDRESS,
+            mma8653::WHO_AM_I,
+            mma8653::DEVICE_IDS,
+        ),
+        (
+            Model::Lsm303agr,
+            lsm303agr::ADDRESS,
+            lsm303agr::WHO_AM_I_A,
+            lsm303agr::DEVICE_IDS,
+        ),
+    ];
+    for &(model, address, who_am_i, device_ids) in probes.iter() {
+        // A missing device doesn't acknowledge its address, which is
+        // reported as an error.
+        if i2c.write_read(address, &[who_am_i], &mut id).is_ok() && device_ids.contains(&id[0]) {
+            return Some(model);
+        }
+    }
+    None
+}
+
+impl<I2C, E> Accelerometer<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Finds whichever accelerometer the board has, and starts it taking 50
+    /// readings a second with a range of ±2g.
+    ///
+    /// Returns `Error::NotFound` if neither model answers (as on boards
+    /// with an FXOS8700).
+    pub fn new(mut i2c: I2C) -> Result<Accelerometer<I2C>, Error<E>> {
+        let model = probe(&mut i2c).ok_or(Error::NotFound)?;
+        Accelerometer::new_model(i2c, model)
+    }
+
+    /// Checks that an MMA8653 is present, and starts it taking 50 readings
+    /// a second with a range of ±2g.
+    ///
+    /// Returns `Error::WrongDevice` if something else answers at its
+    /// address.
+    pub fn new_mma8653(i2c: I2C) -> Result<Accelerometer<I2C>, Error<E>> {
+        Accelerometer::new_model(i2c, Model::Mma8653)
+    }
+
+    /// Checks that an LSM303AGR is present, and starts it taking 50
+    /// readings a second with a range of ±2g.
+    ///
+    /// Returns `Error::WrongDevice` if something else answers at its
+    /// address.
+    pub fn new_lsm303agr(i2c: I2C) -> Result<Accelerometer<I2C>, Error<E>> {
+        Accelerometer::new_model(i2c, Model::Lsm303agr)
+    }
+
+    fn new_model(i2c: I2C, model: Model) -> Result<Accelerometer<I2C>, Error<E>> {
+        let mut accelerometer = Accelerometer {
+            i2c,
+            model,
+            range: Range::G2,
+            rate: DataRate::Hz50,
+            data_ready_interrupt: false,
+        };
+        let (who_am_i, device_ids) = match model {
+            Model::Mma8653 => (mma8653::WHO_AM_I, mma8653::DEVICE_IDS),
+            Model::Lsm303agr => (lsm303agr::WHO_AM_I_A, lsm303agr::DEVICE_IDS),
+        };
+        let id = accelerometer.read_register(who_am_i)?;
+        if !device_ids.contains(&id) {
+            return Err(Error::WrongDevice(id));
+        }
+        accelerometer.apply_settings()?;
+        Ok(accelerometer)
+    }
+
+    /// Gives back the I2C bus.
+    ///
+    /// The sensor is left taking readings.
+    pub fn free(self) -> I2C {
+        self.i2c
+    }
+
+    /// Returns which accelerometer this is.
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Returns the current range.
+    pub fn range(&self) -> Range {
+        self.range
+    }
+
+    /// Sets the range and the output rate.
+    pub fn configure(&mut self, range: Range, rate: DataRate) -> Result<(), E> {
+        self.range = range;
+        self.rate = rate;
+        self.apply_settings()
+    }
+
+    /// Enables or disables the data-ready interrupt on [`INTERRUPT_PIN`].
+    ///
+    /// [`INTERRUPT_PIN`]: sensors::accelerometer::INTERRUPT_PIN
+    pub fn set_data_ready_interrupt(&mut self, enabled: bool) -> Result<(), E> {
+        self.data_ready_interrupt = enabled;
+        self.apply_settings()
+    }
+
+    /// Returns whether there's a reading which hasn't been read yet.
+    pub fn data_ready(&mut self) -> Result<bool, E> {
+        Ok(match self.model {
+            Model::Mma8653 => self.read_register(mma8653::STATUS)? & mma8653::STATUS_ZYXDR != 0,
+            Model::Lsm303agr => {
+                self.read_register(lsm303agr::STATUS_REG_A)? & lsm303agr::STATUS_REG_A_ZYXDA != 0
+            }
+        })
+    }
+
+    /// Returns the latest reading.
+    pub fn read(&mut self) -> Result<Vector<MilliG>, E> {
+        let mut data = [0; 6];
+        // Both models give left-justified values, so full scale is ±32768.
+        let raw = match self.model {
+            Model::Mma8653 => {
+                self.i2c
+                    .write_read(mma8653::ADDRESS, &[mma8653::OUT_X_MSB], &mut data)?;
+                Vector::new(
+                    i16::from_be_bytes([data[0], data[1]]),
+                    i16::from_be_bytes([data[2], data[3]]),
+                    i16::from_be_bytes([data[4], data[5]]),
+                )
+            }
+            Model::Lsm303agr => {
+                self.i2c.write_read(
+                    lsm303agr::ADDRESS,
+                    &[lsm303agr::OUT_X_L_A | lsm303agr::AUTO_INCREMENT],
+                    &mut data,
+                )?;
+                Vector::new(
+                    i16::from_le_bytes([data[0], data[1]]),
+                    i16::from_le_bytes([data[2], data[3]]),
+                    i16::from_le_bytes([data[4], data[5]]),
+                )
+            }
+        };
+        let full_scale = self.range.milli_g();
+        Ok(raw.map(|value| MilliG((i32::from(value) * full_scale / 32768) as i16)))
+    }
+
+    /// Writes the range, rate and interrupt settings to the sensor.
+    fn apply_settings(&mut self) -> Result<(), E> {
+        match self.model {
+            Model::Mma8653 => {
+                use self::mma8653::*;
+
+                let fs = match self.range {
+                    Range::G2 => 0b00,
+                    Range::G4 => 0b01,
+                    Range::G8 => 0b10,
+                };
+                let dr = match self.rate {
+                    DataRate::Hz1 => 7,
+                    DataRate::Hz10 => 5,
+                    DataRate::Hz50 => 4,
+                    DataRate::Hz100 => 3,
+                    DataRate::Hz200 => 2,
+                    DataRate::Hz400 => 1,
+                };
+                let drdy = if self.data_ready_interrupt { DRDY } else { 0 };
+                // The settings can only be changed in standby mode.
+                self.write_register(CTRL_REG1, 0)?;
+                self.write_register(XYZ_DATA_CFG, fs)?;
+                self.write_register(CTRL_REG3, CTRL_REG3_IPOL)?;
+                self.write_register(CTRL_REG4, drdy)?;
+                self.write_register(CTRL_REG5, drdy)?;
+                self.write_register(CTRL_REG1, dr << 3 | CTRL_REG1_ACTIVE)
+            }
+            Model::Lsm303agr => {
+                use self::lsm303agr::*;
+
+                let fs = match self.range {
+                    Range::G2 => 0b00,
+                    Range::G4 => 0b01,
+                    Range::G8 => 0b10,
+                };
+                let odr = match self.rate {
+                    DataRate::Hz1 => 1,
+                    DataRate::Hz10 => 2,
+                    DataRate::Hz50 => 4,
+                    DataRate::Hz100 => 5,
+                    DataRate::Hz200 => 6,
+                    DataRate::Hz400 => 7,
+                };
+                let drdy = if self.data_ready_interrupt {
+                    CTRL_REG3_A_I1_DRDY1
+                } else {
+                    0
+                };
+                self.write_register(CTRL_REG4_A, CTRL_REG4_A_BDU_HR | fs << 4)?;
+                self.write_register(CTRL_REG3_A, drdy)?;
+                self.write_register(CTRL_REG1_A, odr << 4 | CTRL_REG1_A_XYZEN)
+            }
+        }
+    }
+
+    fn address(&self) -> u8 {
+        match self.model {
+            Model::Mma8653 => mma8653::ADDRESS,
+            Model::Lsm303agr => lsm303agr::ADDRESS,
+        }
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, E> {
+        let mut value = [0];
+        let address = self.address();
+        self.i2c.write_read(address, &[register], &mut value)?;
+        Ok(value[0])
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), E> {
+        let address = self.address();
+        self.i2c.write(address, &[register, value])
+    }
+}
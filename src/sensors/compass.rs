@@ -0,0 +1,577 @@
+//! Compass calibration, guided by the display, and tilt-compensated
+//! headings.
+//!
+//! Before the magnetometer can be used as a compass it needs calibrating
+//! (see the [`magnetometer`] module). Like the micro:bit runtime,
+//! [`CompassCalibrator`] asks the user to tilt the board to "fill the
+//! screen": a dot on the display follows the board's tilt like a ball
+//! rolling on it, and each LED the dot visits stays lit. Each time the dot
+//! reaches an LED for the first time, the magnetometer reading is kept, so
+//! once the screen is full there are readings from the board facing in
+//! 25 different directions.
+//!
+//! [`fit_ellipsoid()`] then works out the hard-iron offset and soft-iron
+//! scale factors which turn those readings into points on a sphere.
+//!
+//! # Heading
+//!
+//! [`Magnetometer::heading()`] is only right while the board is level,
+//! because the Earth's field points steeply into the ground (by about 65°
+//! in Europe) and tilting the board mixes that into the horizontal
+//! readings. [`tilt_compensated_heading()`] uses an accelerometer reading to
+//! find which way is down, and removes it. It's accurate to within about
+//! half a degree (given accurate readings) at any tilt, except close to
+//! vertical.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut calibrator = CompassCalibrator::new();
+//! while !calibrator.is_complete() {
+//!     let tilt = accelerometer.read()?.to_board_axes(revision, Sensor::Accelerometer);
+//!     calibrator.update(tilt, magnetometer.read_raw()?);
+//!     show(calibrator.image());
+//! }
+//! if let Some(calibration) = calibrator.calibration() {
+//!     magnetometer.set_calibration(calibration);
+//! }
+//! ```
+//!
+//! [`magnetometer`]: sensors::magnetometer
+//! [`CompassCalibrator`]: sensors::compass::CompassCalibrator
+//! [`fit_ellipsoid()`]: sensors::compass::fit_ellipsoid
+//! [`Magnetometer::heading()`]: sensors::magnetometer::Magnetometer::heading
+//! [`tilt_compensated_heading()`]: sensors::compass::tilt_compensated_heading
+
+use super::magnetometer::Calibration;
+use super::{atan2_degrees, sqrt, Vector};
+use crate::display::image::GreyscaleImage;
+use crate::units::{MicroTesla, MilliG};
+
+/// How far (in milli-g) the board must be tilted to move the dot by one
+/// LED.
+const TILT_PER_LED_MG: i32 = 400;
+
+/// Brightness of LEDs which have been visited.
+const VISITED_BRIGHTNESS: u8 = 4;
+
+/// Brightness of the dot.
+const DOT_BRIGHTNESS: u8 = 9;
+
+/// Guides the user through calibrating the compass, using the display.
+///
+/// See the [module-level documentation](sensors::compass) for details.
+#[derive(Copy, Clone, Debug)]
+pub struct CompassCalibrator {
+    /// The magnetometer reading kept for each LED, a row at a time.
+    samples: [Option<Vector<i16>>; 25],
+    dot: (usize, usize),
+}
+
+impl CompassCalibrator {
+    /// Returns a `CompassCalibrator` with an empty screen.
+    pub const fn new() -> CompassCalibrator {
+        CompassCalibrator {
+            samples: [None; 25],
+            dot: (2, 2),
+        }
+    }
+
+    /// Takes an accelerometer reading and a raw magnetometer reading taken
+    /// at the same time.
+    ///
+    /// The accelerometer reading must be converted into the board's axes
+    /// with [`Vector::to_board_axes()`], so that the dot rolls the right
+    /// way. The magnetometer reading stays in the sensor's own axes, as the
+    /// calibration is applied to those.
+    ///
+    /// [`Vector::to_board_axes()`]: sensors::Vector::to_board_axes
+    pub fn update(&mut self, tilt: Vector<MilliG>, field: Vector<i16>) {
+        // The dot rolls towards the lowest edge: with the left edge lowered x
+        // is positive, and with the top edge lowered y is negative.
+        let x = 2 - leds_for_tilt(tilt.x.0);
+        let y = 2 + leds_for_tilt(tilt.y.0);
+        self.dot = (x as usize, y as usize);
+        let sample = &mut self.samples[y as usize * 5 + x as usize];
+        if sample.is_none() {
+            *sample = Some(field);
+        }
+    }
+
+    /// Returns the number of LEDs which have been visited.
+    pub fn progress(&self) -> usize {
+        self.samples
+            .iter()
+            .filter(|sample| sample.is_some())
+            .count()
+    }
+
+    /// Returns whether every LED has been visited.
+    pub fn is_complete(&self) -> bool {
+        self.progress() == self.samples.len()
+    }
+
+    /// Returns the image to show: the visited LEDs, and the dot.
+    pub fn image(&self) -> GreyscaleImage {
+        let mut image = GreyscaleImage::blank();
+        for (i, sample) in self.samples.iter().enumerate() {
+            if sample.is_some() {
+                image.set_brightness(i % 5, i / 5, VISITED_BRIGHTNESS);
+            }
+        }
+        image.set_brightness(self.dot.0, self.dot.1, DOT_BRIGHTNESS);
+        image
+    }
+
+    /// Returns the calibration from the readings kept so far.
+    ///
+    /// Returns `None` if they don't give a usable fit (which is likely
+    /// until the screen has been filled).
+    pub fn calibration(&self) -> Option<Calibration> {
+        let mut samples = [Vector::default(); 25];
+        let mut count = 0;
+        for sample in self.samples.iter().flatten() {
+            samples[count] = *sample;
+            count += 1;
+        }
+        fit_ellipsoid(&samples[..count])
+    }
+}
+
+impl Default for CompassCalibrator {
+    fn default() -> CompassCalibrator {
+        CompassCalibrator::new()
+    }
+}
+
+/// Returns how many LEDs (-2 to 2) a tilt moves the dot by.
+fn leds_for_tilt(milli_g: i16) -> i32 {
+    let milli_g = i32::from(milli_g);
+    let rounded = if milli_g < 0 {
+        milli_g - TILT_PER_LED_MG / 2
+    } else {
+        milli_g + TILT_PER_LED_MG / 2
+    };
+    (rounded / TILT_PER_LED_MG).clamp(-2, 2)
+}
+
+/// Works out a calibration from raw magnetometer readings taken with the
+/// board facing in many different directions.
+///
+/// In a uniform field, the readings should lie on a sphere centred on zero.
+/// Hard-iron distortion moves the centre, and soft-iron distortion stretches
+/// the sphere into an ellipsoid. This fits an ellipsoid (with its axes
+/// along the sensor's axes) to the readings, and returns the calibration
+/// which moves it back to zero and scales it back into a sphere.
+///
+/// Returns `None` if there are fewer than six readings, or if they don't
+/// lie on an ellipsoid (for example if the board was only turned about one
+/// axis).
+pub fn fit_ellipsoid(samples: &[Vector<i16>]) -> Option<Calibration> {
+    if samples.len() < 6 {
+        return None;
+    }
+
+    // Work relative to the middle of the readings' range, scaled to about
+    // ±1, so that the sums below keep their precision.
+    let mut min = Vector::new(i16::MAX, i16::MAX, i16::MAX);
+    let mut max = Vector::new(i16::MIN, i16::MIN, i16::MIN);
+    for sample in samples {
+        min = Vector::new(
+            min.x.min(sample.x),
+            min.y.min(sample.y),
+            min.z.min(sample.z),
+        );
+        max = Vector::new(
+            max.x.max(sample.x),
+            max.y.max(sample.y),
+            max.z.max(sample.z),
+        );
+    }
+    let middle = |min: i16, max: i16| (i32::from(min) + i32::from(max)) as f32 / 2.0;
+    let centre = Vector::new(
+        middle(min.x, max.x),
+        middle(min.y, max.y),
+        middle(min.z, max.z),
+    );
+    let spread = (i32::from(max.x) - i32::from(min.x))
+        .max(i32::from(max.y) - i32::from(min.y))
+        .max(i32::from(max.z) - i32::from(min.z));
+    if spread == 0 {
+        return None;
+    }
+    let unit = spread as f32 / 2.0;
+
+    // Fit a x² + b y² + c z² + d x + e y + f z = 1 by least squares, using
+    // the normal equations (with the right-hand side in the last column).
+    let mut equations = [[0.0f32; 7]; 6];
+    for sample in samples {
+        let x = (f32::from(sample.x) - centre.x) / unit;
+        let y = (f32::from(sample.y) - centre.y) / unit;
+        let z = (f32::from(sample.z) - centre.z) / unit;
+        let terms = [x * x, y * y, z * z, x, y, z, 1.0];
+        for (row, &term) in equations.iter_mut().zip(terms.iter()) {
+            for (sum, &other) in row.iter_mut().zip(terms.iter()) {
+                *sum += term * other;
+            }
+        }
+    }
+    let [a, b, c, d, e, f] = solve(equations)?;
+    if a <= 0.0 || b <= 0.0 || c <= 0.0 {
+        return None;
+    }
+
+    // Complete the squares: a (x - x0)² + ... = g.
+    let g = 1.0 + d * d / (4.0 * a) + e * e / (4.0 * b) + f * f / (4.0 * c);
+    let radii = Vector::new(sqrt(g / a), sqrt(g / b), sqrt(g / c));
+    let mean_radius = (radii.x + radii.y + radii.z) / 3.0;
+    let offset = |centre: f32, linear: f32, square: f32| {
+        let offset = centre - linear / (2.0 * square) * unit;
+        if offset < 0.0 {
+            (offset - 0.5) as i16
+        } else {
+            (offset + 0.5) as i16
+        }
+    };
+    Some(Calibration {
+        offset: Vector::new(
+            offset(centre.x, d, a),
+            offset(centre.y, e, b),
+            offset(centre.z, f, c),
+        ),
+        scale: radii.map(|radius| mean_radius / radius),
+    })
+}
+
+/// Returns the heading of the board (in degrees clockwise from magnetic
+/// north, 0 to 360) at any tilt.
+///
+/// `acceleration` is an accelerometer reading and `field` a calibrated
+/// magnetometer reading, both converted into the board's axes with
+/// [`Vector::to_board_axes()`]. The heading is the
+/// direction the y axis would point if the board were tilted back to level
+/// without turning it. (If the y axis is pointing straight up or down, that
+/// isn't well defined.)
+///
+/// Returns `None` if the acceleration is zero (because the board is
+/// falling) or the field is vertical.
+///
+/// [`Vector::to_board_axes()`]: sensors::Vector::to_board_axes
+pub fn tilt_compensated_heading(
+    acceleration: Vector<MilliG>,
+    field: Vector<MicroTesla>,
+) -> Option<f32> {
+    // The accelerometer measures the upward reaction to gravity.
+    let down = acceleration.map(|value| -f32::from(value.0));
+    let length = sqrt(dot(down, down));
+    if length == 0.0 {
+        return None;
+    }
+    let down = down.map(|value| value / length);
+
+    // East is at right angles to both down and the field, and north is at
+    // right angles to east and down. These are in the board's axes, so their
+    // y components say how far the y axis points east and north.
+    let east = cross(down, field.map(f32::from));
+    let north = cross(east, down);
+    if east.y == 0.0 && north.y == 0.0 {
+        return None;
+    }
+    let degrees = atan2_degrees(east.y, north.y);
+    Some(if degrees < 0.0 {
+        degrees + 360.0
+    } else {
+        degrees
+    })
+}
+
+fn dot(a: Vector<f32>, b: Vector<f32>) -> f32 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+fn cross(a: Vector<f32>, b: Vector<f32>) -> Vector<f32> {
+    Vector::new(
+        a.y * b.z - a.z * b.y,
+        a.z * b.x - a.x * b.z,
+        a.x * b.y - a.y * b.x,
+    )
+}
+
+/// Solves six linear equations by Gaussian elimination.
+///
+/// Each row holds the coefficients followed by the right-hand side. Returns
+/// `None` if the equations don't have a single solution.
+fn solve(mut rows: [[f32; 7]; 6]) -> Option<[f32; 6]> {
+    let abs = |value: f32| if value < 0.0 { -value } else { value };
+    for column in 0..6 {
+        // Use the row with the largest coefficient, for accuracy.
+        let pivot = (column..6).max_by(|&i, &j| {
+            abs(rows[i][column])
+                .partial_cmp(&abs(rows[j][column]))
+                .unwrap_or(core::cmp::Ordering::Equal)
+        })?;
+        if abs(rows[pivot][column]) < 1e-6 {
+            return None;
+        }
+        rows.swap(column, pivot);
+        let (upper, lower) = rows.split_at_mut(column + 1);
+        let pivot_row = &upper[column];
+        for row in lower.iter_mut() {
+            let factor = row[column] / pivot_row[column];
+            for (value, &subtract) in row.iter_mut().zip(pivot_row.iter()).skip(column) {
+                *value -= factor * subtract;
+            }
+        }
+    }
+    let mut solution = [0.0; 6];
+    for row in (0..6).rev() {
+        let mut value = rows[row][6];
+        for k in row + 1..6 {
+            value -= rows[row][k] * solution[k];
+        }
+        solution[row] = value / rows[row][row];
+    }
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Render;
+
+    /// Turns a vector from the board's axes into the world's (east, north,
+    /// up), for a board with the given heading, pitch (about its x axis)
+    /// and roll (about its y axis), in degrees.
+    fn to_world(v: Vector<f32>, heading: f32, pitch: f32, roll: f32) -> Vector<f32> {
+        let (sin_r, cos_r) = roll.to_radians().sin_cos();
+        let v = Vector::new(v.x * cos_r + v.z * sin_r, v.y, -v.x * sin_r + v.z * cos_r);
+        let (sin_p, cos_p) = pitch.to_radians().sin_cos();
+        let v = Vector::new(v.x, v.y * cos_p - v.z * sin_p, v.y * sin_p + v.z * cos_p);
+        // Turning clockwise (seen from above) by the heading.
+        let (sin_h, cos_h) = heading.to_radians().sin_cos();
+        Vector::new(v.x * cos_h + v.y * sin_h, -v.x * sin_h + v.y * cos_h, v.z)
+    }
+
+    /// The reverse of `to_world()`.
+    fn to_board(v: Vector<f32>, heading: f32, pitch: f32, roll: f32) -> Vector<f32> {
+        let unit = |axis| to_world(axis, heading, pitch, roll);
+        let axes = [
+            unit(Vector::new(1.0, 0.0, 0.0)),
+            unit(Vector::new(0.0, 1.0, 0.0)),
+            unit(Vector::new(0.0, 0.0, 1.0)),
+        ];
+        Vector::new(dot(axes[0], v), dot(axes[1], v), dot(axes[2], v))
+    }
+
+    /// Returns the readings for a board in a field of 50µT pointing north
+    /// and down at the given inclination.
+    fn readings(
+        heading: f32,
+        pitch: f32,
+        roll: f32,
+        inclination: f32,
+    ) -> (Vector<MilliG>, Vector<MicroTesla>) {
+        let (sin_i, cos_i) = inclination.to_radians().sin_cos();
+        let field = Vector::new(0.0, 50.0 * cos_i, -50.0 * sin_i);
+        let up = Vector::new(0.0, 0.0, 1000.0);
+        (
+            to_board(up, heading, pitch, roll).map(|value| MilliG(value.round() as i16)),
+            to_board(field, heading, pitch, roll).map(MicroTesla),
+        )
+    }
+
+    #[test]
+    fn heading_when_level() {
+        let (acceleration, field) = readings(90.0, 0.0, 0.0, 65.0);
+        let heading = tilt_compensated_heading(acceleration, field).unwrap();
+        assert!((heading - 90.0).abs() < 0.5, "{}", heading);
+    }
+
+    #[test]
+    fn heading_at_any_tilt() {
+        for &inclination in &[0.0, 30.0, 65.0, 80.0] {
+            for &pitch in &[-60.0, -30.0, 0.0, 30.0, 60.0] {
+                for &roll in &[-75.0, -30.0, 0.0, 45.0, 75.0] {
+                    for step in 0..72 {
+                        let expected = step as f32 * 5.0;
+                        let (acceleration, field) = readings(expected, pitch, roll, inclination);
+                        let heading = tilt_compensated_heading(acceleration, field).unwrap();
+                        assert!((0.0..360.0).contains(&heading));
+                        let mut error = heading - expected;
+                        if error > 180.0 {
+                            error -= 360.0;
+                        } else if error < -180.0 {
+                            error += 360.0;
+                        }
+                        assert!(
+                            error.abs() < 0.5,
+                            "heading {} pitch {} roll {} inclination {}: got {}",
+                            expected,
+                            pitch,
+                            roll,
+                            inclination,
+                            heading
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_heading_without_acceleration() {
+        let (_, field) = readings(0.0, 0.0, 0.0, 65.0);
+        assert_eq!(tilt_compensated_heading(Vector::default(), field), None);
+    }
+
+    #[test]
+    fn no_heading_in_a_vertical_field() {
+        let acceleration = Vector::new(MilliG(0), MilliG(0), MilliG(1000));
+        let field = Vector::new(MicroTesla(0.0), MicroTesla(0.0), MicroTesla(-50.0));
+        assert_eq!(tilt_compensated_heading(acceleration, field), None);
+        let acceleration = Vector::new(MilliG(600), MilliG(0), MilliG(800));
+        let field = Vector::new(MicroTesla(-30.0), MicroTesla(0.0), MicroTesla(-40.0));
+        assert_eq!(tilt_compensated_heading(acceleration, field), None);
+    }
+
+    /// Returns raw readings on an ellipsoid with the given centre and radii,
+    /// in directions spread over the whole sphere.
+    fn ellipsoid(centre: Vector<f32>, radii: Vector<f32>) -> Vec<Vector<i16>> {
+        let mut samples = Vec::new();
+        for &latitude in &[-90.0f32, -60.0, -30.0, 0.0, 30.0, 60.0, 90.0] {
+            for step in 0..8 {
+                let (sin_lat, cos_lat) = latitude.to_radians().sin_cos();
+                let (sin_long, cos_long) = (step as f32 * 45.0).to_radians().sin_cos();
+                samples.push(Vector::new(
+                    (centre.x + radii.x * cos_lat * cos_long).round() as i16,
+                    (centre.y + radii.y * cos_lat * sin_long).round() as i16,
+                    (centre.z + radii.z * sin_lat).round() as i16,
+                ));
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn fit_recovers_offset_and_scale() {
+        let radii = Vector::new(500.0, 400.0, 450.0);
+        let samples = ellipsoid(Vector::new(300.0, -200.0, 150.0), radii);
+        let Calibration { offset, scale } = fit_ellipsoid(&samples).unwrap();
+        let close = |value: i16, expected: i16| (value - expected).abs() <= 2;
+        assert!(
+            close(offset.x, 300) && close(offset.y, -200) && close(offset.z, 150),
+            "{:?}",
+            offset
+        );
+        // Scaling should turn the ellipsoid into a sphere of the mean radius.
+        for &scaled in &[radii.x * scale.x, radii.y * scale.y, radii.z * scale.z] {
+            assert!((scaled - 450.0).abs() < 4.0, "{:?}", scale);
+        }
+    }
+
+    #[test]
+    fn fit_of_a_sphere_has_unit_scale() {
+        let samples = ellipsoid(
+            Vector::new(-50.0, 0.0, 80.0),
+            Vector::new(400.0, 400.0, 400.0),
+        );
+        let Calibration { offset, scale } = fit_ellipsoid(&samples).unwrap();
+        assert_eq!((offset.x, offset.y, offset.z), (-50, 0, 80));
+        for &factor in &[scale.x, scale.y, scale.z] {
+            assert!((factor - 1.0).abs() < 0.01, "{:?}", scale);
+        }
+    }
+
+    #[test]
+    fn fit_needs_six_readings() {
+        let samples = ellipsoid(Vector::new(0.0, 0.0, 0.0), Vector::new(400.0, 400.0, 400.0));
+        assert_eq!(fit_ellipsoid(&samples[..5]), None);
+        assert_eq!(fit_ellipsoid(&[]), None);
+    }
+
+    #[test]
+    fn fit_fails_for_a_single_rotation() {
+        // Turning the board round on a table only sweeps out a circle.
+        let samples: Vec<_> = (0..24)
+            .map(|step| {
+                let (sin, cos) = (step as f32 * 15.0).to_radians().sin_cos();
+                Vector::new((400.0 * cos) as i16, (400.0 * sin) as i16, -300)
+            })
+            .collect();
+        assert_eq!(fit_ellipsoid(&samples), None);
+    }
+
+    #[test]
+    fn fit_fails_without_spread() {
+        assert_eq!(fit_ellipsoid(&[Vector::new(120, -40, 300); 25]), None);
+    }
+
+    #[test]
+    fn solve_finds_the_solution() {
+        let expected = [1.0, -2.0, 0.5, 3.0, 0.0, -1.5];
+        let mut rows = [[0.0f32; 7]; 6];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().take(6).enumerate() {
+                *value = if i == j {
+                    10.0
+                } else {
+                    (i * 6 + j) as f32 % 5.0 - 2.0
+                };
+            }
+        }
+        // A zero on the diagonal needs a row swap.
+        rows[0][0] = 0.0;
+        for row in rows.iter_mut() {
+            row[6] = (0..6).map(|j| row[j] * expected[j]).sum();
+        }
+        let solution = solve(rows).unwrap();
+        for (value, expected) in solution.iter().zip(expected.iter()) {
+            assert!((value - expected).abs() < 1e-4, "{:?}", solution);
+        }
+    }
+
+    #[test]
+    fn solve_fails_for_singular_equations() {
+        let mut rows = [[0.0f32; 7]; 6];
+        for (i, row) in rows.iter_mut().enumerate() {
+            row[i] = 1.0;
+            row[6] = 1.0;
+        }
+        // Make the last equation a copy of the first.
+        rows[5] = rows[0];
+        assert_eq!(solve(rows), None);
+    }
+
+    #[test]
+    fn calibrator_dot_rolls_downhill() {
+        let mut calibrator = CompassCalibrator::new();
+        let tilt = |x, y| Vector::new(MilliG(x), MilliG(y), MilliG(900));
+        // With the left edge lowered, the dot rolls to the left column.
+        calibrator.update(tilt(900, 0), Vector::default());
+        assert_eq!(calibrator.image().brightness_at(0, 2), DOT_BRIGHTNESS);
+        // With the top edge lowered, it rolls to the top row.
+        calibrator.update(tilt(0, -900), Vector::default());
+        assert_eq!(calibrator.image().brightness_at(2, 0), DOT_BRIGHTNESS);
+        assert_eq!(calibrator.image().brightness_at(0, 2), VISITED_BRIGHTNESS);
+        assert_eq!(calibrator.progress(), 2);
+    }
+
+    #[test]
+    fn calibrator_fills_the_screen() {
+        let samples = ellipsoid(
+            Vector::new(300.0, -200.0, 150.0),
+            Vector::new(500.0, 400.0, 450.0),
+        );
+        let mut calibrator = CompassCalibrator::new();
+        assert_eq!(calibrator.calibration(), None);
+        let mut fields = samples.iter().cycle();
+        for y in -2..=2 {
+            for x in -2..=2 {
+                let tilt = Vector::new(MilliG(x * 400), MilliG(y * 400), MilliG(800));
+                calibrator.update(tilt, *fields.next().unwrap());
+            }
+        }
+        assert!(calibrator.is_complete());
+        assert!(calibrator.calibration().is_some());
+    }
+}
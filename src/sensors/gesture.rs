@@ -0,0 +1,662 @@
+//! Recognising gestures from accelerometer readings.
+//!
+//! [`GestureRecogniser`] turns a stream of readings into the gestures
+//! MicroPython's `accelerometer.current_gesture()` reports:
+//! - the board's posture: [`FaceUp`], [`FaceDown`], [`TiltLeft`],
+//!   [`TiltRight`], [`TiltForward`] and [`TiltBack`]
+//! - [`FreeFall`], while the board is falling
+//! - [`Shake`], while the board is being shaken
+//! - [`G3`], [`G6`] and [`G8`], for impacts of more than 3g, 6g and 8g.
+//!
+//! A posture only becomes current once it has been seen in several
+//! readings in a row, and the current posture is kept until the readings
+//! move a little way past its thresholds (see [`Thresholds`]), so readings
+//! near a threshold don't produce a stream of events. Impacts are reported
+//! straight away, and not again until the readings drop back below 3g.
+//!
+//! # Readings
+//!
+//! Each call to [`update()`] passes a reading in milli-g (see
+//! [`Accelerometer::read()`]). The readings are expected at a steady rate of
+//! around 50 a second; the thresholds which count readings assume that rate.
+//!
+//! The recogniser uses the board's axes: x towards button B, y towards the
+//! edge with the USB connector, and z out of the front of the board (so
+//! lying still face up, the reading is (0, 0, 1000)). Convert the
+//! accelerometer's readings with [`Vector::to_board_axes()`] first.
+//!
+//! The recogniser doesn't touch the hardware, so it can be driven with
+//! recorded readings (for example on the host).
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut gestures = GestureRecogniser::new(Thresholds::default());
+//!
+//! // 50 times a second:
+//! let revision = BoardRevision::from(accelerometer.model());
+//! gestures.update(accelerometer.read()?.to_board_axes(revision, Sensor::Accelerometer));
+//! while let Some(gesture) = gestures.next_event() {
+//!     // ...
+//! }
+//! ```
+//!
+//! [`GestureRecogniser`]: sensors::gesture::GestureRecogniser
+//! [`FaceUp`]: sensors::gesture::Gesture::FaceUp
+//! [`FaceDown`]: sensors::gesture::Gesture::FaceDown
+//! [`TiltLeft`]: sensors::gesture::Gesture::TiltLeft
+//! [`TiltRight`]: sensors::gesture::Gesture::TiltRight
+//! [`TiltForward`]: sensors::gesture::Gesture::TiltForward
+//! [`TiltBack`]: sensors::gesture::Gesture::TiltBack
+//! [`FreeFall`]: sensors::gesture::Gesture::FreeFall
+//! [`Shake`]: sensors::gesture::Gesture::Shake
+//! [`G3`]: sensors::gesture::Gesture::G3
+//! [`G6`]: sensors::gesture::Gesture::G6
+//! [`G8`]: sensors::gesture::Gesture::G8
+//! [`Thresholds`]: sensors::gesture::Thresholds
+//! [`update()`]: sensors::gesture::GestureRecogniser::update
+//! [`Accelerometer::read()`]: sensors::accelerometer::Accelerometer::read
+//! [`Vector::to_board_axes()`]: sensors::Vector::to_board_axes
+
+use super::Vector;
+use crate::units::MilliG;
+
+/// Number of gestures which can be waiting to be taken by
+/// [`next_event()`](GestureRecogniser::next_event).
+const EVENT_QUEUE_LEN: usize = 8;
+
+/// A gesture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Gesture {
+    /// Lying with the display facing up.
+    FaceUp,
+    /// Lying with the display facing down.
+    FaceDown,
+    /// Tilted with the edge by button A lowered.
+    TiltLeft,
+    /// Tilted with the edge by button B lowered.
+    TiltRight,
+    /// Tilted with the edge with the USB connector lowered.
+    TiltForward,
+    /// Tilted with the edge with the USB connector raised.
+    TiltBack,
+    /// Falling.
+    FreeFall,
+    /// Being shaken.
+    Shake,
+    /// An impact of more than 3g.
+    G3,
+    /// An impact of more than 6g.
+    G6,
+    /// An impact of more than 8g.
+    G8,
+}
+
+/// Thresholds for [`GestureRecogniser`], in milli-g or numbers of readings.
+///
+/// [`GestureRecogniser`]: sensors::gesture::GestureRecogniser
+#[derive(Copy, Clone, Debug)]
+pub struct Thresholds {
+    /// How far the reading on the z axis may be from 1g for the board to
+    /// count as face up or face down.
+    pub face_mg: i32,
+    /// How large the reading on the x or y axis must be for the board to
+    /// count as tilted.
+    pub tilt_mg: i32,
+    /// How far past the thresholds above the readings must move before the
+    /// current posture is given up.
+    pub hysteresis_mg: i32,
+    /// The largest total acceleration which counts as falling.
+    pub freefall_mg: i32,
+    /// How large the reading on an axis must be to count towards a shake.
+    pub shake_mg: i32,
+    /// How many changes of direction make a shake.
+    pub shake_count: u8,
+    /// How many readings without a change of direction end a shake.
+    pub shake_timeout: u8,
+    /// How many readings in a row a posture must be seen in before it
+    /// becomes current.
+    pub settle: u8,
+}
+
+impl Default for Thresholds {
+    /// Based on the thresholds used by the micro:bit runtime, for 50
+    /// readings a second.
+    fn default() -> Thresholds {
+        Thresholds {
+            face_mg: 200,
+            tilt_mg: 200,
+            hysteresis_mg: 50,
+            freefall_mg: 400,
+            shake_mg: 400,
+            shake_count: 4,
+            shake_timeout: 25,
+            settle: 5,
+        }
+    }
+}
+
+/// The total acceleration thresholds for impacts, with their gestures.
+const IMPACTS: [(i32, Gesture); 3] = [
+    (8000, Gesture::G8),
+    (6000, Gesture::G6),
+    (3000, Gesture::G3),
+];
+
+/// Tracks changes of direction for shake detection.
+#[derive(Copy, Clone, Debug)]
+struct ShakeDetector {
+    /// The direction of the last large reading on each axis (`true` for
+    /// positive).
+    directions: [Option<bool>; 3],
+    count: u8,
+    quiet_readings: u8,
+    shaking: bool,
+}
+
+impl ShakeDetector {
+    const fn new() -> ShakeDetector {
+        ShakeDetector {
+            directions: [None; 3],
+            count: 0,
+            quiet_readings: 0,
+            shaking: false,
+        }
+    }
+
+    /// Takes a new reading, and returns whether the board is being shaken.
+    fn update(&mut self, reading: Vector<i32>, thresholds: &Thresholds) -> bool {
+        let mut changed = false;
+        for (direction, &value) in self
+            .directions
+            .iter_mut()
+            .zip([reading.x, reading.y, reading.z].iter())
+        {
+            if value > thresholds.shake_mg || value < -thresholds.shake_mg {
+                let positive = value > 0;
+                if *direction == Some(!positive) {
+                    changed = true;
+                }
+                *direction = Some(positive);
+            }
+        }
+        if changed {
+            self.count = self.count.saturating_add(1);
+            self.quiet_readings = 0;
+        } else {
+            self.quiet_readings = self.quiet_readings.saturating_add(1);
+            if self.quiet_readings >= thresholds.shake_timeout {
+                self.count = 0;
+                self.shaking = false;
+            }
+        }
+        if self.count >= thresholds.shake_count {
+            self.shaking = true;
+        }
+        self.shaking
+    }
+}
+
+/// Works out the gestures from a stream of readings.
+#[derive(Copy, Clone, Debug)]
+struct Detector {
+    current: Option<Gesture>,
+    candidate: Option<Gesture>,
+    candidate_readings: u8,
+    /// The largest impact reported since the readings were last below 3g.
+    impact: Option<Gesture>,
+    shake: ShakeDetector,
+}
+
+impl Detector {
+    const fn new() -> Detector {
+        Detector {
+            current: None,
+            candidate: None,
+            candidate_readings: 0,
+            impact: None,
+            shake: ShakeDetector::new(),
+        }
+    }
+
+    fn update(
+        &mut self,
+        reading: Vector<MilliG>,
+        thresholds: &Thresholds,
+        mut report: impl FnMut(Gesture),
+    ) {
+        let reading = reading.map(i32::from);
+        let force_squared = square(reading.x) + square(reading.y) + square(reading.z);
+
+        self.update_impact(force_squared, thresholds, &mut report);
+
+        let shaking = self.shake.update(reading, thresholds);
+        let posture = if shaking {
+            Some(Gesture::Shake)
+        } else {
+            match self.current {
+                Some(current) if holds(current, reading, force_squared, thresholds, true) => {
+                    Some(current)
+                }
+                _ => classify(reading, force_squared, thresholds),
+            }
+        };
+
+        if posture == self.current {
+            self.candidate = None;
+            self.candidate_readings = 0;
+            return;
+        }
+        if posture == self.candidate {
+            self.candidate_readings = self.candidate_readings.saturating_add(1);
+        } else {
+            self.candidate = posture;
+            self.candidate_readings = 1;
+        }
+        if self.candidate_readings >= thresholds.settle {
+            self.current = posture;
+            self.candidate = None;
+            self.candidate_readings = 0;
+            if let Some(gesture) = posture {
+                report(gesture);
+            }
+        }
+    }
+
+    fn update_impact(
+        &mut self,
+        force_squared: i64,
+        thresholds: &Thresholds,
+        report: &mut impl FnMut(Gesture),
+    ) {
+        let lowest = IMPACTS[IMPACTS.len() - 1].0 - thresholds.hysteresis_mg;
+        if force_squared < square(lowest) {
+            self.impact = None;
+            return;
+        }
+        for &(threshold, gesture) in IMPACTS.iter() {
+            if force_squared > square(threshold) {
+                let stronger = match self.impact {
+                    None => true,
+                    Some(previous) => rank(gesture) > rank(previous),
+                };
+                if stronger {
+                    self.impact = Some(gesture);
+                    report(gesture);
+                }
+                break;
+            }
+        }
+    }
+}
+
+fn square(value: i32) -> i64 {
+    i64::from(value) * i64::from(value)
+}
+
+/// Orders the impact gestures by strength.
+fn rank(impact: Gesture) -> u8 {
+    match impact {
+        Gesture::G8 => 3,
+        Gesture::G6 => 2,
+        Gesture::G3 => 1,
+        _ => 0,
+    }
+}
+
+/// Returns the posture a reading shows, checking for falling, then face up
+/// or down, then tilting.
+fn classify(reading: Vector<i32>, force_squared: i64, thresholds: &Thresholds) -> Option<Gesture> {
+    [
+        Gesture::FreeFall,
+        Gesture::FaceUp,
+        Gesture::FaceDown,
+        Gesture::TiltLeft,
+        Gesture::TiltRight,
+        Gesture::TiltForward,
+        Gesture::TiltBack,
+    ]
+    .iter()
+    .cloned()
+    .find(|&posture| holds(posture, reading, force_squared, thresholds, false))
+}
+
+/// Returns whether a reading shows a posture; if `relaxed` is set, the
+/// thresholds are moved by the hysteresis to make it easier.
+fn holds(
+    posture: Gesture,
+    reading: Vector<i32>,
+    force_squared: i64,
+    thresholds: &Thresholds,
+    relaxed: bool,
+) -> bool {
+    let slack = if relaxed { thresholds.hysteresis_mg } else { 0 };
+    let face = 1000 - thresholds.face_mg - slack;
+    let tilt = thresholds.tilt_mg - slack;
+    let freefall = thresholds.freefall_mg + slack;
+    match posture {
+        Gesture::FreeFall => force_squared < square(freefall),
+        Gesture::FaceUp => reading.z > face,
+        Gesture::FaceDown => reading.z < -face,
+        Gesture::TiltLeft => reading.x > tilt,
+        Gesture::TiltRight => reading.x < -tilt,
+        Gesture::TiltForward => reading.y < -tilt,
+        Gesture::TiltBack => reading.y > tilt,
+        _ => false,
+    }
+}
+
+/// A small queue of gestures waiting to be taken.
+#[derive(Copy, Clone, Debug)]
+struct EventQueue {
+    events: [Option<Gesture>; EVENT_QUEUE_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl EventQueue {
+    const fn new() -> EventQueue {
+        EventQueue {
+            events: [None; EVENT_QUEUE_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Adds a gesture, dropping it if the queue is full.
+    fn push(&mut self, event: Gesture) {
+        if self.len < EVENT_QUEUE_LEN {
+            self.events[(self.head + self.len) % EVENT_QUEUE_LEN] = Some(event);
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<Gesture> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.events[self.head].take();
+        self.head = (self.head + 1) % EVENT_QUEUE_LEN;
+        self.len -= 1;
+        event
+    }
+}
+
+/// Recognises gestures from accelerometer readings.
+///
+/// See the [module-level documentation](sensors::gesture) for details.
+#[derive(Copy, Clone, Debug)]
+pub struct GestureRecogniser {
+    thresholds: Thresholds,
+    detector: Detector,
+    events: EventQueue,
+}
+
+impl GestureRecogniser {
+    /// Returns a `GestureRecogniser` which hasn't seen any readings.
+    pub const fn new(thresholds: Thresholds) -> GestureRecogniser {
+        GestureRecogniser {
+            thresholds,
+            detector: Detector::new(),
+            events: EventQueue::new(),
+        }
+    }
+
+    /// Changes the thresholds.
+    pub fn set_thresholds(&mut self, thresholds: Thresholds) {
+        self.thresholds = thresholds;
+    }
+
+    /// Takes a new reading, queueing any gestures for
+    /// [`next_event()`].
+    ///
+    /// [`next_event()`]: sensors::gesture::GestureRecogniser::next_event
+    pub fn update(&mut self, reading: Vector<MilliG>) {
+        let events = &mut self.events;
+        self.detector
+            .update(reading, &self.thresholds, |gesture| events.push(gesture));
+    }
+
+    /// Takes a new reading, calling `report` for each gesture instead of
+    /// queueing it.
+    pub fn update_with(&mut self, reading: Vector<MilliG>, report: impl FnMut(Gesture)) {
+        self.detector.update(reading, &self.thresholds, report);
+    }
+
+    /// Returns the oldest gesture which hasn't been taken yet.
+    ///
+    /// Each gesture is reported when it starts. Up to eight are kept; if
+    /// more arrive before they're taken, the newest are dropped.
+    pub fn next_event(&mut self) -> Option<Gesture> {
+        self.events.pop()
+    }
+
+    /// Returns the current posture, free fall or shake.
+    ///
+    /// This is never one of the impacts.
+    pub fn current(&self) -> Option<Gesture> {
+        self.detector.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAT: (i16, i16, i16) = (0, 0, 1000);
+
+    /// Feeds the same reading `count` times, and returns the gestures
+    /// reported.
+    fn feed(
+        recogniser: &mut GestureRecogniser,
+        reading: (i16, i16, i16),
+        count: usize,
+    ) -> Vec<Gesture> {
+        let (x, y, z) = reading;
+        let mut events = Vec::new();
+        for _ in 0..count {
+            recogniser.update_with(Vector::new(MilliG(x), MilliG(y), MilliG(z)), |gesture| {
+                events.push(gesture)
+            });
+        }
+        events
+    }
+
+    /// Feeds each reading once, and returns the gestures reported.
+    fn feed_all(recogniser: &mut GestureRecogniser, readings: &[(i16, i16, i16)]) -> Vec<Gesture> {
+        readings
+            .iter()
+            .flat_map(|&reading| feed(recogniser, reading, 1))
+            .collect()
+    }
+
+    fn face_up() -> GestureRecogniser {
+        let mut recogniser = GestureRecogniser::new(Thresholds::default());
+        assert_eq!(feed(&mut recogniser, FLAT, 10), [Gesture::FaceUp]);
+        recogniser
+    }
+
+    #[test]
+    fn posture_settles() {
+        let mut recogniser = GestureRecogniser::new(Thresholds::default());
+        assert_eq!(feed(&mut recogniser, FLAT, 4), []);
+        assert_eq!(recogniser.current(), None);
+        assert_eq!(feed(&mut recogniser, FLAT, 1), [Gesture::FaceUp]);
+        assert_eq!(recogniser.current(), Some(Gesture::FaceUp));
+        assert_eq!(feed(&mut recogniser, FLAT, 100), []);
+    }
+
+    #[test]
+    fn brief_postures_are_ignored() {
+        let mut recogniser = face_up();
+        for _ in 0..10 {
+            assert_eq!(feed(&mut recogniser, (300, 0, 700), 4), []);
+            assert_eq!(feed(&mut recogniser, FLAT, 1), []);
+        }
+        assert_eq!(recogniser.current(), Some(Gesture::FaceUp));
+    }
+
+    #[test]
+    fn no_chatter_at_the_thresholds() {
+        let mut recogniser = face_up();
+        // Just past the face-up threshold, but within the hysteresis.
+        for _ in 0..50 {
+            assert_eq!(feed_all(&mut recogniser, &[(0, 0, 790), (0, 0, 810)]), []);
+        }
+        assert_eq!(recogniser.current(), Some(Gesture::FaceUp));
+
+        // Tilted to the left, wobbling either side of the tilt threshold.
+        assert_eq!(feed(&mut recogniser, (210, 0, 600), 5), [Gesture::TiltLeft]);
+        for _ in 0..50 {
+            assert_eq!(
+                feed_all(&mut recogniser, &[(190, 0, 600), (210, 0, 600)]),
+                []
+            );
+        }
+        assert_eq!(recogniser.current(), Some(Gesture::TiltLeft));
+
+        // Past the hysteresis, the posture is given up (with no event).
+        assert_eq!(feed(&mut recogniser, (140, 0, 600), 5), []);
+        assert_eq!(recogniser.current(), None);
+    }
+
+    #[test]
+    fn tilts() {
+        let mut recogniser = face_up();
+        assert_eq!(
+            feed(&mut recogniser, (-500, 0, 700), 5),
+            [Gesture::TiltRight]
+        );
+        assert_eq!(
+            feed(&mut recogniser, (0, -500, 700), 5),
+            [Gesture::TiltForward]
+        );
+        assert_eq!(feed(&mut recogniser, (0, 500, 700), 5), [Gesture::TiltBack]);
+        assert_eq!(feed(&mut recogniser, (0, 0, -1000), 5), [Gesture::FaceDown]);
+    }
+
+    #[test]
+    fn free_fall() {
+        let mut recogniser = face_up();
+        assert_eq!(feed(&mut recogniser, (0, 0, 100), 4), []);
+        assert_eq!(
+            feed(&mut recogniser, (50, -50, 100), 1),
+            [Gesture::FreeFall]
+        );
+        assert_eq!(recogniser.current(), Some(Gesture::FreeFall));
+        assert_eq!(feed(&mut recogniser, FLAT, 5), [Gesture::FaceUp]);
+    }
+
+    #[test]
+    fn shake_needs_enough_changes_of_direction() {
+        let mut recogniser = face_up();
+        // Three changes of direction aren't enough.
+        let shake = [(600, 0, 1000), (-600, 0, 1000)];
+        assert_eq!(feed_all(&mut recogniser, &shake), []);
+        assert_eq!(feed_all(&mut recogniser, &shake), []);
+        assert_eq!(feed(&mut recogniser, FLAT, 30), []);
+        assert_eq!(recogniser.current(), Some(Gesture::FaceUp));
+
+        // Once there are four, the shake settles like a posture.
+        let mut events = Vec::new();
+        for _ in 0..5 {
+            events.extend(feed_all(&mut recogniser, &shake));
+        }
+        assert_eq!(events, [Gesture::Shake]);
+        assert_eq!(recogniser.current(), Some(Gesture::Shake));
+    }
+
+    #[test]
+    fn shake_times_out() {
+        let mut recogniser = face_up();
+        let shake = [(600, 0, 1000), (-600, 0, 1000)];
+        for _ in 0..5 {
+            feed_all(&mut recogniser, &shake);
+        }
+        assert_eq!(recogniser.current(), Some(Gesture::Shake));
+
+        // The shake lasts until there have been 25 readings without a
+        // change of direction, then the posture has to settle again.
+        assert_eq!(feed(&mut recogniser, FLAT, 24), []);
+        assert_eq!(recogniser.current(), Some(Gesture::Shake));
+        assert_eq!(feed(&mut recogniser, FLAT, 4), []);
+        assert_eq!(feed(&mut recogniser, FLAT, 1), [Gesture::FaceUp]);
+    }
+
+    #[test]
+    fn impacts_escalate() {
+        let mut recogniser = face_up();
+        let events = feed_all(
+            &mut recogniser,
+            &[
+                (0, 0, 3500),
+                (0, 0, 6500),
+                (0, 0, 9000),
+                (0, 0, 7000),
+                (0, 0, 3500),
+            ],
+        );
+        assert_eq!(events, [Gesture::G3, Gesture::G6, Gesture::G8]);
+        // Impacts aren't postures.
+        assert_eq!(recogniser.current(), Some(Gesture::FaceUp));
+    }
+
+    #[test]
+    fn impacts_repeat_only_below_3g() {
+        let mut recogniser = face_up();
+        let events = feed_all(
+            &mut recogniser,
+            &[
+                (0, 0, 3500),
+                (0, 0, 2980),
+                (0, 0, 3500),
+                (2000, 0, 2200),
+                (0, 0, 6500),
+            ],
+        );
+        assert_eq!(events, [Gesture::G3, Gesture::G6]);
+
+        let events = feed_all(
+            &mut recogniser,
+            &[(0, 0, 2900), (0, 0, 3500), FLAT, (0, 0, 6500)],
+        );
+        assert_eq!(events, [Gesture::G3, Gesture::G6]);
+    }
+
+    #[test]
+    fn full_queue_drops_newest_events() {
+        let mut recogniser = GestureRecogniser::new(Thresholds::default());
+        let reading = |x, y, z| Vector::new(MilliG(x), MilliG(y), MilliG(z));
+        for _ in 0..5 {
+            recogniser.update(reading(0, 0, 1000));
+        }
+        for _ in 0..5 {
+            recogniser.update(reading(0, 0, 3500));
+            recogniser.update(reading(0, 0, 6500));
+            recogniser.update(reading(0, 0, 1000));
+        }
+
+        let mut events = Vec::new();
+        while let Some(event) = recogniser.next_event() {
+            events.push(event);
+        }
+        assert_eq!(
+            events,
+            [
+                Gesture::FaceUp,
+                Gesture::G3,
+                Gesture::G6,
+                Gesture::G3,
+                Gesture::G6,
+                Gesture::G3,
+                Gesture::G6,
+                Gesture::G3,
+            ]
+        );
+
+        // Once there's room, events are queued again.
+        recogniser.update(reading(0, 0, 9000));
+        assert_eq!(recogniser.next_event(), Some(Gesture::G8));
+        assert_eq!(recogniser.next_event(), None);
+    }
+}
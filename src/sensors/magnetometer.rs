@@ -0,0 +1,396 @@
+//! Driver for the MAG3110 magnetometer.
+//!
+//! The MAG3110 is at address `0x0E` on the internal I2C bus. It measures the
+//! magnetic field along three axes, from -1000µT to 1000µT in steps of
+//! 0.1µT. (The Earth's field is around 25µT to 65µT.)
+//!
+//! [`Magnetometer::new()`] checks that the sensor is present and starts it
+//! taking readings continuously, 80 times a second;
+//! [`Magnetometer::configure()`] changes the rate.
+//!
+//! # Calibration
+//!
+//! Magnets and iron near the sensor (including parts of the board itself)
+//! add a constant "hard-iron" offset to every reading, which is usually much
+//! larger than the Earth's field. To measure it, feed readings to a
+//! [`HardIronCalibrator`] while rotating the board in all directions, then
+//! pass the resulting [`Calibration`] to [`Magnetometer::set_calibration()`].
+//!
+//! Nearby iron also distorts the field so that it's stronger along some
+//! axes than others ("soft-iron" distortion). A `Calibration` can correct
+//! this too, by scaling each axis; the [`compass`] module works out the
+//! scales as well as the offset.
+//!
+//! A `Calibration` can be stored (see [`Calibration::to_bytes()`]) and
+//! reapplied later.
+//!
+//! # Heading
+//!
+//! [`Magnetometer::heading()`] gives the direction of the sensor's y axis in
+//! degrees clockwise from magnetic north, as long as the sensor's z axis is
+//! pointing straight up (so the reading is only useful while the board is
+//! held level).
+//!
+//! [`Magnetometer::new()`]: sensors::magnetometer::Magnetometer::new
+//! [`Magnetometer::configure()`]: sensors::magnetometer::Magnetometer::configure
+//! [`Magnetometer::set_calibration()`]: sensors::magnetometer::Magnetometer::set_calibration
+//! [`Magnetometer::heading()`]: sensors::magnetometer::Magnetometer::heading
+//! [`HardIronCalibrator`]: sensors::magnetometer::HardIronCalibrator
+//! [`Calibration`]: sensors::magnetometer::Calibration
+//! [`Calibration::to_bytes()`]: sensors::magnetometer::Calibration::to_bytes
+//! [`compass`]: sensors::compass
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+use super::{atan2_degrees, Error, Vector};
+use crate::units::MicroTesla;
+
+/// The MAG3110's I2C address.
+pub const ADDRESS: u8 = 0x0E;
+
+/// The value of the MAG3110's WHO_AM_I register.
+const DEVICE_ID: u8 = 0xC4;
+
+const DR_STATUS: u8 = 0x00;
+const OUT_X_MSB: u8 = 0x01;
+const WHO_AM_I: u8 = 0x07;
+const CTRL_REG1: u8 = 0x10;
+const CTRL_REG2: u8 = 0x11;
+
+/// CTRL_REG1: active mode.
+const CTRL_REG1_AC: u8 = 1 << 0;
+/// CTRL_REG2: reset the sensor before each measurement.
+const CTRL_REG2_AUTO_MRST_EN: u8 = 1 << 7;
+/// DR_STATUS: new data is available on all three axes.
+const DR_STATUS_ZYXDR: u8 = 1 << 3;
+
+/// Microtesla per count.
+const MICROTESLA_PER_COUNT: f32 = 0.1;
+
+/// Output rates, when taking 16 samples per reading.
+///
+/// Taking more samples (see [`Oversampling`]) reduces the output rate in
+/// proportion: for example `Hz80` with `X32` gives 40 readings a second.
+///
+/// [`Oversampling`]: sensors::magnetometer::Oversampling
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DataRate {
+    Hz80 = 0,
+    Hz40 = 1,
+    Hz20 = 2,
+    Hz10 = 3,
+    Hz5 = 4,
+    Hz2_5 = 5,
+    Hz1_25 = 6,
+    Hz0_63 = 7,
+}
+
+/// How many samples are averaged for each reading.
+///
+/// More samples give less noisy readings, but use more power.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Oversampling {
+    X16 = 0,
+    X32 = 1,
+    X64 = 2,
+    X128 = 3,
+}
+
+/// Corrections for hard-iron and soft-iron distortion.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Calibration {
+    /// The reading when the Earth's field is excluded, in the sensor's units
+    /// (0.1µT). This is subtracted from every reading.
+    pub offset: Vector<i16>,
+    /// The factor each axis is multiplied by, after subtracting the offset.
+    pub scale: Vector<f32>,
+}
+
+impl Calibration {
+    /// The length of the stored form of a `Calibration`.
+    pub const BYTES: usize = 18;
+
+    /// Returns the calibration as bytes, for storing.
+    pub fn to_bytes(self) -> [u8; Calibration::BYTES] {
+        let mut bytes = [0; Calibration::BYTES];
+        let offsets = [self.offset.x, self.offset.y, self.offset.z];
+        let scales = [self.scale.x, self.scale.y, self.scale.z];
+        for (i, offset) in offsets.iter().enumerate() {
+            bytes[i * 2..i * 2 + 2].copy_from_slice(&offset.to_le_bytes());
+        }
+        for (i, scale) in scales.iter().enumerate() {
+            bytes[6 + i * 4..10 + i * 4].copy_from_slice(&scale.to_bits().to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Reads a calibration stored by [`to_bytes()`].
+    ///
+    /// [`to_bytes()`]: sensors::magnetometer::Calibration::to_bytes
+    pub fn from_bytes(bytes: &[u8; Calibration::BYTES]) -> Calibration {
+        let offset = |i: usize| i16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]]);
+        let scale = |i: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[6 + i * 4..10 + i * 4]);
+            f32::from_bits(u32::from_le_bytes(word))
+        };
+        Calibration {
+            offset: Vector::new(offset(0), offset(1), offset(2)),
+            scale: Vector::new(scale(0), scale(1), scale(2)),
+        }
+    }
+}
+
+impl Default for Calibration {
+    /// No correction.
+    fn default() -> Calibration {
+        Calibration {
+            offset: Vector::default(),
+            scale: Vector::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// Works out a [`Calibration`] from readings taken while rotating the board.
+///
+/// The offset on each axis is taken to be halfway between the smallest and
+/// largest reading seen.
+///
+/// [`Calibration`]: sensors::magnetometer::Calibration
+#[derive(Copy, Clone, Debug)]
+pub struct HardIronCalibrator {
+    min: Vector<i16>,
+    max: Vector<i16>,
+}
+
+impl HardIronCalibrator {
+    /// Returns a `HardIronCalibrator` which hasn't seen any readings.
+    pub const fn new() -> HardIronCalibrator {
+        HardIronCalibrator {
+            min: Vector::new(i16::MAX, i16::MAX, i16::MAX),
+            max: Vector::new(i16::MIN, i16::MIN, i16::MIN),
+        }
+    }
+
+    /// Adds a raw reading (from [`Magnetometer::read_raw()`]).
+    ///
+    /// [`Magnetometer::read_raw()`]: sensors::magnetometer::Magnetometer::read_raw
+    pub fn add(&mut self, raw: Vector<i16>) {
+        self.min = Vector::new(
+            self.min.x.min(raw.x),
+            self.min.y.min(raw.y),
+            self.min.z.min(raw.z),
+        );
+        self.max = Vector::new(
+            self.max.x.max(raw.x),
+            self.max.y.max(raw.y),
+            self.max.z.max(raw.z),
+        );
+    }
+
+    /// Returns the difference between the largest and smallest readings on
+    /// each axis.
+    ///
+    /// Once the board has been turned all the way round on every axis, each
+    /// of these is about twice the strength of the Earth's field (500 to
+    /// 1300 counts).
+    pub fn spread(&self) -> Vector<i32> {
+        if self.min.x > self.max.x {
+            return Vector::default();
+        }
+        Vector::new(
+            i32::from(self.max.x) - i32::from(self.min.x),
+            i32::from(self.max.y) - i32::from(self.min.y),
+            i32::from(self.max.z) - i32::from(self.min.z),
+        )
+    }
+
+    /// Returns the calibration for the readings seen so far.
+    pub fn calibration(&self) -> Calibration {
+        if self.min.x > self.max.x {
+            return Calibration::default();
+        }
+        let middle = |min: i16, max: i16| ((i32::from(min) + i32::from(max)) / 2) as i16;
+        Calibration {
+            offset: Vector::new(
+                middle(self.min.x, self.max.x),
+                middle(self.min.y, self.max.y),
+                middle(self.min.z, self.max.z),
+            ),
+            ..Calibration::default()
+        }
+    }
+}
+
+impl Default for HardIronCalibrator {
+    fn default() -> HardIronCalibrator {
+        HardIronCalibrator::new()
+    }
+}
+
+/// A MAG3110 magnetometer.
+pub struct Magnetometer<I2C> {
+    i2c: I2C,
+    calibration: Calibration,
+}
+
+impl<I2C, E> Magnetometer<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Checks that a MAG3110 is present, and starts it taking 80 readings a
+    /// second.
+    ///
+    /// Returns `Error::WrongDevice` if something else answers at its
+    /// address, and an I2C error if nothing does (as on v1.5 boards).
+    pub fn new(i2c: I2C) -> Result<Magnetometer<I2C>, Error<E>> {
+        let mut magnetometer = Magnetometer {
+            i2c,
+            calibration: Calibration::default(),
+        };
+        let id = magnetometer.read_register(WHO_AM_I)?;
+        if id != DEVICE_ID {
+            return Err(Error::WrongDevice(id));
+        }
+        magnetometer.write_register(CTRL_REG2, CTRL_REG2_AUTO_MRST_EN)?;
+        magnetometer.configure(DataRate::Hz80, Oversampling::X16)?;
+        Ok(magnetometer)
+    }
+
+    /// Gives back the I2C bus.
+    ///
+    /// The sensor is left taking readings.
+    pub fn free(self) -> I2C {
+        self.i2c
+    }
+
+    /// Sets the output rate and the number of samples per reading.
+    pub fn configure(&mut self, rate: DataRate, oversampling: Oversampling) -> Result<(), E> {
+        // The settings can only be changed in standby mode.
+        self.write_register(CTRL_REG1, 0)?;
+        let settings = (rate as u8) << 5 | (oversampling as u8) << 3;
+        self.write_register(CTRL_REG1, settings | CTRL_REG1_AC)
+    }
+
+    /// Returns whether there's a reading which hasn't been read yet.
+    pub fn data_ready(&mut self) -> Result<bool, E> {
+        Ok(self.read_register(DR_STATUS)? & DR_STATUS_ZYXDR != 0)
+    }
+
+    /// Returns the latest reading in the sensor's units (0.1µT), without
+    /// calibration.
+    pub fn read_raw(&mut self) -> Result<Vector<i16>, E> {
+        let mut data = [0; 6];
+        self.i2c.write_read(ADDRESS, &[OUT_X_MSB], &mut data)?;
+        Ok(Vector::new(
+            i16::from_be_bytes([data[0], data[1]]),
+            i16::from_be_bytes([data[2], data[3]]),
+            i16::from_be_bytes([data[4], data[5]]),
+        ))
+    }
+
+    /// Returns the latest reading, with the calibration applied.
+    pub fn read(&mut self) -> Result<Vector<MicroTesla>, E> {
+        let raw = self.read_raw()?;
+        let Calibration { offset, scale } = self.calibration;
+        let correct = |raw: i16, offset: i16, scale: f32| {
+            MicroTesla((i32::from(raw) - i32::from(offset)) as f32 * scale * MICROTESLA_PER_COUNT)
+        };
+        Ok(Vector::new(
+            correct(raw.x, offset.x, scale.x),
+            correct(raw.y, offset.y, scale.y),
+            correct(raw.z, offset.z, scale.z),
+        ))
+    }
+
+    /// Returns the direction of the sensor's y axis in degrees clockwise
+    /// from magnetic north (0 to 360), from the latest reading.
+    ///
+    /// See the [module-level documentation](sensors::magnetometer) for the
+    /// orientation this assumes.
+    pub fn heading(&mut self) -> Result<f32, E> {
+        Ok(heading(self.read()?))
+    }
+
+    /// Returns the calibration applied by [`read()`].
+    ///
+    /// [`read()`]: sensors::magnetometer::Magnetometer::read
+    pub fn calibration(&self) -> Calibration {
+        self.calibration
+    }
+
+    /// Sets the calibration applied by [`read()`].
+    ///
+    /// [`read()`]: sensors::magnetometer::Magnetometer::read
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, E> {
+        let mut value = [0];
+        self.i2c.write_read(ADDRESS, &[register], &mut value)?;
+        Ok(value[0])
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), E> {
+        self.i2c.write(ADDRESS, &[register, value])
+    }
+}
+
+/// Returns the heading (in degrees clockwise from magnetic north, 0 to 360)
+/// of the sensor's y axis, given a calibrated reading taken with the z axis
+/// pointing up.
+pub fn heading(field: Vector<MicroTesla>) -> f32 {
+    let degrees = atan2_degrees(-field.x.0, field.y.0);
+    if degrees < 0.0 {
+        degrees + 360.0
+    } else {
+        degrees
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(x: f32, y: f32) -> Vector<MicroTesla> {
+        Vector::new(MicroTesla(x), MicroTesla(y), MicroTesla(-40.0))
+    }
+
+    #[test]
+    fn calibration_round_trip() {
+        let calibration = Calibration {
+            offset: Vector::new(-1234, 567, i16::MIN),
+            scale: Vector::new(0.875, 1.25, -3.0e-5),
+        };
+        let bytes = calibration.to_bytes();
+        assert_eq!(Calibration::from_bytes(&bytes), calibration);
+        assert_eq!(bytes[..2], (-1234i16).to_le_bytes());
+        assert_eq!(
+            Calibration::from_bytes(&Calibration::default().to_bytes()),
+            Calibration::default()
+        );
+    }
+
+    #[test]
+    fn heading_on_the_axes() {
+        // With the y axis pointing north the horizontal field is along +y;
+        // turning clockwise moves it towards -x.
+        assert_eq!(heading(field(0.0, 20.0)), 0.0);
+        assert_eq!(heading(field(-20.0, 0.0)), 90.0);
+        assert_eq!(heading(field(0.0, -20.0)), 180.0);
+        assert_eq!(heading(field(20.0, 0.0)), 270.0);
+    }
+
+    #[test]
+    fn heading_in_each_quadrant() {
+        assert_eq!(heading(field(-20.0, 20.0)), 45.0);
+        assert_eq!(heading(field(-20.0, -20.0)), 135.0);
+        assert_eq!(heading(field(20.0, -20.0)), 225.0);
+        assert_eq!(heading(field(20.0, 20.0)), 315.0);
+        let degrees = heading(field(-10.0, 17.32));
+        assert!((degrees - 30.0).abs() < 0.3, "{}", degrees);
+        let degrees = heading(field(17.32, 10.0));
+        assert!((degrees - 300.0).abs() < 0.3, "{}", degrees);
+    }
+}
@@ -0,0 +1,316 @@
+//! Drivers for the sensors on the micro:bit's internal I2C bus.
+//!
+//! The internal bus uses `TWI1` on pins 0 (SCL) and 30 (SDA). The drivers
+//! work with anything implementing the embedded-hal blocking I2C traits,
+//! such as the HAL's `I2c`:
+//!
+//! ```ignore
+//! let scl = gpio.pin0.into_open_drain_input().into();
+//! let sda = gpio.pin30.into_open_drain_input().into();
+//! let i2c = i2c::I2c::i2c1(p.TWI1, sda, scl);
+//! let mut magnetometer = Magnetometer::new(i2c)?;
+//! ```
+//!
+//! To use more than one driver at once, share the bus with
+//! [`shared_i2c`]. To recover from a sensor holding the bus after a reset,
+//! wrap the `I2c` in an [`I2cBus`].
+//!
+//! Readings are returned as a [`Vector`] in the sensor's own axes, in the
+//! units from the [`units`] module.
+//!
+//! # Board axes
+//!
+//! The sensors are mounted on the back of the board, each turned its own
+//! way, so their axes don't match each other or the board.
+//! [`Vector::to_board_axes()`] converts a reading into the board's axes:
+//! x towards button B, y towards the edge with the USB connector, and z out
+//! of the front (so lying still face up, the accelerometer reads
+//! (0, 0, 1000)). Combining readings from two sensors, as
+//! [`tilt_compensated_heading()`] does, or recognising gestures needs
+//! readings in the board's axes.
+//!
+//! | Revision | Sensor                  | Board axes      |
+//! |----------|-------------------------|-----------------|
+//! | v1.3     | MMA8653 accelerometer   | (x, -y, -z)     |
+//! | v1.3     | MAG3110 magnetometer    | (-x, y, -z)     |
+//! | v1.5     | LSM303AGR (both)        | (x, -y, -z)     |
+//!
+//! # Board revisions
+//!
+//! micro:bit v1.3 boards have an MMA8653 accelerometer and a MAG3110
+//! magnetometer; v1.5 boards have an LSM303AGR, which combines the two.
+//! Some v1.5 boards have an FXOS8700 (also a combined sensor) instead,
+//! which isn't supported: `board_revision()` returns `None` for them.
+//! [`board_revision()`] finds out which the board has, and
+//! [`Accelerometer::new()`] uses the same check to pick the right driver.
+//! The [`magnetometer`] module only supports the MAG3110 so far.
+//!
+//! [`Vector`]: sensors::Vector
+//! [`Vector::to_board_axes()`]: sensors::Vector::to_board_axes
+//! [`tilt_compensated_heading()`]: sensors::compass::tilt_compensated_heading
+//! [`units`]: units
+//! [`board_revision()`]: sensors::board_revision
+//! [`Accelerometer::new()`]: sensors::accelerometer::Accelerometer::new
+//! [`magnetometer`]: sensors::magnetometer
+//! [`shared_i2c`]: shared_i2c
+//! [`I2cBus`]: i2c_bus::I2cBus
+
+use core::fmt;
+use core::ops::Neg;
+
+use embedded_hal::blocking::i2c::WriteRead;
+
+pub mod accelerometer;
+pub mod compass;
+pub mod gesture;
+pub mod magnetometer;
+
+/// A micro:bit board revision, as far as the sensors are concerned.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BoardRevision {
+    /// v1.3 (and earlier), with an MMA8653 and a MAG3110.
+    V1_3,
+    /// v1.5, with an LSM303AGR.
+    V1_5,
+}
+
+/// Works out the board revision by checking which accelerometer answers on
+/// the internal I2C bus.
+///
+/// Returns `None` if neither does, which includes boards with the
+/// unsupported FXOS8700.
+pub fn board_revision<I2C, E>(i2c: &mut I2C) -> Option<BoardRevision>
+where
+    I2C: WriteRead<Error = E>,
+{
+    accelerometer::probe(i2c).map(BoardRevision::from)
+}
+
+impl From<accelerometer::Model> for BoardRevision {
+    fn from(model: accelerometer::Model) -> BoardRevision {
+        match model {
+            accelerometer::Model::Mma8653 => BoardRevision::V1_3,
+            accelerometer::Model::Lsm303agr => BoardRevision::V1_5,
+        }
+    }
+}
+
+/// Which sensor a reading came from, for [`Vector::to_board_axes()`].
+///
+/// [`Vector::to_board_axes()`]: sensors::Vector::to_board_axes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Sensor {
+    Accelerometer,
+    Magnetometer,
+}
+
+/// A reading along three axes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Vector<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+impl<T> Vector<T> {
+    /// Returns a new `Vector`.
+    pub const fn new(x: T, y: T, z: T) -> Vector<T> {
+        Vector { x, y, z }
+    }
+
+    /// Applies `f` to each component.
+    pub fn map<U, F: FnMut(T) -> U>(self, mut f: F) -> Vector<U> {
+        Vector {
+            x: f(self.x),
+            y: f(self.y),
+            z: f(self.z),
+        }
+    }
+}
+
+impl<T: Neg<Output = T>> Vector<T> {
+    /// Converts a reading from `sensor`'s own axes into the board's axes.
+    ///
+    /// See the [module-level documentation](sensors) for the board's axes
+    /// and how each sensor is mounted.
+    pub fn to_board_axes(self, revision: BoardRevision, sensor: Sensor) -> Vector<T> {
+        let Vector { x, y, z } = self;
+        match (revision, sensor) {
+            (BoardRevision::V1_3, Sensor::Magnetometer) => Vector::new(-x, y, -z),
+            (BoardRevision::V1_3, Sensor::Accelerometer) | (BoardRevision::V1_5, _) => {
+                Vector::new(x, -y, -z)
+            }
+        }
+    }
+}
+
+/// Prints the vector as `(x, y, z)`, applying any formatting options to
+/// each component.
+impl<T: fmt::Display> fmt::Display for Vector<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("(")?;
+        self.x.fmt(f)?;
+        f.write_str(", ")?;
+        self.y.fmt(f)?;
+        f.write_str(", ")?;
+        self.z.fmt(f)?;
+        f.write_str(")")
+    }
+}
+
+/// Errors from the sensor drivers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// An I2C transfer failed.
+    I2c(E),
+    /// The device's identification register didn't hold the expected value
+    /// (the value read is given here).
+    WrongDevice(u8),
+    /// None of the supported devices answered.
+    NotFound,
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(error: E) -> Error<E> {
+        Error::I2c(error)
+    }
+}
+
+/// Returns the angle of the point (x, y) from the x axis in degrees, between
+/// -180 and 180 (like `atan2(y, x)`).
+///
+/// This uses an approximation which is accurate to about 0.3°, so it doesn't
+/// need `libm`.
+pub(crate) fn atan2_degrees(y: f32, x: f32) -> f32 {
+    let abs_x = if x < 0.0 { -x } else { x };
+    let abs_y = if y < 0.0 { -y } else { y };
+    if abs_x == 0.0 && abs_y == 0.0 {
+        return 0.0;
+    }
+    // atan(r) ≈ 45r + 15.64r(1 - r) for r between 0 and 1.
+    let mut angle = if abs_y <= abs_x {
+        let r = abs_y / abs_x;
+        45.0 * r + 15.64 * r * (1.0 - r)
+    } else {
+        let r = abs_x / abs_y;
+        90.0 - (45.0 * r + 15.64 * r * (1.0 - r))
+    };
+    if x < 0.0 {
+        angle = 180.0 - angle;
+    }
+    if y < 0.0 {
+        angle = -angle;
+    }
+    angle
+}
+
+/// Returns the square root of a value (0 for negative values).
+///
+/// This uses Newton's method, so it doesn't need `libm`.
+pub(crate) fn sqrt(value: f32) -> f32 {
+    if value.is_nan() || value <= 0.0 {
+        return 0.0;
+    }
+    // Halving the exponent gives a first guess within about 4%.
+    let mut root = f32::from_bits((value.to_bits() >> 1) + 0x1FBD_1DF5);
+    for _ in 0..3 {
+        root = 0.5 * (root + value / root);
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::MilliG;
+
+    #[test]
+    fn atan2_degrees_is_within_its_bound() {
+        for step in 0..720 {
+            let angle = (step as f32 * 0.5 - 180.0).to_radians();
+            for &radius in &[1e-3, 1.0, 1e4] {
+                let (y, x) = (radius * angle.sin(), radius * angle.cos());
+                let expected = y.atan2(x).to_degrees();
+                let mut error = atan2_degrees(y, x) - expected;
+                if error > 180.0 {
+                    error -= 360.0;
+                } else if error < -180.0 {
+                    error += 360.0;
+                }
+                assert!(error.abs() < 0.3, "atan2({}, {}): error {}", y, x, error);
+            }
+        }
+    }
+
+    #[test]
+    fn atan2_degrees_on_the_axes() {
+        assert_eq!(atan2_degrees(0.0, 0.0), 0.0);
+        assert_eq!(atan2_degrees(0.0, 1.0), 0.0);
+        assert_eq!(atan2_degrees(1.0, 0.0), 90.0);
+        assert_eq!(atan2_degrees(0.0, -1.0), 180.0);
+        assert_eq!(atan2_degrees(-1.0, 0.0), -90.0);
+    }
+
+    #[test]
+    fn sqrt_is_accurate() {
+        let mut value = 1e-6f32;
+        while value < 1e9 {
+            let error = (sqrt(value) - value.sqrt()) / value.sqrt();
+            assert!(
+                error.abs() < 1e-6,
+                "sqrt({}): relative error {}",
+                value,
+                error
+            );
+            value *= 1.37;
+        }
+        assert_eq!(sqrt(4.0), 2.0);
+    }
+
+    #[test]
+    fn sqrt_of_zero_negative_and_nan() {
+        assert_eq!(sqrt(0.0), 0.0);
+        assert_eq!(sqrt(-4.0), 0.0);
+        assert_eq!(sqrt(f32::NAN), 0.0);
+    }
+
+    fn cross(a: Vector<i32>, b: Vector<i32>) -> Vector<i32> {
+        Vector::new(
+            a.y * b.z - a.z * b.y,
+            a.z * b.x - a.x * b.z,
+            a.x * b.y - a.y * b.x,
+        )
+    }
+
+    #[test]
+    fn board_axes_are_rotations() {
+        // A rotation keeps right-handed axes right-handed, so it maps x × y
+        // to (mapped x) × (mapped y).
+        let x = Vector::new(1, 0, 0);
+        let y = Vector::new(0, 1, 0);
+        for &revision in &[BoardRevision::V1_3, BoardRevision::V1_5] {
+            for &sensor in &[Sensor::Accelerometer, Sensor::Magnetometer] {
+                let to_board = |v: Vector<i32>| v.to_board_axes(revision, sensor);
+                assert_eq!(
+                    cross(to_board(x), to_board(y)),
+                    to_board(cross(x, y)),
+                    "{:?} {:?}",
+                    revision,
+                    sensor
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn board_axes_face_up() {
+        // Lying face up, each accelerometer's z axis points into the table.
+        for &revision in &[BoardRevision::V1_3, BoardRevision::V1_5] {
+            let reading = Vector::new(MilliG(0), MilliG(0), MilliG(-1000));
+            assert_eq!(
+                reading.to_board_axes(revision, Sensor::Accelerometer),
+                Vector::new(MilliG(0), MilliG(0), MilliG(1000))
+            );
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Sharing an I2C bus between several drivers.
+//!
+//! The internal I2C bus (`TWI1` on pins 0 and 30, also on edge connector
+//! pins 19 and 20) connects both motion sensors, and external devices can
+//! be added to it. Each driver wants to own an implementation of the
+//! embedded-hal I2C traits, but there's only one `I2c`.
+//!
+//! [`SharedBus`] owns the bus, protected by a [`BusMutex`], and hands out
+//! any number of [`I2cProxy`] handles which implement the traits. Each
+//! transfer through a proxy locks the mutex, so transfers from different
+//! drivers never overlap.
+//!
+//! There are two kinds of mutex:
+//! - [`CriticalSectionMutex`] disables interrupts for each transfer, so
+//!   drivers can be used from any interrupt handler as well as from the
+//!   main thread.
+//! - With the `rtfm` feature, [`RtfmLock`] locks an RTFM resource instead,
+//!   for sharing the bus between devices used within an RTFM task.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let i2c = i2c::I2c::i2c1(p.TWI1, sda, scl);
+//! let bus = SharedBus::critical_section(i2c);
+//! let mut accelerometer = Accelerometer::new(bus.acquire())?;
+//! let mut magnetometer = Magnetometer::new(bus.acquire())?;
+//! ```
+//!
+//! To keep the drivers in `static`s (so interrupt handlers can use them),
+//! the bus must be `'static` too, for example using `cortex_m::singleton!`:
+//!
+//! ```ignore
+//! let bus: &'static _ = cortex_m::singleton!(
+//!     : SharedBus<CriticalSectionMutex<I2c<TWI1>>> = SharedBus::critical_section(i2c)
+//! )
+//! .unwrap();
+//! ```
+//!
+//! [`SharedBus`]: shared_i2c::SharedBus
+//! [`BusMutex`]: shared_i2c::BusMutex
+//! [`I2cProxy`]: shared_i2c::I2cProxy
+//! [`CriticalSectionMutex`]: shared_i2c::CriticalSectionMutex
+//! [`RtfmLock`]: shared_i2c::RtfmLock
+
+use core::cell::RefCell;
+
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+
+/// Gives exclusive access to a bus while a closure runs.
+pub trait BusMutex {
+    /// The bus being protected.
+    type Bus;
+
+    /// Calls `f` with exclusive access to the bus.
+    fn lock<R, F: FnOnce(&mut Self::Bus) -> R>(&self, f: F) -> R;
+}
+
+/// A [`BusMutex`] which disables interrupts while the bus is in use.
+///
+/// [`BusMutex`]: shared_i2c::BusMutex
+pub struct CriticalSectionMutex<T> {
+    bus: RefCell<T>,
+}
+
+// The bus is only borrowed with interrupts disabled, and there's only one
+// core, so no two borrows can overlap.
+unsafe impl<T: Send> Sync for CriticalSectionMutex<T> {}
+
+impl<T> CriticalSectionMutex<T> {
+    /// Returns a new `CriticalSectionMutex` protecting `bus`.
+    pub const fn new(bus: T) -> CriticalSectionMutex<T> {
+        CriticalSectionMutex {
+            bus: RefCell::new(bus),
+        }
+    }
+
+    /// Gives the bus back.
+    pub fn into_inner(self) -> T {
+        self.bus.into_inner()
+    }
+}
+
+impl<T> BusMutex for CriticalSectionMutex<T> {
+    type Bus = T;
+
+    fn lock<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        cortex_m::interrupt::free(|_| f(&mut self.bus.borrow_mut()))
+    }
+}
+
+/// A [`BusMutex`] which locks an RTFM resource.
+///
+/// RTFM gives each task its own handle to a shared resource, so create the
+/// `SharedBus` (and the drivers using it) inside the task:
+///
+/// ```ignore
+/// #[task(resources = [I2C])]
+/// fn read_sensors() {
+///     let bus = SharedBus::new(RtfmLock::new(resources.I2C));
+///     // ...
+/// }
+/// ```
+///
+/// [`BusMutex`]: shared_i2c::BusMutex
+#[cfg(feature = "rtfm")]
+pub struct RtfmLock<R> {
+    resource: RefCell<R>,
+}
+
+#[cfg(feature = "rtfm")]
+impl<R: rtfm::Mutex> RtfmLock<R> {
+    /// Returns a new `RtfmLock` using a task's handle to a resource.
+    pub fn new(resource: R) -> RtfmLock<R> {
+        RtfmLock {
+            resource: RefCell::new(resource),
+        }
+    }
+}
+
+#[cfg(feature = "rtfm")]
+impl<R: rtfm::Mutex> BusMutex for RtfmLock<R> {
+    type Bus = R::T;
+
+    fn lock<T, F: FnOnce(&mut R::T) -> T>(&self, f: F) -> T {
+        self.resource.borrow_mut().lock(f)
+    }
+}
+
+/// A bus shared between several drivers.
+///
+/// See the [module-level documentation](shared_i2c) for details.
+pub struct SharedBus<M> {
+    mutex: M,
+}
+
+impl<M> SharedBus<M> {
+    /// Returns a `SharedBus` using the bus protected by `mutex`.
+    pub const fn new(mutex: M) -> SharedBus<M> {
+        SharedBus { mutex }
+    }
+
+    /// Returns a handle to the bus for one driver.
+    pub fn acquire(&self) -> I2cProxy<'_, M> {
+        I2cProxy { mutex: &self.mutex }
+    }
+
+    /// Gives the mutex (and the bus inside it) back.
+    pub fn into_inner(self) -> M {
+        self.mutex
+    }
+}
+
+impl<T> SharedBus<CriticalSectionMutex<T>> {
+    /// Returns a `SharedBus` which disables interrupts while the bus is in
+    /// use.
+    pub const fn critical_section(bus: T) -> SharedBus<CriticalSectionMutex<T>> {
+        SharedBus::new(CriticalSectionMutex::new(bus))
+    }
+}
+
+/// A driver's handle to a [`SharedBus`], implementing the embedded-hal
+/// blocking I2C traits.
+///
+/// [`SharedBus`]: shared_i2c::SharedBus
+pub struct I2cProxy<'a, M> {
+    mutex: &'a M,
+}
+
+impl<M> Clone for I2cProxy<'_, M> {
+    fn clone(&self) -> Self {
+        I2cProxy { mutex: self.mutex }
+    }
+}
+
+impl<M> Write for I2cProxy<'_, M>
+where
+    M: BusMutex,
+    M::Bus: Write,
+{
+    type Error = <M::Bus as Write>::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.mutex.lock(|bus| bus.write(address, bytes))
+    }
+}
+
+impl<M> Read for I2cProxy<'_, M>
+where
+    M: BusMutex,
+    M::Bus: Read,
+{
+    type Error = <M::Bus as Read>::Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.mutex.lock(|bus| bus.read(address, buffer))
+    }
+}
+
+impl<M> WriteRead for I2cProxy<'_, M>
+where
+    M: BusMutex,
+    M::Bus: WriteRead,
+{
+    type Error = <M::Bus as WriteRead>::Error;
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.mutex
+            .lock(|bus| bus.write_read(address, bytes, buffer))
+    }
+}
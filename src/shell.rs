@@ -0,0 +1,736 @@
+//! A small command shell, for driving the board by hand over a serial port.
+//!
+//! [`Shell`] takes input a byte at a time (for example from
+//! [`BufferedUart::read()`]) and writes its echo and the commands' output to
+//! anything implementing `core::fmt::Write`. It provides:
+//! - line editing with backspace
+//! - a history of the last few lines, recalled with the up and down arrow
+//!   keys
+//! - splitting lines into whitespace-separated arguments, where double quotes
+//!   can be used to include spaces in an argument
+//! - a `help` command listing the registered commands.
+//!
+//! Commands are described by a table of [`Command`]s, each with a name, a
+//! line of help text and a handler function. Handlers are given a context
+//! value of the application's choosing (typically a struct holding the
+//! peripherals the commands use), the arguments, and the output.
+//!
+//! Lines are limited to 80 characters and 8 arguments (including the command
+//! name); the rest is ignored.
+//!
+//! # Built-in commands
+//!
+//! Some commands for the board's own hardware are provided, for adding to
+//! the table alongside the application's:
+//! - [`Command::show()`] shows an image on the display
+//! - [`Command::buttons()`] reads buttons A and B
+//! - [`Command::rng()`] prints numbers from the random number generator
+//! - [`Command::magnetometer()`] reads the magnetometer.
+//!
+//! Each is generic over a small trait ([`ShowImage`], [`ReadButtons`],
+//! [`RandomNumbers`] or [`ReadMagnetometer`]) which the context implements
+//! to give the command its hardware; `examples/serial_shell.rs` shows how.
+//!
+//! # Example
+//!
+//! ```ignore
+//! fn hello(_: &mut Board, args: &[&str], out: &mut dyn Write) -> fmt::Result {
+//!     write!(out, "hello {}\r\n", args.get(1).unwrap_or(&"world"))
+//! }
+//!
+//! const COMMANDS: &[Command<Board>] = &[
+//!     Command { name: "hello", help: "hello [name]: say hello", handler: hello },
+//!     Command::buttons(),
+//! ];
+//!
+//! let mut shell = Shell::new(COMMANDS);
+//! shell.prompt(&mut tx)?;
+//! loop {
+//!     let byte = block!(rx.read())?;
+//!     shell.feed(byte, &mut board, &mut tx)?;
+//! }
+//! ```
+//!
+//! [`Shell`]: shell::Shell
+//! [`Command`]: shell::Command
+//! [`BufferedUart::read()`]: buffered_uart::BufferedUart::read
+//! [`Command::show()`]: shell::Command::show
+//! [`Command::buttons()`]: shell::Command::buttons
+//! [`Command::rng()`]: shell::Command::rng
+//! [`Command::magnetometer()`]: shell::Command::magnetometer
+//! [`ShowImage`]: shell::ShowImage
+//! [`ReadButtons`]: shell::ReadButtons
+//! [`RandomNumbers`]: shell::RandomNumbers
+//! [`ReadMagnetometer`]: shell::ReadMagnetometer
+
+use core::fmt::{self, Write};
+use core::str;
+use core::str::FromStr;
+
+use crate::sensors::Vector;
+use crate::units::MicroTesla;
+
+/// Longest line which can be entered.
+const LINE_LEN: usize = 80;
+
+/// Number of previous lines remembered.
+const HISTORY_LEN: usize = 4;
+
+/// Most arguments passed to a command (including its name).
+const MAX_ARGS: usize = 8;
+
+const PROMPT: &str = "> ";
+
+/// A handler function for a [`Command`].
+///
+/// `args[0]` is the command name.
+pub type Handler<C> = fn(context: &mut C, args: &[&str], out: &mut dyn Write) -> fmt::Result;
+
+/// A command which can be run from the shell.
+pub struct Command<C> {
+    /// The name typed to run the command.
+    pub name: &'static str,
+    /// One line describing the command, shown by `help`.
+    pub help: &'static str,
+    /// The function which runs the command.
+    pub handler: Handler<C>,
+}
+
+/// Hardware used by [`Command::show()`].
+pub trait ShowImage {
+    /// Shows a 5×5 image (1 for a lit LED, 0 for an unlit one) for
+    /// `duration_ms` milliseconds.
+    fn show_image(&mut self, image: [[u8; 5]; 5], duration_ms: u32);
+}
+
+/// Hardware used by [`Command::buttons()`].
+pub trait ReadButtons {
+    /// Returns whether buttons A and B are pressed.
+    fn buttons_pressed(&mut self) -> (bool, bool);
+}
+
+/// Hardware used by [`Command::rng()`].
+pub trait RandomNumbers {
+    /// Returns a random number.
+    fn random_u32(&mut self) -> u32;
+}
+
+/// Hardware used by [`Command::magnetometer()`].
+pub trait ReadMagnetometer {
+    /// Returns the magnetic field, or `None` if the magnetometer isn't
+    /// responding.
+    fn magnetic_field(&mut self) -> Option<Vector<MicroTesla>>;
+}
+
+impl<C: ShowImage> Command<C> {
+    /// Returns the `show` command, which shows one of a few images for a
+    /// given time (1 second by default).
+    pub const fn show() -> Command<C> {
+        Command {
+            name: "show",
+            help: "show heart|smile|cross [ms]: show an image on the display",
+            handler: show,
+        }
+    }
+}
+
+impl<C: ReadButtons> Command<C> {
+    /// Returns the `buttons` command, which prints whether each button is
+    /// pressed.
+    pub const fn buttons() -> Command<C> {
+        Command {
+            name: "buttons",
+            help: "buttons: read buttons A and B",
+            handler: buttons,
+        }
+    }
+}
+
+impl<C: RandomNumbers> Command<C> {
+    /// Returns the `rng` command, which prints a given number of random
+    /// numbers (1 by default).
+    pub const fn rng() -> Command<C> {
+        Command {
+            name: "rng",
+            help: "rng [count]: print random numbers from the hardware RNG",
+            handler: random,
+        }
+    }
+}
+
+impl<C: ReadMagnetometer> Command<C> {
+    /// Returns the `mag` command, which prints the magnetic field.
+    pub const fn magnetometer() -> Command<C> {
+        Command {
+            name: "mag",
+            help: "mag: read the magnetometer",
+            handler: magnetometer,
+        }
+    }
+}
+
+/// Images for the `show` command.
+const IMAGES: [(&str, [[u8; 5]; 5]); 3] = [
+    (
+        "heart",
+        [
+            [0, 1, 0, 1, 0],
+            [1, 0, 1, 0, 1],
+            [1, 0, 0, 0, 1],
+            [0, 1, 0, 1, 0],
+            [0, 0, 1, 0, 0],
+        ],
+    ),
+    (
+        "smile",
+        [
+            [0, 0, 0, 0, 0],
+            [0, 1, 0, 1, 0],
+            [0, 0, 0, 0, 0],
+            [1, 0, 0, 0, 1],
+            [0, 1, 1, 1, 0],
+        ],
+    ),
+    (
+        "cross",
+        [
+            [1, 0, 0, 0, 1],
+            [0, 1, 0, 1, 0],
+            [0, 0, 1, 0, 0],
+            [0, 1, 0, 1, 0],
+            [1, 0, 0, 0, 1],
+        ],
+    ),
+];
+
+fn show<C: ShowImage>(context: &mut C, args: &[&str], out: &mut dyn Write) -> fmt::Result {
+    let image = match IMAGES.iter().find(|(name, _)| args.get(1) == Some(name)) {
+        Some(&(_, image)) => image,
+        None => return out.write_str("usage: show heart|smile|cross [ms]\r\n"),
+    };
+    let duration_ms = match number_arg(args, 2, 1000) {
+        Ok(duration_ms) => duration_ms,
+        Err(arg) => return write!(out, "not a number: '{}'\r\n", arg),
+    };
+    context.show_image(image, duration_ms);
+    Ok(())
+}
+
+fn buttons<C: ReadButtons>(context: &mut C, _: &[&str], out: &mut dyn Write) -> fmt::Result {
+    let (a, b) = context.buttons_pressed();
+    write!(out, "A: {}, B: {}\r\n", a, b)
+}
+
+fn random<C: RandomNumbers>(context: &mut C, args: &[&str], out: &mut dyn Write) -> fmt::Result {
+    let count: u32 = match number_arg(args, 1, 1) {
+        Ok(count) => count,
+        Err(arg) => return write!(out, "not a number: '{}'\r\n", arg),
+    };
+    for _ in 0..count {
+        write!(out, "{}\r\n", context.random_u32())?;
+    }
+    Ok(())
+}
+
+fn magnetometer<C: ReadMagnetometer>(
+    context: &mut C,
+    _: &[&str],
+    out: &mut dyn Write,
+) -> fmt::Result {
+    match context.magnetic_field() {
+        Some(field) => write!(
+            out,
+            "x: {:.1}, y: {:.1}, z: {:.1}\r\n",
+            field.x, field.y, field.z
+        ),
+        None => out.write_str("magnetometer not responding\r\n"),
+    }
+}
+
+/// Parses `args[index]` as a number, or returns `default` if there's no such
+/// argument. Returns the argument if it isn't a number.
+fn number_arg<'a, T: FromStr>(args: &[&'a str], index: usize, default: T) -> Result<T, &'a str> {
+    match args.get(index) {
+        Some(arg) => arg.parse().map_err(|_| *arg),
+        None => Ok(default),
+    }
+}
+
+/// Progress through an ANSI escape sequence.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Escape {
+    None,
+    /// Seen ESC.
+    Started,
+    /// Seen ESC `[`.
+    Csi,
+}
+
+/// A line of text.
+#[derive(Copy, Clone)]
+struct Line {
+    bytes: [u8; LINE_LEN],
+    len: usize,
+}
+
+impl Line {
+    const fn new() -> Line {
+        Line {
+            bytes: [0; LINE_LEN],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only printable ASCII is ever stored.
+        str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+/// A command shell.
+///
+/// See the [module-level documentation](shell) for details.
+pub struct Shell<'a, C> {
+    commands: &'a [Command<C>],
+    line: Line,
+    history: [Line; HISTORY_LEN],
+    /// Number of lines in `history`, most recent first.
+    history_len: usize,
+    /// How far back in the history the arrow keys have gone (0 for the line
+    /// being edited).
+    history_pos: usize,
+    /// The line being edited, kept while browsing the history.
+    saved: Line,
+    escape: Escape,
+    /// Set after a CR, so that a following LF is ignored.
+    after_cr: bool,
+}
+
+impl<'a, C> Shell<'a, C> {
+    /// Returns a new shell running commands from `commands`.
+    pub fn new(commands: &'a [Command<C>]) -> Shell<'a, C> {
+        Shell {
+            commands,
+            line: Line::new(),
+            history: [Line::new(); HISTORY_LEN],
+            history_len: 0,
+            history_pos: 0,
+            saved: Line::new(),
+            escape: Escape::None,
+            after_cr: false,
+        }
+    }
+
+    /// Writes the prompt.
+    pub fn prompt(&self, out: &mut dyn Write) -> fmt::Result {
+        out.write_str(PROMPT)
+    }
+
+    /// Handles one byte of input.
+    ///
+    /// When the byte ends a line, the command is run and a new prompt is
+    /// written.
+    pub fn feed(&mut self, byte: u8, context: &mut C, out: &mut dyn Write) -> fmt::Result {
+        let after_cr = self.after_cr;
+        self.after_cr = false;
+
+        match self.escape {
+            Escape::Started => {
+                self.escape = if byte == b'[' {
+                    Escape::Csi
+                } else {
+                    Escape::None
+                };
+                return Ok(());
+            }
+            Escape::Csi => {
+                // Parameters and intermediates come before the final byte.
+                if (0x40..=0x7E).contains(&byte) {
+                    self.escape = Escape::None;
+                    match byte {
+                        b'A' => return self.recall(self.history_pos + 1, out),
+                        b'B' if self.history_pos > 0 => {
+                            return self.recall(self.history_pos - 1, out)
+                        }
+                        _ => {}
+                    }
+                }
+                return Ok(());
+            }
+            Escape::None => {}
+        }
+
+        match byte {
+            0x1B => self.escape = Escape::Started,
+            b'\r' | b'\n' => {
+                if byte == b'\n' && after_cr {
+                    return Ok(());
+                }
+                self.after_cr = byte == b'\r';
+                out.write_str("\r\n")?;
+                self.execute(context, out)?;
+                self.prompt(out)?;
+            }
+            // Backspace and delete
+            0x08 | 0x7F if self.line.len > 0 => {
+                self.line.len -= 1;
+                out.write_str("\x08 \x08")?;
+            }
+            0x20..=0x7E if self.line.len < LINE_LEN => {
+                self.line.bytes[self.line.len] = byte;
+                self.line.len += 1;
+                out.write_char(byte as char)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Replaces the line being edited with one from the history (or with
+    /// the line which was being edited, if `pos` is 0).
+    fn recall(&mut self, pos: usize, out: &mut dyn Write) -> fmt::Result {
+        if pos > self.history_len {
+            return Ok(());
+        }
+        if self.history_pos == 0 {
+            self.saved = self.line;
+        }
+        self.history_pos = pos;
+        for _ in 0..self.line.len {
+            out.write_str("\x08 \x08")?;
+        }
+        self.line = if pos == 0 {
+            self.saved
+        } else {
+            self.history[pos - 1]
+        };
+        out.write_str(self.line.as_str())
+    }
+
+    /// Runs the command on the current line, and starts a new line.
+    fn execute(&mut self, context: &mut C, out: &mut dyn Write) -> fmt::Result {
+        let line = self.line;
+        self.line.len = 0;
+        self.history_pos = 0;
+
+        let mut args = [""; MAX_ARGS];
+        let count = tokenise(line.as_str(), &mut args);
+        if count == 0 {
+            return Ok(());
+        }
+        self.history.rotate_right(1);
+        self.history[0] = line;
+        self.history_len = (self.history_len + 1).min(HISTORY_LEN);
+
+        let args = &args[..count];
+        if args[0] == "help" {
+            return self.help(out);
+        }
+        match self.commands.iter().find(|command| command.name == args[0]) {
+            Some(command) => (command.handler)(context, args, out),
+            None => write!(out, "unknown command '{}' (try 'help')\r\n", args[0]),
+        }
+    }
+
+    fn help(&self, out: &mut dyn Write) -> fmt::Result {
+        out.write_str("help: list commands\r\n")?;
+        for command in self.commands {
+            write!(out, "{}\r\n", command.help)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a line into arguments, and returns how many were found.
+///
+/// Arguments are separated by spaces; a double-quoted section may contain
+/// spaces. Arguments beyond the size of `args` are ignored.
+///
+/// ```ignore
+/// let mut args = [""; 4];
+/// assert_eq!(tokenise(r#"show "a b" c"#, &mut args), 3);
+/// assert_eq!(args[..3], ["show", "a b", "c"]);
+/// ```
+pub fn tokenise<'l>(line: &'l str, args: &mut [&'l str]) -> usize {
+    let bytes = line.as_bytes();
+    let mut count = 0;
+    let mut pos = 0;
+    while count < args.len() {
+        while pos < bytes.len() && bytes[pos] == b' ' {
+            pos += 1;
+        }
+        if pos == bytes.len() {
+            break;
+        }
+        let (start, end) = if bytes[pos] == b'"' {
+            let start = pos + 1;
+            let end = bytes[start..]
+                .iter()
+                .position(|&b| b == b'"')
+                .map_or(bytes.len(), |len| start + len);
+            pos = (end + 1).min(bytes.len());
+            (start, end)
+        } else {
+            let start = pos;
+            while pos < bytes.len() && bytes[pos] != b' ' {
+                pos += 1;
+            }
+            (start, pos)
+        };
+        args[count] = &line[start..end];
+        count += 1;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the arguments of each command run.
+    #[derive(Default)]
+    struct Runs(Vec<Vec<String>>);
+
+    fn record(runs: &mut Runs, args: &[&str], out: &mut dyn Write) -> fmt::Result {
+        runs.0
+            .push(args.iter().map(|arg| arg.to_string()).collect());
+        out.write_str("ran\r\n")
+    }
+
+    const COMMANDS: &[Command<Runs>] = &[Command {
+        name: "run",
+        help: "run [args]: record the arguments",
+        handler: record,
+    }];
+
+    fn feed(shell: &mut Shell<Runs>, runs: &mut Runs, input: &[u8]) -> String {
+        let mut out = String::new();
+        for &byte in input {
+            shell.feed(byte, runs, &mut out).unwrap();
+        }
+        out
+    }
+
+    fn tokens(line: &str) -> Vec<&str> {
+        let mut args = [""; MAX_ARGS];
+        let count = tokenise(line, &mut args);
+        args[..count].to_vec()
+    }
+
+    #[test]
+    fn runs_command_with_arguments() {
+        let (mut shell, mut runs) = (Shell::new(COMMANDS), Runs::default());
+        let out = feed(&mut shell, &mut runs, b"run a b\r");
+        assert_eq!(out, "run a b\r\nran\r\n> ");
+        assert_eq!(runs.0, [["run", "a", "b"]]);
+    }
+
+    #[test]
+    fn crlf_ends_one_line() {
+        let (mut shell, mut runs) = (Shell::new(COMMANDS), Runs::default());
+        feed(&mut shell, &mut runs, b"run\r\nrun\n");
+        assert_eq!(runs.0.len(), 2);
+    }
+
+    #[test]
+    fn unknown_command_and_blank_line() {
+        let (mut shell, mut runs) = (Shell::new(COMMANDS), Runs::default());
+        let out = feed(&mut shell, &mut runs, b"   \rfoo\r");
+        assert_eq!(
+            out,
+            "   \r\n> foo\r\nunknown command 'foo' (try 'help')\r\n> "
+        );
+        assert!(runs.0.is_empty());
+    }
+
+    #[test]
+    fn backspace_edits_line() {
+        let (mut shell, mut runs) = (Shell::new(COMMANDS), Runs::default());
+        let out = feed(&mut shell, &mut runs, b"\x08rux\x7F\x08un x\r");
+        assert_eq!(out, "rux\x08 \x08\x08 \x08un x\r\nran\r\n> ");
+        assert_eq!(runs.0, [["run", "x"]]);
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        let (mut shell, mut runs) = (Shell::new(COMMANDS), Runs::default());
+        let mut input = b"run ".to_vec();
+        input.extend_from_slice(&[b'x'; LINE_LEN]);
+        input.push(b'\r');
+        feed(&mut shell, &mut runs, &input);
+        assert_eq!(runs.0[0][1].len(), LINE_LEN - 4);
+    }
+
+    #[test]
+    fn history_recalls_previous_lines() {
+        let (mut shell, mut runs) = (Shell::new(COMMANDS), Runs::default());
+        feed(&mut shell, &mut runs, b"run 1\rrun 2\r");
+        let out = feed(&mut shell, &mut runs, b"ru\x1B[A");
+        assert_eq!(out, "ru\x08 \x08\x08 \x08run 2");
+        feed(&mut shell, &mut runs, b"\x1B[A\x1B[A\x1B[A");
+        assert_eq!(shell.line.as_str(), "run 1");
+        feed(&mut shell, &mut runs, b"\x1B[B\x1B[B");
+        assert_eq!(shell.line.as_str(), "ru");
+        feed(&mut shell, &mut runs, b"\x1B[B\x1B[A\r");
+        assert_eq!(runs.0.last().unwrap(), &["run", "2"]);
+    }
+
+    #[test]
+    fn history_keeps_newest_lines() {
+        let (mut shell, mut runs) = (Shell::new(COMMANDS), Runs::default());
+        for i in 0..HISTORY_LEN + 2 {
+            feed(&mut shell, &mut runs, format!("run {}\r", i).as_bytes());
+        }
+        for _ in 0..HISTORY_LEN + 2 {
+            feed(&mut shell, &mut runs, b"\x1B[A");
+        }
+        assert_eq!(shell.line.as_str(), "run 2");
+    }
+
+    #[test]
+    fn other_escape_sequences_are_ignored() {
+        let (mut shell, mut runs) = (Shell::new(COMMANDS), Runs::default());
+        let out = feed(&mut shell, &mut runs, b"r\x1B[1;5C\x1BOun\r");
+        assert_eq!(runs.0, [["run"]]);
+        assert!(out.starts_with("run"));
+    }
+
+    #[test]
+    fn help_lists_commands() {
+        let (mut shell, mut runs) = (Shell::new(COMMANDS), Runs::default());
+        let out = feed(&mut shell, &mut runs, b"help\r");
+        assert_eq!(
+            out,
+            "help\r\nhelp: list commands\r\nrun [args]: record the arguments\r\n> "
+        );
+    }
+
+    /// Stands in for the hardware used by the built-in commands.
+    #[derive(Default)]
+    struct FakeBoard {
+        shown: Vec<([[u8; 5]; 5], u32)>,
+        buttons: (bool, bool),
+        next_random: u32,
+        field: Option<Vector<MicroTesla>>,
+    }
+
+    impl ShowImage for FakeBoard {
+        fn show_image(&mut self, image: [[u8; 5]; 5], duration_ms: u32) {
+            self.shown.push((image, duration_ms));
+        }
+    }
+
+    impl ReadButtons for FakeBoard {
+        fn buttons_pressed(&mut self) -> (bool, bool) {
+            self.buttons
+        }
+    }
+
+    impl RandomNumbers for FakeBoard {
+        fn random_u32(&mut self) -> u32 {
+            self.next_random += 1;
+            self.next_random
+        }
+    }
+
+    impl ReadMagnetometer for FakeBoard {
+        fn magnetic_field(&mut self) -> Option<Vector<MicroTesla>> {
+            self.field
+        }
+    }
+
+    const BUILT_IN: &[Command<FakeBoard>] = &[
+        Command::show(),
+        Command::buttons(),
+        Command::rng(),
+        Command::magnetometer(),
+    ];
+
+    /// Runs one line on `board`, and returns the command's output.
+    fn run(board: &mut FakeBoard, line: &str) -> String {
+        let mut shell = Shell::new(BUILT_IN);
+        let mut out = String::new();
+        for &byte in line.as_bytes() {
+            shell.feed(byte, board, &mut out).unwrap();
+        }
+        shell.feed(b'\r', board, &mut out).unwrap();
+        let echo = format!("{}\r\n", line);
+        out[echo.len()..out.len() - PROMPT.len()].to_string()
+    }
+
+    #[test]
+    fn show_command() {
+        let mut board = FakeBoard::default();
+        assert_eq!(run(&mut board, "show smile"), "");
+        assert_eq!(run(&mut board, "show cross 250"), "");
+        assert_eq!(board.shown[0], (IMAGES[1].1, 1000));
+        assert_eq!(board.shown[1], (IMAGES[2].1, 250));
+    }
+
+    #[test]
+    fn show_command_errors() {
+        let mut board = FakeBoard::default();
+        let usage = "usage: show heart|smile|cross [ms]\r\n";
+        assert_eq!(run(&mut board, "show"), usage);
+        assert_eq!(run(&mut board, "show square"), usage);
+        assert_eq!(run(&mut board, "show heart 1s"), "not a number: '1s'\r\n");
+        assert_eq!(run(&mut board, "show heart -5"), "not a number: '-5'\r\n");
+        assert!(board.shown.is_empty());
+    }
+
+    #[test]
+    fn buttons_command() {
+        let mut board = FakeBoard {
+            buttons: (false, true),
+            ..FakeBoard::default()
+        };
+        assert_eq!(run(&mut board, "buttons"), "A: false, B: true\r\n");
+    }
+
+    #[test]
+    fn rng_command() {
+        let mut board = FakeBoard::default();
+        assert_eq!(run(&mut board, "rng"), "1\r\n");
+        assert_eq!(run(&mut board, "rng 3"), "2\r\n3\r\n4\r\n");
+        assert_eq!(run(&mut board, "rng 0"), "");
+        assert_eq!(run(&mut board, "rng many"), "not a number: 'many'\r\n");
+        assert_eq!(board.next_random, 4);
+    }
+
+    #[test]
+    fn magnetometer_command() {
+        let mut board = FakeBoard::default();
+        assert_eq!(run(&mut board, "mag"), "magnetometer not responding\r\n");
+        board.field = Some(Vector::new(
+            MicroTesla(12.34),
+            MicroTesla(-5.0),
+            MicroTesla(40.06),
+        ));
+        assert_eq!(
+            run(&mut board, "mag"),
+            "x: 12.3 µT, y: -5.0 µT, z: 40.1 µT\r\n"
+        );
+    }
+
+    #[test]
+    fn tokenise_splits_on_spaces() {
+        assert_eq!(tokens("  show  a b "), ["show", "a", "b"]);
+        assert!(tokens("").is_empty());
+        assert!(tokens("   ").is_empty());
+    }
+
+    #[test]
+    fn tokenise_quoting() {
+        assert_eq!(tokens(r#"show "a b" c"#), ["show", "a b", "c"]);
+        assert_eq!(tokens(r#"say """#), ["say", ""]);
+        assert_eq!(tokens(r#"say "unterminated x"#), ["say", "unterminated x"]);
+        assert_eq!(tokens(r#""a"b"#), ["a", "b"]);
+    }
+
+    #[test]
+    fn tokenise_ignores_extra_arguments() {
+        let mut args = [""; 2];
+        assert_eq!(tokenise("a b c", &mut args), 2);
+        assert_eq!(args, ["a", "b"]);
+    }
+}
@@ -0,0 +1,419 @@
+//! A software ("bit-banged") UART on any pair of GPIO pins.
+//!
+//! The nRF51 has only one UART, and on the micro:bit it's connected to the
+//! USB interface chip. [`SoftUart`] provides a second serial port on any two
+//! free pins (such as edge connector pins 0 and 1), for GPS modules and
+//! similar devices. It works at 1200 to 38400 baud, with 8 data bits, no
+//! parity and one stop bit.
+//!
+//! `SoftUart` uses a timer to time each bit, a GPIOTE [`Channel`] to detect
+//! the start of each received byte, and a PPI [`ppi::Channel`] to timestamp
+//! it. The timer and GPIOTE channel are driven by interrupts:
+//! call [`handle_timer_interrupt()`] from the timer's interrupt handler and
+//! [`handle_gpiote_interrupt()`] from the `GPIOTE` interrupt handler. Up to
+//! 16 received bytes are buffered.
+//!
+//! It implements the embedded-hal `serial::Read` and `serial::Write` traits,
+//! like the HAL's `Tx` and `Rx`.
+//!
+//! # Timing
+//!
+//! The falling edge at the start of each received byte captures the timer's
+//! count through PPI, and each bit is sampled at a fixed time after that
+//! edge, so the bit timing doesn't depend on how quickly the interrupt
+//! handlers run. The line is sampled again in the middle of the start bit,
+//! and a byte is only received if it's still low, so short glitches are
+//! ignored.
+//!
+//! The handlers should still run within half a bit period of each edge and
+//! sample time (13µs at 38400 baud), so give the GPIOTE and timer interrupts
+//! a high priority and keep other high-priority handlers short. A sample
+//! which is handled late is taken as soon as the handler runs.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let (gpiote, channels) = Gpiote::new(p.GPIOTE);
+//! let ppi_channels = microbit::ppi::split(p.PPI);
+//! let tx = gpio.pin3.into_push_pull_output();
+//! let rx = gpio.pin2.into_floating_input();
+//! let mut gps = SoftUart::new(p.TIMER2, channels.ch0, ppi_channels.ch0, tx, rx, 9600);
+//!
+//! // In the interrupt handlers (with `gps` moved into a static):
+//! gps.handle_timer_interrupt();  // TIMER2
+//! gps.handle_gpiote_interrupt(); // GPIOTE
+//!
+//! // Elsewhere:
+//! let byte = block!(gps.read())?;
+//! ```
+//!
+//! [`SoftUart`]: soft_uart::SoftUart
+//! [`Channel`]: gpiote::Channel
+//! [`ppi::Channel`]: ppi::Channel
+//! [`handle_timer_interrupt()`]: soft_uart::SoftUart::handle_timer_interrupt
+//! [`handle_gpiote_interrupt()`]: soft_uart::SoftUart::handle_gpiote_interrupt
+
+use core::convert::Infallible;
+use core::ops::Deref;
+
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::serial;
+
+use crate::gpiote::{Channel, EventPin, PinNumber};
+use crate::hal::nrf51::{gpio, gpiote, timer0, GPIO, GPIOTE};
+use crate::ppi;
+
+/// Timer frequency (prescaler 0).
+const TIMER_HZ: u32 = 16_000_000;
+
+/// Number of received bytes which can be waiting to be read.
+const RX_BUFFER_LEN: usize = 16;
+
+/// Compare channels used for sending, receiving, reading the time and
+/// capturing the start of a received byte.
+const CC_TX: usize = 0;
+const CC_RX: usize = 1;
+const CC_NOW: usize = 2;
+const CC_EDGE: usize = 3;
+
+/// Errors when receiving.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A byte was received without a valid stop bit.
+    Framing,
+    /// Bytes were received while the buffer was full, and were lost.
+    Overrun,
+}
+
+/// Progress through sending or receiving a byte.
+#[derive(Copy, Clone, Debug)]
+struct Shifter {
+    /// The bits still to send, or received so far.
+    bits: u16,
+    /// The number of bit periods left.
+    remaining: u8,
+}
+
+/// A software UART on two GPIO pins.
+///
+/// See the [module-level documentation](soft_uart) for details.
+pub struct SoftUart<T, TX, RX> {
+    timer: T,
+    channel: Channel,
+    ppi_channel: ppi::Channel,
+    tx_pin: TX,
+    rx_pin: RX,
+    bit_ticks: u16,
+    tx: Option<Shifter>,
+    tx_pending: Option<u8>,
+    rx: Option<Shifter>,
+    rx_buffer: [u8; RX_BUFFER_LEN],
+    rx_head: usize,
+    rx_len: usize,
+    rx_error: Option<Error>,
+}
+
+impl<T, TX, RX> SoftUart<T, TX, RX>
+where
+    T: Deref<Target = timer0::RegisterBlock>,
+    TX: PinNumber + OutputPin,
+    RX: EventPin,
+{
+    /// Sets up a software UART using `timer`, a GPIOTE channel and a PPI
+    /// channel, sending on `tx_pin` and receiving on `rx_pin`.
+    ///
+    /// Enables the timer's and the GPIOTE channel's interrupts; they still
+    /// need to be unmasked in the NVIC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `baud_rate` isn't between 1200 and 38400.
+    pub fn new(
+        timer: T,
+        channel: Channel,
+        ppi_channel: ppi::Channel,
+        tx_pin: TX,
+        rx_pin: RX,
+        baud_rate: u32,
+    ) -> Self {
+        assert!((1200..=38400).contains(&baud_rate));
+        let bit_ticks = ((TIMER_HZ + baud_rate / 2) / baud_rate) as u16;
+
+        // The line idles high.
+        gpio().outset.write(|w| unsafe { w.bits(1 << TX::NUMBER) });
+
+        timer.tasks_stop.write(|w| unsafe { w.bits(1) });
+        timer.tasks_clear.write(|w| unsafe { w.bits(1) });
+        // Timer mode, 16 bits, 16MHz.
+        timer.mode.write(|w| unsafe { w.bits(0) });
+        timer.bitmode.write(|w| unsafe { w.bits(0) });
+        timer.prescaler.write(|w| unsafe { w.bits(0) });
+        timer.shorts.write(|w| unsafe { w.bits(0) });
+        timer.intenclr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        timer.tasks_start.write(|w| unsafe { w.bits(1) });
+
+        let uart = SoftUart {
+            timer,
+            channel,
+            ppi_channel,
+            tx_pin,
+            rx_pin,
+            bit_ticks,
+            tx: None,
+            tx_pending: None,
+            rx: None,
+            rx_buffer: [0; RX_BUFFER_LEN],
+            rx_head: 0,
+            rx_len: 0,
+            rx_error: None,
+        };
+        let index = uart.channel.index();
+        gpiote().config[index].write(|w| {
+            let w = unsafe { w.mode().event().psel().bits(RX::NUMBER) };
+            w.polarity().hi_to_lo()
+        });
+        let event = &gpiote().events_in[index] as *const _ as u32;
+        let task = &uart.timer.tasks_capture[CC_EDGE] as *const _ as u32;
+        uart.ppi_channel.connect(event, task);
+        uart.listen_for_start_bit();
+        uart
+    }
+
+    /// Stops the timer and the GPIOTE and PPI channels, and gives back the
+    /// timer, channels and pins.
+    pub fn free(self) -> (T, Channel, ppi::Channel, TX, RX) {
+        self.ppi_channel.disconnect();
+        let index = self.channel.index();
+        let gpiote = gpiote();
+        gpiote.intenclr.write(|w| unsafe { w.bits(1 << index) });
+        gpiote.config[index].write(|w| unsafe { w.bits(0) });
+        gpiote.events_in[index].write(|w| unsafe { w.bits(0) });
+        self.timer
+            .intenclr
+            .write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        self.timer.tasks_stop.write(|w| unsafe { w.bits(1) });
+        (
+            self.timer,
+            self.channel,
+            self.ppi_channel,
+            self.tx_pin,
+            self.rx_pin,
+        )
+    }
+
+    /// Handles the start of a received byte.
+    ///
+    /// Call this from the `GPIOTE` interrupt handler.
+    pub fn handle_gpiote_interrupt(&mut self) {
+        let index = self.channel.index();
+        let gpiote = gpiote();
+        if gpiote.events_in[index].read().bits() == 0 {
+            return;
+        }
+        gpiote.events_in[index].write(|w| unsafe { w.bits(0) });
+        if self.rx.is_some() {
+            return;
+        }
+
+        // Ignore the data bits' edges until the byte is over, and sample
+        // each bit in its middle, starting with the start bit. The edge's
+        // time was captured by PPI when it happened.
+        gpiote.intenclr.write(|w| unsafe { w.bits(1 << index) });
+        let edge = self.timer.cc[CC_EDGE].read().bits() as u16;
+        let first_sample = edge.wrapping_add(self.bit_ticks / 2);
+        self.rx = Some(Shifter {
+            bits: 0,
+            remaining: 10,
+        });
+        self.schedule(CC_RX, first_sample);
+    }
+
+    /// Sends and samples bits.
+    ///
+    /// Call this from the timer's interrupt handler.
+    pub fn handle_timer_interrupt(&mut self) {
+        if self.timer.events_compare[CC_TX].read().bits() != 0 {
+            self.timer.events_compare[CC_TX].write(|w| unsafe { w.bits(0) });
+            self.send_bit();
+        }
+        if self.timer.events_compare[CC_RX].read().bits() != 0 {
+            self.timer.events_compare[CC_RX].write(|w| unsafe { w.bits(0) });
+            self.receive_bit();
+        }
+    }
+
+    fn send_bit(&mut self) {
+        let mut shifter = match self.tx {
+            Some(shifter) => shifter,
+            None => return,
+        };
+        if shifter.remaining == 0 {
+            // The stop bit has finished.
+            self.tx = None;
+            match self.tx_pending.take() {
+                Some(byte) => self.start_sending(byte),
+                None => self
+                    .timer
+                    .intenclr
+                    .write(|w| unsafe { w.bits(1 << (16 + CC_TX)) }),
+            }
+            return;
+        }
+        if shifter.bits & 1 != 0 {
+            gpio().outset.write(|w| unsafe { w.bits(1 << TX::NUMBER) });
+        } else {
+            gpio().outclr.write(|w| unsafe { w.bits(1 << TX::NUMBER) });
+        }
+        shifter.bits >>= 1;
+        shifter.remaining -= 1;
+        self.tx = Some(shifter);
+        let next = self.timer.cc[CC_TX].read().bits() as u16;
+        self.schedule(CC_TX, next.wrapping_add(self.bit_ticks));
+    }
+
+    fn receive_bit(&mut self) {
+        let mut shifter = match self.rx {
+            Some(shifter) => shifter,
+            None => return,
+        };
+        let high = gpio().in_.read().bits() & (1 << RX::NUMBER) != 0;
+        shifter.remaining -= 1;
+        if shifter.remaining == 9 && high {
+            // The line was high again by the middle of the start bit, so
+            // that was a glitch rather than a byte.
+            self.stop_receiving();
+            return;
+        }
+        if shifter.remaining > 0 {
+            if shifter.remaining < 9 {
+                shifter.bits = (shifter.bits >> 1) | if high { 0x80 } else { 0 };
+            }
+            self.rx = Some(shifter);
+            let next = self.timer.cc[CC_RX].read().bits() as u16;
+            self.schedule(CC_RX, next.wrapping_add(self.bit_ticks));
+            return;
+        }
+
+        // This was the stop bit.
+        self.stop_receiving();
+        if !high {
+            self.rx_error = Some(Error::Framing);
+        } else if self.rx_len == RX_BUFFER_LEN {
+            self.rx_error = Some(Error::Overrun);
+        } else {
+            self.rx_buffer[(self.rx_head + self.rx_len) % RX_BUFFER_LEN] = shifter.bits as u8;
+            self.rx_len += 1;
+        }
+    }
+
+    fn stop_receiving(&mut self) {
+        self.rx = None;
+        self.timer
+            .intenclr
+            .write(|w| unsafe { w.bits(1 << (16 + CC_RX)) });
+        self.listen_for_start_bit();
+    }
+
+    fn start_sending(&mut self, byte: u8) {
+        // Start bit (0), 8 data bits, stop bit (1), sent LSB first.
+        self.tx = Some(Shifter {
+            bits: (u16::from(byte) << 1) | (1 << 9),
+            remaining: 10,
+        });
+        let start = self.now().wrapping_add(self.bit_ticks / 8);
+        self.schedule(CC_TX, start);
+    }
+
+    fn listen_for_start_bit(&self) {
+        let index = self.channel.index();
+        let gpiote = gpiote();
+        gpiote.events_in[index].write(|w| unsafe { w.bits(0) });
+        gpiote.intenset.write(|w| unsafe { w.bits(1 << index) });
+    }
+
+    /// Returns the timer's current count.
+    fn now(&self) -> u16 {
+        self.timer.tasks_capture[CC_NOW].write(|w| unsafe { w.bits(1) });
+        self.timer.cc[CC_NOW].read().bits() as u16
+    }
+
+    /// Sets a compare channel to fire at `time`, and enables its interrupt.
+    ///
+    /// If `time` has already passed (because an interrupt handler ran
+    /// late), the channel fires straight away rather than after the 16-bit
+    /// timer wraps around, about 4ms later.
+    fn schedule(&self, cc: usize, time: u16) {
+        self.timer.cc[cc].write(|w| unsafe { w.bits(u32::from(time)) });
+        self.timer.events_compare[cc].write(|w| unsafe { w.bits(0) });
+        // Times are never scheduled more than half the timer's range ahead.
+        if time.wrapping_sub(self.now()) as i16 <= 0 {
+            self.timer.events_compare[cc].write(|w| unsafe { w.bits(1) });
+        }
+        self.timer
+            .intenset
+            .write(|w| unsafe { w.bits(1 << (16 + cc)) });
+    }
+}
+
+impl<T, TX, RX> serial::Read<u8> for SoftUart<T, TX, RX>
+where
+    T: Deref<Target = timer0::RegisterBlock>,
+    TX: PinNumber + OutputPin,
+    RX: EventPin,
+{
+    type Error = Error;
+
+    /// Returns the next received byte.
+    ///
+    /// An error is reported once, before the bytes received after it.
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        if let Some(error) = self.rx_error.take() {
+            return Err(nb::Error::Other(error));
+        }
+        if self.rx_len == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        let byte = self.rx_buffer[self.rx_head];
+        self.rx_head = (self.rx_head + 1) % RX_BUFFER_LEN;
+        self.rx_len -= 1;
+        Ok(byte)
+    }
+}
+
+impl<T, TX, RX> serial::Write<u8> for SoftUart<T, TX, RX>
+where
+    T: Deref<Target = timer0::RegisterBlock>,
+    TX: PinNumber + OutputPin,
+    RX: EventPin,
+{
+    type Error = Infallible;
+
+    /// Starts sending a byte, or queues it if another byte is being sent.
+    fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+        if self.tx.is_none() {
+            self.start_sending(byte);
+            Ok(())
+        } else if self.tx_pending.is_none() {
+            self.tx_pending = Some(byte);
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        if self.tx.is_none() && self.tx_pending.is_none() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+fn gpio() -> &'static gpio::RegisterBlock {
+    unsafe { &*GPIO::ptr() }
+}
+
+fn gpiote() -> &'static gpiote::RegisterBlock {
+    unsafe { &*GPIOTE::ptr() }
+}
@@ -0,0 +1,151 @@
+//! Reading the nRF51's on-die temperature sensor.
+//!
+//! The `TEMP` peripheral measures the temperature of the nRF51 itself, in
+//! steps of 0.25°C. That's the value MicroPython's `microbit.temperature()`
+//! returns (in whole degrees). [`Temperature`] owns the peripheral and
+//! returns readings as [`Celsius`].
+//!
+//! A measurement takes about 36µs. [`measure()`] starts one and waits for
+//! it; alternatively call [`start()`] and then [`read()`], which returns
+//! `WouldBlock` until the result is ready. With
+//! [`set_data_ready_interrupt()`], the `TEMP` interrupt fires when the
+//! result is ready, and the handler can call `read()`.
+//!
+//! # Calibration
+//!
+//! The die is usually a few degrees warmer than the air around the board,
+//! and the sensor is only accurate to ±4°C, so each board reads a little
+//! differently. To correct for this, compare a reading with a thermometer
+//! and pass the difference to [`set_offset()`]; it's added to every
+//! reading.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut temperature = Temperature::new(p.TEMP);
+//! temperature.set_offset(Celsius(-2.5));
+//! let celsius = temperature.measure();
+//! ```
+//!
+//! [`Temperature`]: temperature::Temperature
+//! [`Celsius`]: units::Celsius
+//! [`measure()`]: temperature::Temperature::measure
+//! [`start()`]: temperature::Temperature::start
+//! [`read()`]: temperature::Temperature::read
+//! [`set_data_ready_interrupt()`]: temperature::Temperature::set_data_ready_interrupt
+//! [`set_offset()`]: temperature::Temperature::set_offset
+
+use core::convert::Infallible;
+
+use crate::hal::nrf51::TEMP;
+use crate::units::Celsius;
+
+/// An undocumented register which must be cleared before using the
+/// peripheral (nRF51 anomaly 31).
+const TEMP_CALIBRATION: *mut u32 = 0x4000_C504 as *mut u32;
+
+/// The sign bit of the 10-bit value in the TEMP register. The bits above
+/// it aren't set for negative values (nRF51 anomaly 28).
+const SIGN_BIT: u32 = 1 << 9;
+
+/// The on-die temperature sensor.
+pub struct Temperature {
+    temp: TEMP,
+    offset: Celsius,
+}
+
+impl Temperature {
+    /// Takes control of the `TEMP` peripheral.
+    pub fn new(temp: TEMP) -> Temperature {
+        unsafe { core::ptr::write_volatile(TEMP_CALIBRATION, 0) };
+        temp.events_datardy.write(|w| unsafe { w.bits(0) });
+        Temperature {
+            temp,
+            offset: Celsius(0.0),
+        }
+    }
+
+    /// Gives back the `TEMP` peripheral.
+    pub fn free(self) -> TEMP {
+        self.temp.intenclr.write(|w| w.datardy().clear());
+        self.temp
+    }
+
+    /// Returns the calibration offset.
+    pub fn offset(&self) -> Celsius {
+        self.offset
+    }
+
+    /// Sets the calibration offset, which is added to every reading from
+    /// [`read()`] and [`measure()`].
+    ///
+    /// The offset is a difference between temperatures rather than a
+    /// temperature: `Celsius(-2.5)` lowers every reading by 2.5°C.
+    ///
+    /// [`read()`]: temperature::Temperature::read
+    /// [`measure()`]: temperature::Temperature::measure
+    pub fn set_offset(&mut self, offset: Celsius) {
+        self.offset = offset;
+    }
+
+    /// Enables or disables the `TEMP` interrupt when a measurement is
+    /// ready.
+    pub fn set_data_ready_interrupt(&mut self, enabled: bool) {
+        if enabled {
+            self.temp.intenset.write(|w| w.datardy().set());
+        } else {
+            self.temp.intenclr.write(|w| w.datardy().clear());
+        }
+    }
+
+    /// Starts a measurement.
+    pub fn start(&mut self) {
+        self.temp.events_datardy.write(|w| unsafe { w.bits(0) });
+        self.temp.tasks_start.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Returns the result of the measurement started by [`start()`], in
+    /// units of 0.25°C, without the calibration offset.
+    ///
+    /// Returns `WouldBlock` until the result is ready (and forever if no
+    /// measurement was started).
+    ///
+    /// [`start()`]: temperature::Temperature::start
+    pub fn read_raw(&mut self) -> nb::Result<i16, Infallible> {
+        if self.temp.events_datardy.read().bits() == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.temp.events_datardy.write(|w| unsafe { w.bits(0) });
+        /* Read the value before stopping, which clears it (nRF51 anomaly
+         * 29), and stop because the sensor doesn't power down by itself
+         * (anomaly 30) */
+        let value = self.temp.temp.read().bits();
+        self.temp.tasks_stop.write(|w| unsafe { w.bits(1) });
+        let value = if value & SIGN_BIT != 0 {
+            value | !(SIGN_BIT - 1)
+        } else {
+            value
+        };
+        Ok(value as i32 as i16)
+    }
+
+    /// Returns the result of the measurement started by [`start()`].
+    ///
+    /// Returns `WouldBlock` until the result is ready (and forever if no
+    /// measurement was started).
+    ///
+    /// [`start()`]: temperature::Temperature::start
+    pub fn read(&mut self) -> nb::Result<Celsius, Infallible> {
+        let raw = self.read_raw()?;
+        Ok(Celsius(f32::from(raw) * 0.25 + self.offset.0))
+    }
+
+    /// Measures the temperature, waiting for the result.
+    pub fn measure(&mut self) -> Celsius {
+        self.start();
+        match nb::block!(self.read()) {
+            Ok(celsius) => celsius,
+            Err(never) => match never {},
+        }
+    }
+}
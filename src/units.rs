@@ -0,0 +1,142 @@
+//! Physical quantities returned by the sensor drivers.
+//!
+//! Each quantity is a newtype around a number in one fixed unit, so
+//! readings from different sensors can't be mixed up, and the unit doesn't
+//! have to be looked up in a datasheet. The number is public for doing
+//! arithmetic, and there are methods converting to other units.
+//!
+//! Each type implements `Display`, printing the number followed by the
+//! unit's symbol. Formatting options (such as the precision in `{:.1}`)
+//! apply to the number:
+//!
+//! ```ignore
+//! let field = magnetometer.read()?;
+//! write!(tx, "{:.1}\n\r", field.x)?; // "-23.4 µT"
+//! ```
+//!
+//! [`MilliG`] prints as `mg`, the usual symbol for milli-g on
+//! accelerometer datasheets; here it never means milligrams.
+//!
+//! To print plain numbers, for example for a [`Plotter`], use the number
+//! inside.
+//!
+//! [`MilliG`]: units::MilliG
+//! [`Plotter`]: plotter::Plotter
+
+use core::fmt;
+use core::ops::Neg;
+
+/// Standard gravity, in m/s².
+const STANDARD_GRAVITY: f32 = 9.806_65;
+
+/// An acceleration, in thousandths of standard gravity (milli-g).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MilliG(pub i16);
+
+impl MilliG {
+    /// Returns the acceleration in g.
+    pub fn to_g(self) -> f32 {
+        f32::from(self.0) / 1000.0
+    }
+
+    /// Returns the acceleration in m/s².
+    pub fn to_metres_per_second_squared(self) -> f32 {
+        self.to_g() * STANDARD_GRAVITY
+    }
+}
+
+impl From<MilliG> for i16 {
+    fn from(acceleration: MilliG) -> i16 {
+        acceleration.0
+    }
+}
+
+impl From<MilliG> for i32 {
+    fn from(acceleration: MilliG) -> i32 {
+        i32::from(acceleration.0)
+    }
+}
+
+/// Negates the acceleration, saturating at `i16::MAX` milli-g.
+impl Neg for MilliG {
+    type Output = MilliG;
+
+    fn neg(self) -> MilliG {
+        MilliG(self.0.saturating_neg())
+    }
+}
+
+/// Prints the acceleration followed by " mg", meaning milli-g (not
+/// milligrams).
+impl fmt::Display for MilliG {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)?;
+        f.write_str(" mg")
+    }
+}
+
+/// A magnetic field strength (flux density), in microtesla.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct MicroTesla(pub f32);
+
+impl MicroTesla {
+    /// Returns the field strength in gauss.
+    pub fn to_gauss(self) -> f32 {
+        self.0 / 100.0
+    }
+
+    /// Returns the field strength in nanotesla.
+    pub fn to_nanotesla(self) -> f32 {
+        self.0 * 1000.0
+    }
+}
+
+impl From<MicroTesla> for f32 {
+    fn from(field: MicroTesla) -> f32 {
+        field.0
+    }
+}
+
+impl Neg for MicroTesla {
+    type Output = MicroTesla;
+
+    fn neg(self) -> MicroTesla {
+        MicroTesla(-self.0)
+    }
+}
+
+impl fmt::Display for MicroTesla {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)?;
+        f.write_str(" µT")
+    }
+}
+
+/// A temperature, in degrees Celsius.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Celsius(pub f32);
+
+impl Celsius {
+    /// Returns the temperature in degrees Fahrenheit.
+    pub fn to_fahrenheit(self) -> f32 {
+        self.0 * 1.8 + 32.0
+    }
+
+    /// Returns the temperature in kelvin.
+    pub fn to_kelvin(self) -> f32 {
+        self.0 + 273.15
+    }
+}
+
+impl From<Celsius> for f32 {
+    fn from(temperature: Celsius) -> f32 {
+        temperature.0
+    }
+}
+
+impl fmt::Display for Celsius {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)?;
+        f.write_str(" °C")
+    }
+}